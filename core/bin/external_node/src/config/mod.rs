@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     env, fmt,
     num::{NonZeroU32, NonZeroUsize},
     str::FromStr,
@@ -11,6 +12,7 @@ use url::Url;
 use zksync_basic_types::{Address, L1ChainId, L2ChainId};
 use zksync_config::{
     configs::{
+        api::parse_method_concurrency_limits,
         chain::L1BatchCommitDataGeneratorMode,
         consensus::{ConsensusConfig, ConsensusSecrets},
     },
@@ -182,6 +184,10 @@ pub(crate) struct OptionalENConfig {
     /// Maximum response body size in MiBs. Default is 10 MiB.
     #[serde(default = "OptionalENConfig::default_max_response_body_size_mb")]
     pub max_response_body_size_mb: usize,
+    /// Per-method limits on the number of concurrently processed calls, each in the `<method>=<limit>` format
+    /// (e.g., `debug_traceCall=10`). Calls exceeding the limit are rejected with the "server is busy" error.
+    #[serde(default)]
+    method_concurrency_limits: Vec<String>,
 
     // Other API config settings
     /// Interval between polling DB for pubsub (in ms).
@@ -279,6 +285,10 @@ pub(crate) struct OptionalENConfig {
     /// Timeout to wait for the Merkle tree database to run compaction on stalled writes.
     #[serde(default = "OptionalENConfig::default_merkle_tree_stalled_writes_timeout_sec")]
     merkle_tree_stalled_writes_timeout_sec: u64,
+    /// Whether to collect RocksDB statistics for the Merkle tree and report block cache hits and misses as metrics.
+    /// Collecting statistics has a runtime overhead for all RocksDB operations, so it is disabled by default.
+    #[serde(default)]
+    pub merkle_tree_enable_block_cache_stats: bool,
 
     // Postgres config (new parameters)
     /// Threshold in milliseconds for the DB connection lifetime to denote it as long-living and log its details.
@@ -526,6 +536,10 @@ impl OptionalENConfig {
         self.max_response_body_size_mb * BYTES_IN_MEGABYTE
     }
 
+    pub fn method_concurrency_limits(&self) -> anyhow::Result<HashMap<String, usize>> {
+        parse_method_concurrency_limits(&self.method_concurrency_limits)
+    }
+
     pub fn healthcheck_slow_time_limit(&self) -> Option<Duration> {
         self.healthcheck_slow_time_limit_ms
             .map(Duration::from_millis)
@@ -143,6 +143,7 @@ async fn run_tree(
             .merkle_tree_include_indices_and_filters_in_block_cache,
         memtable_capacity: config.optional.merkle_tree_memtable_capacity(),
         stalled_writes_timeout: config.optional.merkle_tree_stalled_writes_timeout(),
+        enable_block_cache_stats: config.optional.merkle_tree_enable_block_cache_stats,
     };
 
     let max_concurrency = config
@@ -507,6 +508,10 @@ async fn run_api(
         mempool_cache_update_task.run(stop_receiver.clone()),
     ));
 
+    let method_concurrency_limits = config
+        .optional
+        .method_concurrency_limits()
+        .context("invalid method concurrency limits")?;
     if components.contains(&Component::HttpApi) {
         let mut builder =
             ApiBuilder::jsonrpsee_backend(config.clone().into(), connection_pool.clone())
@@ -514,6 +519,7 @@ async fn run_api(
                 .with_filter_limit(config.optional.filters_limit)
                 .with_batch_request_size_limit(config.optional.max_batch_request_size)
                 .with_response_body_size_limit(config.optional.max_response_body_size())
+                .with_method_concurrency_limits(method_concurrency_limits.clone())
                 .with_tx_sender(tx_sender.clone())
                 .with_vm_barrier(vm_barrier.clone())
                 .with_sync_state(sync_state.clone())
@@ -541,6 +547,7 @@ async fn run_api(
                 .with_subscriptions_limit(config.optional.subscriptions_limit)
                 .with_batch_request_size_limit(config.optional.max_batch_request_size)
                 .with_response_body_size_limit(config.optional.max_response_body_size())
+                .with_method_concurrency_limits(method_concurrency_limits)
                 .with_polling_interval(config.optional.polling_interval())
                 .with_tx_sender(tx_sender)
                 .with_vm_barrier(vm_barrier)
@@ -1,5 +1,6 @@
-use std::{net::SocketAddr, num::NonZeroU32, time::Duration};
+use std::{collections::HashMap, net::SocketAddr, num::NonZeroU32, time::Duration};
 
+use anyhow::Context as _;
 use serde::Deserialize;
 use zksync_basic_types::{Address, H256};
 
@@ -96,6 +97,11 @@ pub struct Web3JsonRpcConfig {
     /// (additionally to natively bridged tokens).
     #[serde(default)]
     pub whitelisted_tokens_for_aa: Vec<Address>,
+    /// Per-method limits on the number of concurrently processed calls, each in the `<method>=<limit>` format
+    /// (e.g., `debug_traceCall=10`). Calls exceeding the limit are rejected with the "server is busy" error.
+    /// Methods without a limit are not limited.
+    #[serde(default)]
+    pub method_concurrency_limits: Vec<String>,
 }
 
 impl Web3JsonRpcConfig {
@@ -133,6 +139,7 @@ impl Web3JsonRpcConfig {
             mempool_cache_size: Default::default(),
             tree_api_url: None,
             whitelisted_tokens_for_aa: Default::default(),
+            method_concurrency_limits: Default::default(),
         }
     }
 
@@ -220,6 +227,30 @@ impl Web3JsonRpcConfig {
     pub fn mempool_cache_size(&self) -> usize {
         self.mempool_cache_size.unwrap_or(10_000)
     }
+
+    /// Returns parsed per-method concurrency limits.
+    pub fn method_concurrency_limits(&self) -> anyhow::Result<HashMap<String, usize>> {
+        parse_method_concurrency_limits(&self.method_concurrency_limits)
+    }
+}
+
+/// Parses per-method concurrency limits specified in the `<method>=<limit>` format.
+pub fn parse_method_concurrency_limits(
+    limits: &[String],
+) -> anyhow::Result<HashMap<String, usize>> {
+    limits
+        .iter()
+        .map(|limit| {
+            let (method_name, limit_value) = limit.split_once('=').with_context(|| {
+                format!("concurrency limit `{limit}` is not in the `<method>=<limit>` format")
+            })?;
+            let limit_value = limit_value
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid concurrency limit for method `{method_name}`"))?;
+            Ok((method_name.trim().to_owned(), limit_value))
+        })
+        .collect()
 }
 
 #[derive(Debug, Deserialize, Clone, PartialEq)]
@@ -79,6 +79,7 @@ impl Distribution<configs::api::Web3JsonRpcConfig> for EncodeDist {
             mempool_cache_update_interval: self.sample(rng),
             mempool_cache_size: self.sample(rng),
             whitelisted_tokens_for_aa: self.sample_range(rng).map(|_| rng.gen()).collect(),
+            method_concurrency_limits: self.sample_range(rng).map(|_| self.sample(rng)).collect(),
         }
     }
 }
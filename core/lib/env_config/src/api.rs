@@ -91,6 +91,7 @@ mod tests {
                     addr("0x0000000000000000000000000000000000000001"),
                     addr("0x0000000000000000000000000000000000000002"),
                 ],
+                method_concurrency_limits: vec!["eth_call=100".into(), "debug_traceCall=10".into()],
             },
             prometheus: PrometheusConfig {
                 listener_port: 3312,
@@ -136,6 +137,7 @@ mod tests {
             API_WEB3_JSON_RPC_WEBSOCKET_REQUESTS_PER_MINUTE_LIMIT=10
             API_WEB3_JSON_RPC_MEMPOOL_CACHE_SIZE=10000
             API_WEB3_JSON_RPC_MEMPOOL_CACHE_UPDATE_INTERVAL=50
+            API_WEB3_JSON_RPC_METHOD_CONCURRENCY_LIMITS="eth_call=100,debug_traceCall=10"
             API_CONTRACT_VERIFICATION_PORT="3070"
             API_CONTRACT_VERIFICATION_URL="http://127.0.0.1:3070"
             API_WEB3_JSON_RPC_MAX_RESPONSE_BODY_SIZE_MB=10
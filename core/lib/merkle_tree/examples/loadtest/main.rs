@@ -68,6 +68,10 @@ struct Cli {
     /// Enables tree pruning.
     #[arg(long = "prune", conflicts_with = "in_memory")]
     prune: bool,
+    /// Comma-separated numbers of threads to verify tree consistency with (e.g., `1,4,16`), which allows to benchmark
+    /// the speedup of parallel verification. If not specified, consistency is verified once on the global `rayon` thread pool.
+    #[arg(long = "consistency-threads", value_delimiter = ',')]
+    consistency_threads: Vec<usize>,
 }
 
 impl Cli {
@@ -164,12 +168,29 @@ impl Cli {
             tracing::info!("Processed block #{version} in {elapsed:?}, root hash = {root_hash:?}");
         }
 
-        tracing::info!("Verifying tree consistency...");
-        let start = Instant::now();
-        tree.verify_consistency(self.commit_count - 1, false)
-            .expect("tree consistency check failed");
-        let elapsed = start.elapsed();
-        tracing::info!("Verified tree consistency in {elapsed:?}");
+        if self.consistency_threads.is_empty() {
+            tracing::info!("Verifying tree consistency...");
+            let start = Instant::now();
+            tree.verify_consistency(self.commit_count - 1, false)
+                .expect("tree consistency check failed");
+            let elapsed = start.elapsed();
+            tracing::info!("Verified tree consistency in {elapsed:?}");
+        }
+        for &thread_count in &self.consistency_threads {
+            let thread_pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(thread_count)
+                .build()
+                .expect("failed initializing `rayon` thread pool");
+            tracing::info!("Verifying tree consistency with {thread_count} thread(s)...");
+            let start = Instant::now();
+            thread_pool
+                .install(|| tree.verify_consistency(self.commit_count - 1, false))
+                .expect("tree consistency check failed");
+            let elapsed = start.elapsed();
+            tracing::info!(
+                "Verified tree consistency with {thread_count} thread(s) in {elapsed:?}"
+            );
+        }
 
         if let Some((pruner_handle, pruner_thread)) = pruner_handles {
             pruner_handle.abort();
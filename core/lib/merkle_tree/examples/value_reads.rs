@@ -0,0 +1,125 @@
+//! Benchmark for single-key reads from `ZkSyncTreeReader`.
+//!
+//! Compares value-only reads with reads that load and discard additional data (leaf indices or Merkle proofs).
+//! Should be compiled with the release profile, otherwise hashing and other ops would be prohibitively slow.
+
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use tempfile::TempDir;
+use tracing_subscriber::EnvFilter;
+use zksync_merkle_tree::{
+    domain::{ZkSyncTree, ZkSyncTreeReader},
+    Key, TreeEntry, TreeInstruction,
+};
+use zksync_storage::{RocksDB, RocksDBOptions};
+use zksync_types::{AccountTreeId, Address, L1BatchNumber, StorageKey, H256};
+
+/// CLI for benchmarking single-key reads from the Merkle tree.
+#[derive(Debug, Parser)]
+struct Cli {
+    /// Number of L1 batches to populate the tree with.
+    #[arg(name = "batches")]
+    batch_count: u32,
+    /// Number of writes per L1 batch.
+    #[arg(name = "ops")]
+    writes_per_batch: usize,
+    /// Number of reads to perform for each read method.
+    #[arg(long = "reads", default_value = "10000")]
+    read_count: usize,
+    /// Block cache capacity for RocksDB in bytes.
+    #[arg(long = "block-cache")]
+    block_cache: Option<usize>,
+    /// Seed to use in the RNG for reproducibility.
+    #[arg(long = "rng-seed", default_value = "0")]
+    rng_seed: u64,
+}
+
+impl Cli {
+    fn init_logging() {
+        tracing_subscriber::fmt()
+            .pretty()
+            .with_env_filter(EnvFilter::from_default_env())
+            .init();
+    }
+
+    fn run(self) {
+        Self::init_logging();
+        tracing::info!("Launched with options: {self:?}");
+
+        let dir = TempDir::new().expect("failed creating temp dir for RocksDB");
+        tracing::info!(
+            "Created temp dir for RocksDB: {}",
+            dir.path().to_string_lossy()
+        );
+        let db_options = RocksDBOptions {
+            block_cache_capacity: self.block_cache,
+            ..RocksDBOptions::default()
+        };
+        let db = RocksDB::with_options(dir.path(), db_options).unwrap();
+        let mut tree = ZkSyncTree::new_lightweight(db.into());
+        let mut rng = StdRng::seed_from_u64(self.rng_seed);
+
+        let mut keys = Vec::with_capacity(self.batch_count as usize * self.writes_per_batch);
+        for batch_number in 0..self.batch_count {
+            let started_at = Instant::now();
+            let instructions: Vec<_> = (0..self.writes_per_batch)
+                .map(|_| {
+                    let address = Address::from(rng.gen::<[u8; 20]>());
+                    let key = StorageKey::new(AccountTreeId::new(address), H256(rng.gen()));
+                    keys.push(ZkSyncTree::hash_storage_key(&key));
+                    let entry = TreeEntry::new(key, keys.len() as u64, H256(rng.gen()));
+                    TreeInstruction::Write(entry)
+                })
+                .collect();
+            tree.process_l1_batch(&instructions);
+            tree.save();
+            tracing::info!(
+                "Processed L1 batch #{batch_number} in {:?}",
+                started_at.elapsed()
+            );
+        }
+
+        let reader = tree.reader();
+        let l1_batch_number = L1BatchNumber(self.batch_count - 1);
+        let read_keys: Vec<Key> = (0..self.read_count)
+            .map(|_| *keys.choose(&mut rng).expect("no keys in tree"))
+            .collect();
+
+        let value_latency = Self::measure(&read_keys, |key| {
+            reader.value(l1_batch_number, key).unwrap().is_some()
+        });
+        tracing::info!("`value()`: {value_latency:?} per read");
+        let entry_latency = Self::measure(&read_keys, |key| {
+            reader.entry(l1_batch_number, key).unwrap().is_some()
+        });
+        tracing::info!("`entry()`: {entry_latency:?} per read");
+        let proof_latency = Self::measure(&read_keys, |key| {
+            Self::read_with_proof(&reader, l1_batch_number, key)
+        });
+        tracing::info!("`entries_with_proofs()`: {proof_latency:?} per read");
+    }
+
+    fn read_with_proof(
+        reader: &ZkSyncTreeReader,
+        l1_batch_number: L1BatchNumber,
+        key: Key,
+    ) -> bool {
+        let entries = reader.entries_with_proofs(l1_batch_number, &[key]).unwrap();
+        !entries[0].base.is_empty()
+    }
+
+    /// Returns the mean latency of a single read.
+    fn measure(keys: &[Key], mut read: impl FnMut(Key) -> bool) -> Duration {
+        let started_at = Instant::now();
+        for &key in keys {
+            assert!(read(key), "key {key:?} is missing");
+        }
+        started_at.elapsed() / u32::try_from(keys.len().max(1)).unwrap()
+    }
+}
+
+fn main() {
+    Cli::parse().run();
+}
@@ -7,7 +7,7 @@ use rayon::prelude::*;
 use crate::{
     errors::DeserializeError,
     hasher::{HashTree, HasherWithStats},
-    types::{LeafNode, Nibbles, Node, NodeKey, Root},
+    types::{ChildRef, LeafNode, Nibbles, Node, NodeKey, Root},
     Database, Key, MerkleTree, ValueHash,
 };
 
@@ -72,9 +72,16 @@ impl<DB: Database, H: HashTree> MerkleTree<DB, H> {
     /// If `validate_indices` flag is set, it will be checked that indices for all tree leaves are unique
     /// and are sequentially assigned starting from 1.
     ///
+    /// Verification is parallelized across subtrees using `rayon`, so it runs on the current `rayon` thread pool
+    /// (e.g., the global one, or the one [installed](rayon::ThreadPool::install()) by the caller).
+    ///
     /// # Errors
     ///
-    /// Returns an error (the first encountered one if there are multiple).
+    /// Returns an error if there are any inconsistencies. If there are multiple inconsistencies,
+    /// the returned error is deterministic: it corresponds to the node with the least key
+    /// in the depth-first traversal order, regardless of the order in which subtrees are checked.
+    /// The only exception is [`ConsistencyError::DuplicateLeafIndex`], for which the reported key
+    /// depends on the order leaves are visited in.
     pub fn verify_consistency(
         &self,
         version: u64,
@@ -99,18 +106,63 @@ impl<DB: Database, H: HashTree> MerkleTree<DB, H> {
         // much in memory.
         let root_key = Nibbles::EMPTY.with_version(version);
         let leaf_data = validate_indices.then(|| LeafConsistencyData::new(leaf_count));
-        self.validate_node(&root_node, root_key, leaf_data.as_ref())?;
+        self.validate_node(&root_node, root_key, leaf_data.as_ref(), 0)?;
         if let Some(leaf_data) = leaf_data {
             leaf_data.validate_count()?;
         }
         Ok(())
     }
 
+    /// Verifies consistency of the nodes created in the specified tree `version`. Unlike [`Self::verify_consistency()`],
+    /// subtrees not changed in `version` are not traversed; only the presence of their root nodes
+    /// and their hashes are checked. Thus, the cost of this check is proportional to the number of changes
+    /// in the version rather than to the tree size. Leaf indices are not validated.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are any inconsistencies in the checked nodes.
+    pub(crate) fn verify_version_consistency(&self, version: u64) -> Result<(), ConsistencyError> {
+        self.verify_root_node(version, version)
+    }
+
+    /// Verifies the top levels of the tree at the specified `version`, i.e., checks that all children
+    /// of the root node are present in the database and hash to the values recorded in the root.
+    /// Only the root and its children are loaded (i.e., at most 17 nodes), so the cost of this check
+    /// doesn't depend on the tree size.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are any inconsistencies in the checked nodes.
+    pub(crate) fn verify_top_levels(&self, version: u64) -> Result<(), ConsistencyError> {
+        self.verify_root_node(version, u64::MAX)
+    }
+
+    fn verify_root_node(&self, version: u64, min_version: u64) -> Result<(), ConsistencyError> {
+        let manifest = self.db.try_manifest()?;
+        let manifest = manifest.ok_or(ConsistencyError::MissingVersion(version))?;
+        if version >= manifest.version_count {
+            return Err(ConsistencyError::MissingVersion(version));
+        }
+
+        let root = self
+            .db
+            .try_root(version)?
+            .ok_or(ConsistencyError::MissingRoot(version))?;
+        if let Root::Filled { node, .. } = root {
+            let root_key = Nibbles::EMPTY.with_version(version);
+            self.validate_node(&node, root_key, None, min_version)?;
+        }
+        Ok(())
+    }
+
+    /// Validates the subtree rooted at `node` and returns the hash of `node`. Children with versions
+    /// less than `min_version` are loaded and hashed, but their subtrees are not validated.
     fn validate_node(
         &self,
         node: &Node,
         key: NodeKey,
         leaf_data: Option<&LeafConsistencyData>,
+        min_version: u64,
     ) -> Result<ValueHash, ConsistencyError> {
         match node {
             Node::Leaf(leaf) => {
@@ -142,12 +194,8 @@ impl<DB: Database, H: HashTree> MerkleTree<DB, H> {
                     });
                 }
 
-                // `.into_par_iter()` below is the only place where `rayon`-based parallelism
-                // is used in tree verification.
-                let children: Vec<_> = node.children().collect();
-                children
-                    .into_par_iter()
-                    .try_for_each(|(nibble, child_ref)| {
+                let check_child =
+                    |(nibble, child_ref): (u8, &ChildRef)| -> Result<(), ConsistencyError> {
                         let child_key = key
                             .nibbles
                             .push(nibble)
@@ -163,7 +211,12 @@ impl<DB: Database, H: HashTree> MerkleTree<DB, H> {
 
                         // Recursion here is OK; the tree isn't that deep (approximately 8 nibbles for a tree with
                         // approximately 1B entries).
-                        let child_hash = self.validate_node(&child, child_key, leaf_data)?;
+                        let child_hash = if child_ref.version >= min_version {
+                            self.validate_node(&child, child_key, leaf_data, min_version)?
+                        } else {
+                            let level = child_key.nibbles.nibble_count() * 4;
+                            child.hash(&mut HasherWithStats::new(&self.hasher), level)
+                        };
                         if child_hash == child_ref.hash {
                             Ok(())
                         } else {
@@ -174,7 +227,26 @@ impl<DB: Database, H: HashTree> MerkleTree<DB, H> {
                                 actual: child_hash,
                             })
                         }
-                    })?;
+                    };
+
+                if rayon::current_num_threads() == 1 {
+                    // Children are checked in the nibble order, so the first encountered error
+                    // is the one for the least key.
+                    node.children().try_for_each(check_child)?;
+                } else {
+                    // `.into_par_iter()` below is the only place where `rayon`-based parallelism
+                    // is used in tree verification. `find_map_first()` returns the error for the child
+                    // with the least nibble (i.e., the least key), so that the returned error doesn't depend
+                    // on the order in which children were checked, while still skipping the remaining children
+                    // once an error is found.
+                    let children: Vec<_> = node.children().collect();
+                    let err = children
+                        .into_par_iter()
+                        .find_map_first(|child| check_child(child).err());
+                    if let Some(err) = err {
+                        return Err(err);
+                    }
+                }
             }
         }
 
@@ -281,6 +353,7 @@ mod tests {
 
     const FIRST_KEY: Key = U256([0, 0, 0, 0x_dead_beef_0000_0000]);
     const SECOND_KEY: Key = U256([0, 0, 0, 0x_dead_beef_0100_0000]);
+    const THIRD_KEY: Key = U256([0, 0, 0, 0x_1234_0000_0000_0000]);
 
     fn prepare_database() -> PatchSet {
         let mut tree = MerkleTree::new(PatchSet::default());
@@ -318,6 +391,78 @@ mod tests {
         thread_pool.install(|| MerkleTree::new(db).verify_consistency(0, true))
     }
 
+    #[test]
+    fn version_consistency_checks() {
+        let mut tree = MerkleTree::new(prepare_database());
+        tree.extend(vec![TreeEntry::new(THIRD_KEY, 3, H256([3; 32]))]);
+        let mut db = tree.db;
+        MerkleTree::new(&mut db)
+            .verify_version_consistency(0)
+            .unwrap();
+        MerkleTree::new(&mut db)
+            .verify_version_consistency(1)
+            .unwrap();
+        let err = MerkleTree::new(&mut db)
+            .verify_version_consistency(2)
+            .unwrap_err();
+        assert_matches!(err, ConsistencyError::MissingVersion(2));
+
+        // Leaves created in version 0 are not traversed when checking version 1.
+        let leaf_key = db.nodes_mut().find_map(|(key, node)| {
+            (key.version == 0 && matches!(node, Node::Leaf(_))).then(|| *key)
+        });
+        let leaf_key = leaf_key.unwrap();
+        db.remove_node(&leaf_key);
+        MerkleTree::new(&mut db)
+            .verify_version_consistency(1)
+            .unwrap();
+        let err = MerkleTree::new(&mut db)
+            .verify_version_consistency(0)
+            .unwrap_err();
+        assert_matches!(
+            err,
+            ConsistencyError::MissingNode { key, is_leaf: true } if key == leaf_key
+        );
+
+        let leaf_key = db.nodes_mut().find_map(|(key, node)| {
+            (key.version == 1 && matches!(node, Node::Leaf(_))).then(|| *key)
+        });
+        let leaf_key = leaf_key.unwrap();
+        db.remove_node(&leaf_key);
+        let err = MerkleTree::new(&mut db)
+            .verify_version_consistency(1)
+            .unwrap_err();
+        assert_matches!(
+            err,
+            ConsistencyError::MissingNode { key, is_leaf: true } if key == leaf_key
+        );
+    }
+
+    #[test]
+    fn top_level_consistency_checks() {
+        let mut db = prepare_database();
+        MerkleTree::new(&mut db).verify_top_levels(0).unwrap();
+
+        // Nodes below the children of the root are not loaded.
+        let leaf_key = db.nodes_mut().find_map(|(key, node)| {
+            (key.nibbles.nibble_count() > 1 && matches!(node, Node::Leaf(_))).then(|| *key)
+        });
+        let leaf_key = leaf_key.unwrap();
+        db.remove_node(&leaf_key);
+        MerkleTree::new(&mut db).verify_top_levels(0).unwrap();
+
+        let child_key = db
+            .nodes_mut()
+            .find_map(|(key, _)| (key.nibbles.nibble_count() == 1).then(|| *key));
+        let child_key = child_key.unwrap();
+        db.remove_node(&child_key);
+        let err = MerkleTree::new(&mut db).verify_top_levels(0).unwrap_err();
+        assert_matches!(
+            err,
+            ConsistencyError::MissingNode { key, .. } if key == child_key
+        );
+    }
+
     #[test]
     fn missing_version_error() {
         let mut db = prepare_database();
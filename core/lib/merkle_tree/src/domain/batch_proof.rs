@@ -0,0 +1,193 @@
+//! Merkle multiproofs for sets of keys written in an L1 batch.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::ensure;
+use rayon::prelude::*;
+use zksync_crypto::hasher::blake2::Blake2Hasher;
+use zksync_types::{L1BatchNumber, U256};
+
+use super::{par_chunks_per_thread, ZkSyncTreeReader};
+use crate::{
+    getters::load_root,
+    types::{Key, TreeEntry, TreeEntryWithProof, ValueHash, TREE_DEPTH},
+    HashTree, NoVersionError,
+};
+
+/// Hash of a node in the binary Merkle tree included into a [`BatchWriteProof`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchProofNode {
+    /// Height of the node in the binary tree. 0 corresponds to leaves, and 256 to the root.
+    pub height: usize,
+    /// Index of the node among nodes with the same height, i.e. `key >> height` for any key in the node subtree.
+    pub index: Key,
+    /// Hash of the node.
+    pub hash: ValueHash,
+}
+
+/// Merkle proof for a set of keys at a certain tree version in which hashes shared among the Merkle paths
+/// of the keys are deduplicated. Returned by [`ZkSyncTreeReader::batch_write_proof()`].
+///
+/// # Format
+///
+/// The proof treats the tree as a binary Merkle tree of depth 256, in which a node at height `h`
+/// has index `key >> h` for any key in its subtree. The proof consists of:
+///
+/// - Entries for the requested keys. Entries for keys missing from the tree are [empty](TreeEntry::is_empty()).
+/// - Hashes of the nodes adjacent to the paths from the entries to the root, excluding nodes on the path
+///   of another entry (their hashes are computed from the entries) and empty subtrees (their hashes are well-known).
+///   Nodes are ordered by height, then by index.
+///
+/// Thus, the proof is never larger than individual Merkle proofs for all keys, and is substantially smaller
+/// if the keys share path prefixes.
+#[derive(Debug, Clone)]
+pub struct BatchWriteProof {
+    /// Entries for the requested keys in the order of the request.
+    pub entries: Vec<TreeEntry>,
+    /// Hashes of the nodes necessary to restore the tree root hash.
+    pub nodes: Vec<BatchProofNode>,
+}
+
+impl BatchWriteProof {
+    fn new(hasher: &dyn HashTree, entries: Vec<TreeEntryWithProof>) -> Self {
+        let path_nodes: HashSet<_> = entries
+            .iter()
+            .flat_map(|entry| (0..TREE_DEPTH).map(move |height| (height, entry.base.key >> height)))
+            .collect();
+
+        let mut nodes = BTreeMap::new();
+        for entry in &entries {
+            let key = entry.base.key;
+            let full_path = hasher.extend_merkle_path(&entry.merkle_path);
+            for (height, hash) in full_path.enumerate() {
+                let index = (key >> height) ^ U256::one();
+                if hash != hasher.empty_subtree_hash(height)
+                    && !path_nodes.contains(&(height, index))
+                {
+                    nodes.insert((height, index), hash);
+                }
+            }
+        }
+
+        Self {
+            entries: entries.into_iter().map(|entry| entry.base).collect(),
+            nodes: nodes
+                .into_iter()
+                .map(|((height, index), hash)| BatchProofNode {
+                    height,
+                    index,
+                    hash,
+                })
+                .collect(),
+        }
+    }
+
+    /// Verifies this proof against the trusted root hash of the tree.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the proof has no entries, contains conflicting entries or redundant nodes,
+    /// or if the restored root hash doesn't match the trusted one.
+    pub fn verify(&self, trusted_root_hash: ValueHash) -> anyhow::Result<()> {
+        let hasher: &dyn HashTree = &Blake2Hasher;
+        ensure!(!self.entries.is_empty(), "proof has no entries");
+
+        let mut proof_nodes = HashMap::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            ensure!(
+                node.height < TREE_DEPTH,
+                "invalid node height: {}",
+                node.height
+            );
+            let prev_hash = proof_nodes.insert((node.height, node.index), node.hash);
+            ensure!(
+                prev_hash.is_none(),
+                "duplicate node with height {} and index {:#x}",
+                node.height,
+                node.index
+            );
+        }
+
+        let mut level = HashMap::with_capacity(self.entries.len());
+        for entry in &self.entries {
+            ensure!(
+                entry.leaf_index != 0 || entry.value.is_zero(),
+                "invalid missing value specification for key {:#x}: leaf index is zero, but value is non-default",
+                entry.key
+            );
+            let hash = hasher.hash_leaf(&entry.value, entry.leaf_index);
+            let prev_hash = level.insert(entry.key, hash);
+            ensure!(
+                prev_hash.is_none() || prev_hash == Some(hash),
+                "conflicting entries for key {:#x}",
+                entry.key
+            );
+        }
+
+        let mut used_node_count = 0;
+        for height in 0..TREE_DEPTH {
+            let mut parent_level = HashMap::with_capacity(level.len());
+            for (&index, &hash) in &level {
+                let parent_index = index >> 1;
+                if parent_level.contains_key(&parent_index) {
+                    continue; // The parent was computed when processing the sibling node
+                }
+                let sibling_index = index ^ U256::one();
+                let sibling_hash = if let Some(&sibling_hash) = level.get(&sibling_index) {
+                    sibling_hash
+                } else if let Some(&sibling_hash) = proof_nodes.get(&(height, sibling_index)) {
+                    used_node_count += 1;
+                    sibling_hash
+                } else {
+                    hasher.empty_subtree_hash(height)
+                };
+                let parent_hash = if index.bit(0) {
+                    hasher.hash_branch(&sibling_hash, &hash)
+                } else {
+                    hasher.hash_branch(&hash, &sibling_hash)
+                };
+                parent_level.insert(parent_index, parent_hash);
+            }
+            level = parent_level;
+        }
+
+        ensure!(
+            used_node_count == proof_nodes.len(),
+            "proof contains {} redundant nodes",
+            proof_nodes.len() - used_node_count
+        );
+        let root_hash = level[&U256::zero()];
+        ensure!(root_hash == trusted_root_hash, "root hash mismatch");
+        Ok(())
+    }
+}
+
+impl<H: HashTree + Clone> ZkSyncTreeReader<H> {
+    /// Creates a [proof](BatchWriteProof) for the specified keys (e.g., ones written in the L1 batch) at the specified
+    /// L1 batch. Unlike independent Merkle proofs returned by [`Self::entries_with_proofs()`], hashes shared
+    /// among the Merkle paths of the keys are included into the proof only once.
+    ///
+    /// Merkle paths for the keys are loaded in parallel using the current `rayon` thread pool.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree version for the L1 batch is missing.
+    pub fn batch_write_proof(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        written_keys: &[Key],
+    ) -> Result<BatchWriteProof, NoVersionError> {
+        let version = u64::from(l1_batch_number.0);
+        if written_keys.is_empty() {
+            load_root(&self.0.db, version)?;
+            return Ok(BatchWriteProof::new(&self.0.hasher, vec![]));
+        }
+
+        let _permit = self.acquire_proof_permit();
+        let entries: Vec<_> = par_chunks_per_thread(written_keys)
+            .map(|chunk| self.0.entries_with_proofs(version, chunk))
+            .collect::<Result<_, _>>()?;
+        let entries = entries.into_iter().flatten().collect();
+        Ok(BatchWriteProof::new(&self.0.hasher, entries))
+    }
+}
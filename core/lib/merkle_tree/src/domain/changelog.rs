@@ -0,0 +1,62 @@
+//! Per-batch changelogs of tree leaves.
+
+use zksync_types::L1BatchNumber;
+
+use super::ZkSyncTreeReader;
+use crate::{
+    getters::load_root,
+    types::{Key, ValueHash},
+    HashTree, NoVersionError,
+};
+
+/// Change of a single tree leaf in an L1 batch. Returned by [`ZkSyncTreeReader::batch_changelog()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeafChange {
+    /// Hashed key of the leaf.
+    pub key: Key,
+    /// Value of the leaf after the L1 batch.
+    pub value: ValueHash,
+    /// Enumeration index of the leaf.
+    pub leaf_index: u64,
+    /// Whether the leaf was inserted in the L1 batch (as opposed to being updated).
+    pub is_initial_write: bool,
+}
+
+impl<H: HashTree + Clone> ZkSyncTreeReader<H> {
+    /// Returns changes of tree leaves in the specified L1 batch, i.e., leaves that were inserted or changed
+    /// their value in the batch. No-op updates are omitted. Changes are ordered by enumeration index,
+    /// so initial writes come last in the order of insertion. This allows maintaining an external index
+    /// of the tree state without running a tree.
+    ///
+    /// The changelog is computed by comparing tree nodes created in the L1 batch with the previous tree version,
+    /// so its cost is proportional to the number of changes rather than to the tree size. Changes are collected
+    /// in RAM; for very large batches, this is comparable to the size of the batch witness.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree version for the L1 batch or the preceding L1 batch is missing
+    /// (e.g., was pruned).
+    pub fn batch_changelog(
+        &self,
+        l1_batch_number: L1BatchNumber,
+    ) -> Result<Vec<LeafChange>, NoVersionError> {
+        let version = u64::from(l1_batch_number.0);
+        let prev_version = version.checked_sub(1);
+        let prev_leaf_count = match prev_version {
+            Some(prev_version) => load_root(&self.0.db, prev_version)?.leaf_count(),
+            None => 0,
+        };
+        let entries = self.0.changed_entries(prev_version, version)?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| LeafChange {
+                key: entry.key,
+                value: entry.value,
+                leaf_index: entry.leaf_index,
+                // Enumeration indices are assigned sequentially, so leaves inserted in the batch
+                // are exactly the leaves with indices exceeding the previous leaf count.
+                is_initial_write: entry.leaf_index > prev_leaf_count,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,88 @@
+//! Concurrency limiting for proof computations in [`ZkSyncTreeReader`].
+
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+
+use super::ZkSyncTreeReader;
+use crate::{metrics::TREE_METRICS, HashTree};
+
+/// Counting semaphore limiting the number of concurrent proof computations.
+#[derive(Debug)]
+pub(super) struct ConcurrencyLimiter {
+    max_concurrency: usize,
+    active_count: Mutex<usize>,
+    condvar: Condvar,
+}
+
+impl ConcurrencyLimiter {
+    fn new(max_concurrency: usize) -> Self {
+        Self {
+            max_concurrency,
+            active_count: Mutex::new(0),
+            condvar: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self) -> ConcurrencyPermit<'_> {
+        let mut active_count = self
+            .active_count
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if *active_count >= self.max_concurrency {
+            TREE_METRICS.queued_proof_requests.inc_by(1);
+            active_count = self
+                .condvar
+                .wait_while(active_count, |count| *count >= self.max_concurrency)
+                .unwrap_or_else(PoisonError::into_inner);
+            TREE_METRICS.queued_proof_requests.dec_by(1);
+        }
+        *active_count += 1;
+        ConcurrencyPermit(self)
+    }
+}
+
+/// Permit for a single proof computation; released on drop.
+#[derive(Debug)]
+#[must_use = "permit is released when dropped"]
+pub(super) struct ConcurrencyPermit<'a>(&'a ConcurrencyLimiter);
+
+impl Drop for ConcurrencyPermit<'_> {
+    fn drop(&mut self) {
+        let mut active_count = self
+            .0
+            .active_count
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *active_count -= 1;
+        drop(active_count);
+        self.0.condvar.notify_one();
+    }
+}
+
+impl<H: HashTree + Clone> ZkSyncTreeReader<H> {
+    /// Limits the number of concurrent proof computations (e.g., [`Self::entries_with_proofs()`]) and other
+    /// operations traversing Merkle paths (e.g., [`Self::key_depth()`] or [`Self::rebuild_witness()`])
+    /// performed via this reader and its clones to `max_concurrency`. Excess requests block until one
+    /// of the running computations completes, rather than competing for RocksDB and the `rayon` thread pool.
+    /// The number of queued requests is reported as a metric.
+    ///
+    /// Requests issued from within a `rayon` thread pool are not limited. Blocking pool threads could deadlock
+    /// if the running computations need these threads to make progress, and the number of such requests
+    /// is already bounded by the pool size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_concurrency` is zero.
+    #[must_use]
+    pub fn with_concurrency_limit(mut self, max_concurrency: usize) -> Self {
+        assert!(max_concurrency > 0, "concurrency limit must be positive");
+        self.1 = Some(Arc::new(ConcurrencyLimiter::new(max_concurrency)));
+        self
+    }
+
+    pub(super) fn acquire_proof_permit(&self) -> Option<ConcurrencyPermit<'_>> {
+        if rayon::current_thread_index().is_some() {
+            return None;
+        }
+        Some(self.1.as_deref()?.acquire())
+    }
+}
@@ -0,0 +1,39 @@
+//! Disk usage statistics for the tree.
+
+use std::collections::BTreeMap;
+
+use zksync_storage::db::NamedColumnFamily;
+
+use super::ZkSyncTreeReader;
+use crate::{HashTree, MerkleTreeColumnFamily};
+
+/// Disk usage of the tree returned by [`ZkSyncTreeReader::disk_usage()`]. Sizes are based on the total size
+/// of SST files reported by RocksDB, so they don't include the write-ahead log and data not flushed from memtables.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeDiskUsage {
+    /// Total size of all tree column families in bytes.
+    pub total_bytes: u64,
+    /// Sizes of the individual column families in bytes, keyed by the column family name.
+    pub column_family_bytes: BTreeMap<&'static str, u64>,
+    /// Number of tree versions retained in the database, i.e. not removed by pruning.
+    pub live_version_count: u64,
+}
+
+impl<H: HashTree + Clone> ZkSyncTreeReader<H> {
+    /// Returns approximate disk usage of the tree, which can be used for capacity planning. Sizes are obtained
+    /// using RocksDB properties, so this method is cheap and doesn't traverse tree data or files.
+    pub fn disk_usage(&self) -> TreeDiskUsage {
+        let column_family_bytes: BTreeMap<_, _> = MerkleTreeColumnFamily::ALL
+            .iter()
+            .map(|&cf| (cf.name(), self.0.db.sst_size(cf)))
+            .collect();
+        let live_version_count = self.0.latest_version().map_or(0, |latest_version| {
+            latest_version + 1 - self.0.first_retained_version(latest_version)
+        });
+        TreeDiskUsage {
+            total_bytes: column_family_bytes.values().sum(),
+            column_family_bytes,
+            live_version_count,
+        }
+    }
+}
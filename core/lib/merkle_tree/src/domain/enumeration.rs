@@ -0,0 +1,87 @@
+//! Validation of leaf enumeration indices.
+
+use zksync_types::L1BatchNumber;
+
+use super::ZkSyncTreeReader;
+use crate::{getters::load_root, HashTree, NoVersionError};
+
+/// Error returned by [`ZkSyncTreeReader::verify_enumeration_indices()`].
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum EnumerationError {
+    /// Tree version for the requested L1 batch is missing.
+    #[error(transparent)]
+    NoVersion(#[from] NoVersionError),
+    /// No leaf has the specified enumeration index, although it's in `1..=leaf_count`.
+    #[error("enumeration index {index} is not assigned to any leaf")]
+    Missing {
+        /// Missing enumeration index.
+        index: u64,
+    },
+    /// Enumeration index is assigned to multiple leaves.
+    #[error("enumeration index {index} is assigned to multiple leaves")]
+    Duplicate {
+        /// Duplicate enumeration index.
+        index: u64,
+    },
+    /// Enumeration index is outside the `1..=leaf_count` range.
+    #[error("enumeration index {index} is outside the valid range 1..={leaf_count}")]
+    OutOfRange {
+        /// Offending enumeration index.
+        index: u64,
+        /// Number of leaves specified at the tree root.
+        leaf_count: u64,
+    },
+}
+
+impl<H: HashTree + Clone> ZkSyncTreeReader<H> {
+    /// Verifies that enumeration indices of the tree leaves at the specified L1 batch form
+    /// the contiguous range `1..=leaf_count` without duplicates, where `leaf_count` is the number
+    /// of leaves specified at the tree root.
+    ///
+    /// Unlike [`ZkSyncTree::verify_consistency()`](super::ZkSyncTree::verify_consistency()), this method
+    /// doesn't check node hashes, so it catches index assignment errors even if the tree is otherwise
+    /// self-consistent. It traverses all tree leaves and keeps their indices in RAM, so it is slow
+    /// for large trees and should be used out-of-band.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree version for the L1 batch is missing, or if indices are invalid.
+    /// In the latter case, the error reports the least offending index.
+    pub fn verify_enumeration_indices(
+        &self,
+        l1_batch_number: L1BatchNumber,
+    ) -> Result<(), EnumerationError> {
+        let version = u64::from(l1_batch_number.0);
+        let root = load_root(&self.0.db, version)?;
+        let leaf_count = root.leaf_count();
+
+        let mut indices: Vec<_> = self
+            .leaves_from_root(root)
+            .map(|entry| entry.leaf_index)
+            .collect();
+        indices.sort_unstable();
+
+        let mut expected_index = 1;
+        for index in indices {
+            if index == 0 {
+                return Err(EnumerationError::OutOfRange { index, leaf_count });
+            } else if index < expected_index {
+                return Err(EnumerationError::Duplicate { index });
+            } else if expected_index > leaf_count {
+                return Err(EnumerationError::OutOfRange { index, leaf_count });
+            } else if index > expected_index {
+                return Err(EnumerationError::Missing {
+                    index: expected_index,
+                });
+            }
+            expected_index += 1;
+        }
+        if expected_index <= leaf_count {
+            return Err(EnumerationError::Missing {
+                index: expected_index,
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,2718 @@
+//! Tying the Merkle tree implementation to the problem domain.
+
+use std::{
+    any::Any,
+    collections::{hash_map, HashMap, HashSet},
+    fmt, mem,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+use tokio::sync::{broadcast, oneshot};
+use zksync_crypto::hasher::blake2::Blake2Hasher;
+use zksync_prover_interface::inputs::{PrepareBasicCircuitsJob, StorageLogMetadata};
+use zksync_types::{L1BatchNumber, StorageKey};
+
+use self::concurrency::ConcurrencyLimiter;
+pub use self::{
+    batch_proof::{BatchProofNode, BatchWriteProof},
+    changelog::LeafChange,
+    disk_usage::TreeDiskUsage,
+    enumeration::EnumerationError,
+    profile::TreeWorkloadProfile,
+    proof_bundle::{verify_proofs_consistent, ProofInconsistencyError},
+    range_commitment::{RangeCommitment, RangeCommitmentError},
+    serialization::decode_entries_with_proofs,
+    snapshot::{ExportError, ImportError},
+    structure::TreeStructureStats,
+};
+use crate::{
+    errors::ErrorContext,
+    getters::load_root,
+    metrics::{TreeModeLabel, TREE_METRICS},
+    storage::{PatchSet, Patched, RocksDBWrapper},
+    types::{
+        Key, Root, TreeEntry, TreeEntryWithProof, TreeInstruction, TreeLogEntry,
+        TreeLogEntryWithProof, ValueHash, TREE_DEPTH,
+    },
+    BackgroundSaveError, BlockOutput, BlockOutputWithProofs, ConsistencyError, EmptyTreeError,
+    GenesisAlreadyExists, HashTree, LatestEntriesError, MerkleTree, MerkleTreePruner,
+    NoVersionError, OrphanReport, PendingLimitExceeded, ProcessL1BatchError, ProofWithStateError,
+    RebuildWitnessError, RootNotFoundError, SnapshotError, TreeOpenError, WitnessTooLarge,
+};
+
+mod batch_proof;
+mod changelog;
+mod concurrency;
+mod disk_usage;
+mod enumeration;
+mod profile;
+mod proof_bundle;
+mod range_commitment;
+mod serialization;
+mod snapshot;
+mod structure;
+#[cfg(test)]
+mod tests;
+
+/// Metadata for the current tree state.
+#[derive(Debug, Clone)]
+pub struct TreeMetadata {
+    /// Current root hash of the tree.
+    pub root_hash: ValueHash,
+    /// 1-based index of the next leaf to be inserted in the tree.
+    pub rollup_last_leaf_index: u64,
+    /// Witness information. As with `repeated_writes`, no-op updates will be omitted from Merkle paths.
+    pub witness: Option<PrepareBasicCircuitsJob>,
+    /// Number of read instructions in the processed L1 batch.
+    pub read_count: usize,
+    /// Number of write instructions in the processed L1 batch, including no-op updates.
+    pub write_count: usize,
+    /// Number of no-op writes in the processed L1 batch, i.e. updates that don't change the value of the key.
+    /// Such writes are included into [`Self::total_writes()`], but are omitted from the witness.
+    pub noop_writes: usize,
+    /// Log entries for all instructions in the processed L1 batch (including reads) in the order of instructions.
+    /// Only set for trees [with reads](ZkSyncTree::new_lightweight_with_reads()).
+    pub logs: Option<Vec<TreeLogEntry>>,
+    /// Statistics for the witness. Only set if the witness is built when processing the L1 batch (i.e., in the full
+    /// mode without [lazy witnesses](ZkSyncTree::set_lazy_witnesses())); also set if the witness is
+    /// [flushed incrementally](ZkSyncTree::set_witness_flush()), in which case statistics cover all flushed chunks.
+    pub witness_stats: Option<WitnessStats>,
+}
+
+impl TreeMetadata {
+    /// Returns the total number of write instructions in the processed L1 batch, including no-op updates.
+    /// This is the same as [`Self::write_count`].
+    pub fn total_writes(&self) -> usize {
+        self.write_count
+    }
+}
+
+/// Statistics for the witness of an L1 batch, which can be used for prover capacity planning. Path lengths
+/// are measured as the number of Merkle path hashes emitted into the witness, i.e. after omitting hashes
+/// shared with the first path in the witness (or in a flushed witness chunk). No-op updates are not counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WitnessStats {
+    /// Number of entries (storage logs) in the witness.
+    pub entries: usize,
+    /// Total number of Merkle path hashes in the witness.
+    pub total_hashes: usize,
+    /// Maximum Merkle path length among entries. 0 if the witness is empty.
+    pub max_path_len: usize,
+    /// Minimum Merkle path length among entries. 0 if the witness is empty.
+    pub min_path_len: usize,
+}
+
+impl WitnessStats {
+    fn push_path(&mut self, path_len: usize) {
+        self.min_path_len = if self.entries == 0 {
+            path_len
+        } else {
+            self.min_path_len.min(path_len)
+        };
+        self.max_path_len = self.max_path_len.max(path_len);
+        self.total_hashes += path_len;
+        self.entries += 1;
+    }
+}
+
+/// Aggregated metadata for a range of L1 batches returned by [`ZkSyncTree::process_l1_batches_aggregated()`].
+#[derive(Debug, Clone)]
+pub struct AggregatedMetadata {
+    /// Root hash of the tree after processing the last batch.
+    pub root_hash: ValueHash,
+    /// Number of leaves in the tree after processing the last batch.
+    pub leaf_count: u64,
+    /// Writes in all processed batches deduplicated by key. If a key is written multiple times,
+    /// only the last write is retained; writes are ordered by the first write of the corresponding key.
+    pub writes: Vec<TreeEntry<StorageKey>>,
+}
+
+/// Proofs needed to show that a key is absent from the tree and to locate the position at which
+/// it would be inserted. Returned by [`ZkSyncTreeReader::insertion_proof()`].
+#[derive(Debug)]
+pub struct InsertionProof {
+    /// Proof for the requested key itself. If the key is absent from the tree, the entry is empty.
+    pub entry: TreeEntryWithProof,
+    /// Entry with the greatest key less than the requested key, or `None` if there is no such entry.
+    pub low_neighbor: Option<TreeEntryWithProof>,
+    /// Entry with the least key greater than the requested key, or `None` if there is no such entry.
+    pub high_neighbor: Option<TreeEntryWithProof>,
+}
+
+/// Recent API latency signal shared between API servers and the tree maintenance logic
+/// (e.g., wrapped in an `Arc`). API servers should periodically [update](Self::set()) the signal,
+/// e.g. with a moving average or a high percentile of the request latency.
+#[derive(Debug, Default)]
+pub struct LatencySignal {
+    latency_micros: AtomicU64,
+}
+
+impl LatencySignal {
+    /// Updates the signal with the specified latency.
+    pub fn set(&self, latency: Duration) {
+        let latency_micros = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        self.latency_micros.store(latency_micros, Ordering::Relaxed);
+    }
+
+    /// Returns the latest latency reported to this signal.
+    pub fn get(&self) -> Duration {
+        Duration::from_micros(self.latency_micros.load(Ordering::Relaxed))
+    }
+}
+
+/// Policy deciding whether [`ZkSyncTree::save()`] should be deferred based on the API latency.
+pub trait SaveDeferralPolicy: fmt::Debug + Send + Sync {
+    /// Checks whether a save should be deferred. `unsaved_batch_count` is the number of L1 batches
+    /// processed by the tree, but not yet saved to RocksDB.
+    fn should_defer(&self, latency: Duration, unsaved_batch_count: usize) -> bool;
+}
+
+/// [`SaveDeferralPolicy`] deferring saves while the API latency exceeds a threshold, unless too many
+/// L1 batches are accumulated in memory.
+#[derive(Debug, Clone, Copy)]
+pub struct LatencyThresholdPolicy {
+    /// Latency threshold above which saves are deferred.
+    pub latency_threshold: Duration,
+    /// Maximum number of unsaved L1 batches. Once this number is reached, saves are no longer deferred.
+    pub max_unsaved_batches: usize,
+}
+
+impl SaveDeferralPolicy for LatencyThresholdPolicy {
+    fn should_defer(&self, latency: Duration, unsaved_batch_count: usize) -> bool {
+        latency > self.latency_threshold && unsaved_batch_count < self.max_unsaved_batches
+    }
+}
+
+/// Consumer of witness chunks produced by [`ZkSyncTree`] when [witness flushing](ZkSyncTree::set_witness_flush())
+/// is enabled.
+pub trait WitnessSink: fmt::Debug + Send + Sync {
+    /// Consumes the next witness chunk for the specified L1 batch. Chunks for a batch are consumed
+    /// in the order of storage logs in the batch.
+    fn consume(&mut self, l1_batch_number: L1BatchNumber, chunk: PrepareBasicCircuitsJob);
+}
+
+/// Event emitted by [`ZkSyncTree`] after processing an L1 batch. Can be received via [`ZkSyncTree::subscribe_batches()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BatchEvent {
+    /// Number of the processed L1 batch.
+    pub l1_batch_number: L1BatchNumber,
+    /// Root hash of the tree after processing the batch.
+    pub root_hash: ValueHash,
+    /// Number of initial writes in the batch.
+    pub initial_writes: usize,
+    /// Number of repeated writes in the batch, not counting no-op updates.
+    pub repeated_writes: usize,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum TreeMode {
+    Lightweight,
+    /// Lightweight mode that resolves read instructions instead of dropping them.
+    LightweightWithReads,
+    Full,
+}
+
+/// L1 batch being processed in the full mode.
+#[derive(Debug)]
+struct FullBatch {
+    l1_batch_number: L1BatchNumber,
+    starting_leaf_count: u64,
+    starting_root_hash: ValueHash,
+    read_count: usize,
+    write_count: usize,
+    /// Batch instructions with hashed keys.
+    instructions: Vec<TreeInstruction>,
+}
+
+/// Dedicated thread pool for a [`ZkSyncTree`] together with its usage policy.
+#[derive(Debug)]
+struct TreeThreadPool {
+    pool: Option<Arc<ThreadPool>>,
+    min_batch_size: usize,
+}
+
+impl TreeThreadPool {
+    /// Returns the dedicated thread pool if it should be used to process a batch of the specified size.
+    fn for_batch(&self, batch_size: usize) -> Option<&ThreadPool> {
+        self.pool
+            .as_deref()
+            .filter(|_| batch_size >= self.min_batch_size)
+    }
+}
+
+/// Tree changes being saved on a background thread.
+#[derive(Debug)]
+struct BackgroundSave {
+    /// Receives the outcome of saving; a panic while writing changes is transmitted as an error.
+    result: mpsc::Receiver<thread::Result<()>>,
+    /// Resolves (with an error) once saving terminates, either normally or by panicking.
+    done: oneshot::Receiver<()>,
+}
+
+impl BackgroundSave {
+    /// Returns the save handle together with a closure writing `patch` to `db`. The closure doesn't depend
+    /// on the tree hasher, so it can be sent to another thread regardless of the hasher type.
+    fn new(db: RocksDBWrapper, patch: Arc<PatchSet>) -> (Self, impl FnOnce() + Send + 'static) {
+        let (result_sender, result) = mpsc::channel();
+        let (done_sender, done) = oneshot::channel();
+        let task = SaveTask {
+            db,
+            patch,
+            result_sender,
+            done_sender,
+        };
+        (Self { result, done }, move || task.run())
+    }
+}
+
+/// Task writing a frozen patch to RocksDB.
+///
+/// The patch must be released before the outcome of saving is signalled; otherwise, the tree
+/// may fail to [unfreeze](Patched::unfreeze()) the patch after a failed save. Fields are dropped in
+/// the declaration order, so this also holds if the task is dropped without running (e.g., if it's cancelled).
+struct SaveTask {
+    db: RocksDBWrapper,
+    patch: Arc<PatchSet>,
+    result_sender: mpsc::Sender<thread::Result<()>>,
+    done_sender: oneshot::Sender<()>,
+}
+
+impl SaveTask {
+    fn run(self) {
+        let Self {
+            db,
+            patch,
+            result_sender,
+            done_sender,
+        } = self;
+        let save_result = panic::catch_unwind(AssertUnwindSafe(|| db.write_patch(&patch)));
+        drop(patch);
+        result_sender.send(save_result).ok();
+        drop(done_sender); // signals that saving has terminated
+    }
+}
+
+/// Domain-specific wrapper of the Merkle tree.
+///
+/// This wrapper will accumulate changes introduced by [`Self::process_l1_batch()`],
+/// [`Self::process_l1_batches()`] and [`Self::revert_logs()`] in RAM without saving them
+/// to RocksDB. The accumulated changes can be saved to RocksDB via [`Self::save()`]
+/// or discarded via [`Self::reset()`].
+///
+/// The tree is generic over the hasher `H`, which defaults to Blake2 used by all constructors except
+/// [`Self::new_with_hasher()`]. The hasher is statically dispatched, so it doesn't incur overhead on hot paths.
+#[derive(Debug)]
+pub struct ZkSyncTree<H = Blake2Hasher> {
+    tree: MerkleTree<Patched<RocksDBWrapper>, H>,
+    thread_pool: TreeThreadPool,
+    mode: TreeMode,
+    save_deferral_policy: Option<Box<dyn SaveDeferralPolicy>>,
+    lazy_witnesses: bool,
+    witness_flush: Option<(usize, Box<dyn WitnessSink>)>,
+    witness_without_paths: bool,
+    max_pending_versions: usize,
+    max_witness_bytes: usize,
+    batch_events: broadcast::Sender<BatchEvent>,
+    background_save: Option<BackgroundSave>,
+}
+
+impl ZkSyncTree {
+    /// Capacity of the channel for [`BatchEvent`]s.
+    pub const BATCH_EVENTS_CAPACITY: usize = 128;
+    /// Default minimum batch size for which the [dedicated thread pool](Self::use_dedicated_thread_pool())
+    /// is used. The dedicated pool is used for all batches by default; a non-zero threshold should be chosen
+    /// based on benchmarks for the specific workload.
+    pub const DEFAULT_THREAD_POOL_MIN_BATCH_SIZE: usize = 0;
+
+    fn create_thread_pool(thread_count: usize) -> ThreadPool {
+        ThreadPoolBuilder::new()
+            .thread_name(|idx| format!("new-merkle-tree-{idx}"))
+            .num_threads(thread_count)
+            .build()
+            .expect("failed initializing `rayon` thread pool")
+    }
+
+    /// Returns metadata based on `storage_logs` generated by the genesis L1 batch. This does not
+    /// create a persistent tree; use [`Self::process_genesis_batch_persisted()`] for that.
+    pub fn process_genesis_batch(storage_logs: &[TreeInstruction<StorageKey>]) -> BlockOutput {
+        tracing::debug!(
+            "Computing genesis batch in an in-memory tree; this doesn't check whether genesis \
+             was already processed by a persistent tree"
+        );
+        let kvs = Self::filter_write_instructions(storage_logs);
+        tracing::info!(
+            "Creating Merkle tree for genesis batch with {instr_count} writes",
+            instr_count = kvs.len()
+        );
+
+        let kvs: Vec<_> = kvs
+            .iter()
+            .map(|instr| instr.map_key(Self::hash_storage_key))
+            .collect();
+
+        let mut in_memory_tree = MerkleTree::new(PatchSet::default());
+        let output = in_memory_tree.extend(kvs);
+
+        tracing::info!(
+            "Processed genesis batch; root hash is {root_hash}, {leaf_count} leaves in total",
+            root_hash = output.root_hash,
+            leaf_count = output.leaf_count
+        );
+        output
+    }
+
+    /// Parallel version of [`Self::process_genesis_batch()`]. Tree operations are executed on a dedicated `rayon`
+    /// thread pool with the specified number of threads, similarly to [`Self::process_l1_batch()`] with
+    /// a [dedicated thread pool](Self::use_dedicated_thread_pool()). This speeds up processing large genesis batches
+    /// (e.g., for forked states). The returned output is identical to the one of `process_genesis_batch()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread pool cannot be created.
+    pub fn process_genesis_batch_parallel(
+        storage_logs: &[TreeInstruction<StorageKey>],
+        thread_count: usize,
+    ) -> BlockOutput {
+        let thread_pool = Self::create_thread_pool(thread_count);
+        thread_pool.install(|| Self::process_genesis_batch(storage_logs))
+    }
+
+    /// Chunked version of [`Self::process_genesis_batch()`]. Write instructions are inserted into the in-memory tree
+    /// in chunks of `chunk_size` writes, and tree nodes replaced by each chunk are removed immediately. Thus, peak RAM
+    /// consumption is bounded by the size of the resulting tree rather than by the size of all nodes created when
+    /// inserting all writes at once. The returned output is identical to the one of `process_genesis_batch()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    pub fn process_genesis_batch_chunked(
+        storage_logs: &[TreeInstruction<StorageKey>],
+        chunk_size: usize,
+    ) -> BlockOutput {
+        assert!(chunk_size > 0, "chunk size must be positive");
+        let kvs = Self::filter_write_instructions(storage_logs);
+        if kvs.len() <= chunk_size {
+            return Self::process_genesis_batch(storage_logs);
+        }
+        tracing::info!(
+            "Creating Merkle tree for genesis batch with {instr_count} writes in chunks of {chunk_size} writes",
+            instr_count = kvs.len()
+        );
+
+        let mut db = PatchSet::default();
+        let mut logs = Vec::with_capacity(kvs.len());
+        let mut output = None;
+        for chunk in kvs.chunks(chunk_size) {
+            let chunk: Vec<_> = chunk
+                .iter()
+                .map(|instr| instr.map_key(Self::hash_storage_key))
+                .collect();
+            let chunk_output = MerkleTree::new(&mut db).extend(chunk);
+            logs.extend_from_slice(&chunk_output.logs);
+            output = Some(chunk_output);
+
+            // Only the latest tree version is necessary to insert the following chunks.
+            let (mut pruner, _handle) = MerkleTreePruner::new(&mut db, 0);
+            while pruner.run_once().is_some() {
+                // Continue pruning until all stale nodes are removed
+            }
+        }
+        let output = BlockOutput {
+            logs,
+            ..output.expect("no chunks processed")
+        };
+
+        tracing::info!(
+            "Processed genesis batch; root hash is {root_hash}, {leaf_count} leaves in total",
+            root_hash = output.root_hash,
+            leaf_count = output.leaf_count
+        );
+        output
+    }
+
+    /// Hashes a storage key to obtain the corresponding tree key. This is the mapping used by the tree
+    /// when processing storage logs, so it should be used to obtain keys for [`ZkSyncTreeReader`] methods.
+    pub fn hash_storage_key(key: &StorageKey) -> Key {
+        key.hashed_key_u256()
+    }
+
+    /// Creates a tree with the full processing mode.
+    pub fn new(db: RocksDBWrapper) -> Self {
+        Self::new_with_mode(db, TreeMode::Full, Blake2Hasher)
+    }
+
+    /// Creates a tree with the full processing mode, checking the integrity of the latest tree version first.
+    /// Unlike [`Self::new()`], this recomputes hashes of the root children from the data stored in RocksDB
+    /// and compares them with the hashes recorded in the root node. This allows detecting corruption
+    /// (e.g., after an unclean shutdown) before the tree produces incorrect proofs. The check only loads
+    /// top tree levels, so it is cheap, but doesn't detect corruption in deeper levels;
+    /// use [`Self::try_verify_consistency()`] for a full check.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the check fails.
+    pub fn new_checked(db: RocksDBWrapper) -> Result<Self, TreeOpenError> {
+        let this = Self::new(db);
+        if let Some(version) = this.tree.latest_version() {
+            this.tree
+                .verify_top_levels(version)
+                .map_err(|err| ConsistencyError {
+                    version,
+                    description: err.to_string(),
+                })?;
+        }
+        Ok(this)
+    }
+
+    /// Creates a tree with the lightweight processing mode.
+    pub fn new_lightweight(db: RocksDBWrapper) -> Self {
+        Self::new_with_mode(db, TreeMode::Lightweight, Blake2Hasher)
+    }
+
+    /// Creates a tree with the lightweight processing mode that additionally resolves read instructions.
+    /// Like in the lightweight mode, no Merkle paths are computed and the witness is not produced, but
+    /// the returned [`TreeMetadata`] contains [log entries](TreeMetadata::logs) for all instructions,
+    /// including reads. This is cheaper than the full mode if only the classification of reads and writes
+    /// is needed (e.g., for generating access lists).
+    pub fn new_lightweight_with_reads(db: RocksDBWrapper) -> Self {
+        Self::new_with_mode(db, TreeMode::LightweightWithReads, Blake2Hasher)
+    }
+}
+
+impl<H: HashTree + Clone> ZkSyncTree<H> {
+    /// Creates a tree with the full processing mode and the specified hasher. Unlike other constructors,
+    /// which use the Blake2 hasher, this allows experimenting with alternative tree hashing. The hasher is used
+    /// for all tree operations, including computing empty subtree hashes in witnesses, and is shared
+    /// with [readers](Self::reader()) of the tree.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the hasher doesn't match the one used in the database.
+    pub fn new_with_hasher(db: RocksDBWrapper, hasher: H) -> Self {
+        Self::new_with_mode(db, TreeMode::Full, hasher)
+    }
+
+    fn new_with_mode(db: RocksDBWrapper, mode: TreeMode, hasher: H) -> Self {
+        Self {
+            tree: MerkleTree::with_hasher(Patched::new(db), hasher),
+            thread_pool: TreeThreadPool {
+                pool: None,
+                min_batch_size: ZkSyncTree::DEFAULT_THREAD_POOL_MIN_BATCH_SIZE,
+            },
+            mode,
+            save_deferral_policy: None,
+            lazy_witnesses: false,
+            witness_flush: None,
+            witness_without_paths: false,
+            max_pending_versions: usize::MAX,
+            max_witness_bytes: usize::MAX,
+            batch_events: broadcast::channel(ZkSyncTree::BATCH_EVENTS_CAPACITY).0,
+            background_save: None,
+        }
+    }
+
+    /// Returns a readonly handle to the tree. The handle **does not** see uncommitted changes to the tree,
+    /// only ones flushed to RocksDB.
+    pub fn reader(&self) -> ZkSyncTreeReader<H> {
+        let db = self.tree.db.inner().clone();
+        ZkSyncTreeReader(MerkleTree::with_hasher(db, self.tree.hasher.clone()), None)
+    }
+
+    /// Creates a [snapshot](ZkSyncTreeSnapshot) pinned to the latest tree version flushed to RocksDB.
+    /// Unsaved changes in this tree are not visible in the snapshot.
+    pub fn snapshot(&self) -> ZkSyncTreeSnapshot<H> {
+        self.reader().snapshot()
+    }
+
+    /// Samples RocksDB block cache hits and misses and reports them as metrics.
+    /// See [`ZkSyncTreeReader::report_block_cache_stats()`] for details.
+    pub fn report_block_cache_stats(&self) {
+        if let Some(stats) = self.tree.db.inner().block_cache_stats() {
+            TREE_METRICS.observe_block_cache_stats(stats);
+        }
+    }
+
+    /// Sets the chunk size for multi-get operations. The requested keys will be split
+    /// into chunks of this size and requested in parallel using `rayon`. Setting chunk size
+    /// to a large value (e.g., `usize::MAX`) will effectively disable parallelism.
+    ///
+    /// Returns the previously set chunk size, so that the setting can be restored after a bulk operation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    pub fn set_multi_get_chunk_size(&mut self, chunk_size: usize) -> usize {
+        assert!(chunk_size > 0, "Multi-get chunk size must be positive");
+        self.tree
+            .db
+            .inner_mut()
+            .set_multi_get_chunk_size(chunk_size)
+    }
+
+    /// Signals that the tree should use a dedicated `rayon` thread pool for parallel operations
+    /// (for now, hash computations).
+    ///
+    /// If `thread_count` is 0, the default number of threads will be used; see `rayon` docs
+    /// for details.
+    pub fn use_dedicated_thread_pool(&mut self, thread_count: usize) {
+        self.thread_pool.pool = Some(Arc::new(ZkSyncTree::create_thread_pool(thread_count)));
+    }
+
+    /// Sets an externally provided `rayon` thread pool to be used for parallel operations instead of
+    /// a [dedicated thread pool](Self::use_dedicated_thread_pool()). The pool can be shared among multiple trees
+    /// (or other components) to cap the total CPU usage. The pool is used in the same way as the dedicated one;
+    /// e.g., it's only used for L1 batches with at least the [minimum size](Self::set_thread_pool_min_batch_size()).
+    pub fn set_thread_pool(&mut self, pool: Arc<ThreadPool>) {
+        self.thread_pool.pool = Some(pool);
+    }
+
+    /// Sets the minimum number of instructions in an L1 batch for which the [dedicated thread pool](Self::use_dedicated_thread_pool())
+    /// is used. Smaller batches are processed on the calling thread (parallel operations, if any, use the global `rayon` pool),
+    /// since for them, dispatching work to the dedicated pool can take longer than the work itself.
+    /// Setting this to 0 makes the tree always use the dedicated pool. The default value is
+    /// [`Self::DEFAULT_THREAD_POOL_MIN_BATCH_SIZE`].
+    pub fn set_thread_pool_min_batch_size(&mut self, min_batch_size: usize) {
+        self.thread_pool.min_batch_size = min_batch_size;
+    }
+
+    /// Enables or disables lazy witness computation. This only has effect in the full processing mode.
+    ///
+    /// If enabled, [`Self::process_l1_batch()`] does not build a witness (i.e., [`TreeMetadata::witness`]
+    /// is `None`). Instead, instructions for the processed L1 batch (with hashed keys, values and leaf indices)
+    /// are persisted in RocksDB on [`Self::save()`] atomically with tree changes, so that the witness
+    /// can be reconstructed later via [`ZkSyncTreeReader::reconstruct_witness()`]. Reconstruction additionally
+    /// requires the tree versions for the L1 batch and the preceding one to be present (i.e., not pruned).
+    ///
+    /// # Stored data
+    ///
+    /// For each L1 batch, a single row keyed by the tree version is stored in the `witness_inputs` column family.
+    /// The row contains all instructions for the batch in the processing order: hashed keys for reads (33 bytes each),
+    /// and hashed keys, values and leaf indices for writes (~70 bytes each). Thus, the stored data is an order
+    /// of magnitude smaller than the witness (which contains a Merkle path for each instruction).
+    /// Rows are removed when the corresponding versions are [reverted](Self::revert_logs()) (once the revert is saved)
+    /// or their witnesses can no longer be reconstructed because of [pruning](crate::MerkleTreePruner).
+    pub fn set_lazy_witnesses(&mut self, enabled: bool) {
+        self.lazy_witnesses = enabled;
+    }
+
+    /// Enables incremental witness emission in the full processing mode, which bounds the peak RAM
+    /// consumption for witnesses of large L1 batches. After every `flush_every` storage logs added
+    /// to the witness, the accumulated witness chunk is handed over to `sink` and the buffer is reset.
+    /// Once all logs in a batch are processed, the remaining chunk is handed over as well, even if it's empty,
+    /// so the last chunk for each batch contains less than `flush_every` logs. Since the witness is streamed out,
+    /// [`TreeMetadata::witness`] returned by [`Self::process_l1_batch()`] is `None`.
+    ///
+    /// No-op updates (i.e., ones writing the same value) are omitted from witnesses and thus
+    /// are not counted towards `flush_every`. Each chunk contains full Merkle path for its first log,
+    /// and its next enumeration index is the same as for the entire batch witness.
+    ///
+    /// This has no effect if [lazy witnesses](Self::set_lazy_witnesses()) are enabled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `flush_every` is zero.
+    pub fn set_witness_flush(&mut self, flush_every: usize, sink: impl WitnessSink + 'static) {
+        assert!(flush_every > 0, "witness flush interval must be positive");
+        self.witness_flush = Some((flush_every, Box::new(sink)));
+    }
+
+    /// Enables or disables omitting Merkle paths from witnesses produced in the full processing mode.
+    /// If enabled, [`StorageLogMetadata::merkle_paths`] in produced witnesses are empty, which drastically
+    /// reduces witness size; other log metadata (e.g., whether a log is a write or a first write) is populated as usual.
+    /// This applies to witnesses returned from [`Self::process_l1_batch()`] and ones emitted
+    /// with [witness flushing](Self::set_witness_flush()).
+    ///
+    /// **Important.** Witnesses without Merkle paths cannot be proven; they must only be used by consumers
+    /// that don't need proofs (e.g., for dry-run validation of storage logs classification).
+    /// Witnesses [reconstructed](ZkSyncTreeReader::reconstruct_witness()) from lazy witness inputs
+    /// always contain Merkle paths.
+    pub fn set_witness_without_paths(&mut self, enabled: bool) {
+        self.witness_without_paths = enabled;
+    }
+
+    /// Sets the maximum number of unsaved tree versions (i.e., L1 batches processed since the last [`Self::save()`])
+    /// kept in RAM. Once this limit is reached, [`Self::process_l1_batch()`] flushes the accumulated changes
+    /// to RocksDB before processing the next L1 batch, while [`Self::try_process_l1_batch()`] returns an error.
+    /// By default, the number of unsaved versions is unlimited.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is zero.
+    pub fn set_max_pending_versions(&mut self, max: usize) {
+        assert!(
+            max > 0,
+            "Maximum number of pending versions must be positive"
+        );
+        self.max_pending_versions = max;
+    }
+
+    /// Sets the maximum size of a witness produced in the full mode, in bytes. The size is measured incrementally
+    /// while the witness is built (approximately as the size of its `bincode` serialization), so that an oversized
+    /// witness is never fully constructed. The limit is only enforced by [`Self::try_process_l1_batch()`],
+    /// which returns an error and leaves the tree unchanged if the limit is exceeded; other methods processing
+    /// L1 batches (e.g., [`Self::process_l1_batch()`]) ignore it.
+    ///
+    /// If witnesses are [flushed incrementally](Self::set_witness_flush()), the limit applies to each flushed chunk.
+    /// The limit doesn't apply to [lazy witnesses](Self::set_lazy_witnesses()). By default, the witness size
+    /// is unlimited.
+    pub fn set_max_witness_bytes(&mut self, max_bytes: usize) {
+        self.max_witness_bytes = max_bytes;
+    }
+
+    /// Subscribes to events emitted after processing each L1 batch.
+    ///
+    /// The channel is bounded by [`Self::BATCH_EVENTS_CAPACITY`]; the tree never blocks on sending events.
+    /// If a subscriber falls behind by more than the capacity, the oldest events are dropped for it,
+    /// and the subscriber receives [`broadcast::error::RecvError::Lagged`] with the number of dropped events.
+    /// Events are emitted when batches are processed, not when they are saved, so a subscriber may observe
+    /// events for batches that are later [reverted](Self::revert_logs()) or [reset](Self::reset()).
+    pub fn subscribe_batches(&self) -> broadcast::Receiver<BatchEvent> {
+        self.batch_events.subscribe()
+    }
+
+    /// Sets the policy used by [`Self::should_defer_save()`]. By default, saves are never deferred.
+    pub fn set_save_deferral_policy(&mut self, policy: impl SaveDeferralPolicy + 'static) {
+        self.save_deferral_policy = Some(Box::new(policy));
+    }
+
+    /// Checks whether saving the tree should be deferred given the provided API latency signal.
+    /// The decision is delegated to the [policy](Self::set_save_deferral_policy()); the tree
+    /// doesn't defer saves on its own, so the caller is responsible for calling [`Self::save()`]
+    /// once this method returns `false`.
+    pub fn should_defer_save(&self, latency_signal: &LatencySignal) -> bool {
+        let Some(policy) = &self.save_deferral_policy else {
+            return false;
+        };
+        let unsaved_batch_count = self.tree.db.patched_versions().len();
+        unsaved_batch_count > 0 && policy.should_defer(latency_signal.get(), unsaved_batch_count)
+    }
+
+    /// Returns the current root hash of this tree.
+    pub fn root_hash(&self) -> ValueHash {
+        self.tree.latest_root_hash()
+    }
+
+    /// Checks whether this tree is empty.
+    pub fn is_empty(&self) -> bool {
+        let Some(version) = self.tree.latest_version() else {
+            return true;
+        };
+        self.tree
+            .root(version)
+            .map_or(true, |root| matches!(root, Root::Empty))
+    }
+
+    /// Returns the next L1 batch number that should be processed by the tree.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn next_l1_batch_number(&self) -> L1BatchNumber {
+        let number = self.tree.latest_version().map_or(0, |version| {
+            u32::try_from(version + 1).expect("integer overflow for L1 batch number")
+        });
+        L1BatchNumber(number)
+    }
+
+    /// Returns the latest L1 batch processed by the tree, or `None` if the tree has no versions.
+    /// Unlike [`Self::is_empty()`], this only depends on the number of tree versions and not on their contents.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn latest_l1_batch_number(&self) -> Option<L1BatchNumber> {
+        let version = self.tree.latest_version()?;
+        let number = u32::try_from(version).expect("integer overflow for L1 batch number");
+        Some(L1BatchNumber(number))
+    }
+
+    /// Returns the oldest L1 batch for which the tree state is retained, or `None` if the tree has no versions.
+    /// Older tree versions are removed by the [pruner](crate::MerkleTreePruner); if the tree is not pruned,
+    /// this is L1 batch #0.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn oldest_retained_l1_batch_number(&self) -> Option<L1BatchNumber> {
+        let latest_version = self.tree.latest_version()?;
+        let version = self.tree.first_retained_version(latest_version);
+        let number = u32::try_from(version).expect("integer overflow for L1 batch number");
+        Some(L1BatchNumber(number))
+    }
+
+    /// Estimates the amount of RAM (in bytes) occupied by changes accumulated since the last [save](Self::save()).
+    /// This covers the pending tree patch (including one being [saved in the background](Self::begin_save()), if any)
+    /// and [lazy witness](Self::set_lazy_witnesses()) inputs.
+    ///
+    /// The estimate is based on allocated rather than used capacity of the underlying collections, so it should
+    /// be treated as an approximate upper bound (allocator overhead is not accounted for). It scales linearly
+    /// with the number of pending tree nodes, so it can be polled to decide when to call [`Self::save()`]
+    /// (e.g., during long recovery runs). Computing the estimate iterates over all pending nodes.
+    pub fn estimated_memory_usage(&self) -> usize {
+        self.tree.db.estimated_memory_usage()
+    }
+
+    /// Verifies tree consistency. `l1_batch_number` specifies the version of the tree
+    /// to be checked, expressed as the number of latest L1 batch applied to the tree.
+    ///
+    /// Verification is parallelized across subtrees; it uses the [dedicated thread pool](Self::use_dedicated_thread_pool())
+    /// if one is configured.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an inconsistency is detected. Use [`Self::try_verify_consistency()`] to get an error instead.
+    pub fn verify_consistency(&self, l1_batch_number: L1BatchNumber) {
+        self.try_verify_consistency(l1_batch_number)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    /// Non-panicking version of [`Self::verify_consistency()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an inconsistency is detected, including if the tree version is missing.
+    pub fn try_verify_consistency(
+        &self,
+        l1_batch_number: L1BatchNumber,
+    ) -> Result<(), ConsistencyError> {
+        let version = u64::from(l1_batch_number.0);
+        let result = if let Some(thread_pool) = &self.thread_pool.pool {
+            thread_pool.install(|| self.tree.verify_consistency(version, true))
+        } else {
+            self.tree.verify_consistency(version, true)
+        };
+        result.map_err(|err| ConsistencyError {
+            version,
+            description: err.to_string(),
+        })
+    }
+
+    /// Verifies consistency of `depth` most recent tree versions up to and including `last_l1_batch`
+    /// (or all versions up to `last_l1_batch` if there are fewer of them). This is a cheap alternative
+    /// to [`Self::try_verify_consistency()`] for routine checks: for each version, only nodes created in this version
+    /// are traversed. For these nodes, it's checked that all their children are present and have the expected hashes,
+    /// so that the root hash of each version is correctly chained to the previous versions.
+    ///
+    /// Versions are checked starting from the latest one. Verification is parallelized across subtrees;
+    /// it uses the [dedicated thread pool](Self::use_dedicated_thread_pool()) if one is configured.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for the first detected inconsistency, including if one of the checked versions is missing.
+    pub fn verify_recent_consistency(
+        &self,
+        last_l1_batch: L1BatchNumber,
+        depth: u32,
+    ) -> Result<(), ConsistencyError> {
+        let last_version = u64::from(last_l1_batch.0);
+        let first_version = (last_version + 1).saturating_sub(depth.into());
+        let verify = || {
+            (first_version..=last_version)
+                .rev()
+                .try_for_each(|version| {
+                    self.tree
+                        .verify_version_consistency(version)
+                        .map_err(|err| ConsistencyError {
+                            version,
+                            description: err.to_string(),
+                        })
+                })
+        };
+        if let Some(thread_pool) = &self.thread_pool.pool {
+            thread_pool.install(verify)
+        } else {
+            verify()
+        }
+    }
+
+    /// Processes the genesis L1 batch in this tree. Like with [`Self::process_l1_batch()`], changes
+    /// are not persisted until [`Self::save()`] is called.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree already contains the genesis L1 batch (saved or not),
+    /// or more generally, if the tree is not empty (e.g., it was recovered from a snapshot).
+    pub fn process_genesis_batch_persisted(
+        &mut self,
+        storage_logs: &[TreeInstruction<StorageKey>],
+    ) -> Result<TreeMetadata, GenesisAlreadyExists> {
+        if let Some(latest_version) = self.tree.latest_version() {
+            return Err(GenesisAlreadyExists {
+                version_count: latest_version + 1,
+            });
+        }
+        Ok(self.process_l1_batch(storage_logs))
+    }
+
+    /// Processes an iterator of storage logs comprising a single L1 batch.
+    ///
+    /// If the [limit on unsaved versions](Self::set_max_pending_versions()) is reached, the tree is saved
+    /// before processing the batch. The [witness size limit](Self::set_max_witness_bytes()) is ignored;
+    /// use [`Self::try_process_l1_batch()`] to enforce it.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn process_l1_batch(
+        &mut self,
+        storage_logs: &[TreeInstruction<StorageKey>],
+    ) -> TreeMetadata {
+        self.save_if_pending_limit_reached();
+        self.process_l1_batch_inner(storage_logs, usize::MAX)
+            .expect("witness size is unlimited")
+    }
+
+    /// Processes multiple consecutive L1 batches. Returns metadata for each batch in the same order as the batches;
+    /// the result is the same as if [`Self::process_l1_batch()`] was called for each batch. Like with `process_l1_batch()`,
+    /// changes are not persisted until [`Self::save()`] is called.
+    ///
+    /// If the [dedicated thread pool](Self::use_dedicated_thread_pool()) is configured, all batches are processed
+    /// within a single pool installation, regardless of batch sizes.
+    pub fn process_l1_batches(
+        &mut self,
+        batches: &[&[TreeInstruction<StorageKey>]],
+    ) -> Vec<TreeMetadata> {
+        let process_batches = |this: &mut Self| {
+            batches
+                .iter()
+                .map(|storage_logs| this.process_l1_batch(storage_logs))
+                .collect()
+        };
+
+        // Take the pool so that it's not installed again for each batch.
+        if let Some(thread_pool) = self.thread_pool.pool.take() {
+            let metadata = thread_pool.install(|| process_batches(self));
+            self.thread_pool.pool = Some(thread_pool);
+            metadata
+        } else {
+            process_batches(self)
+        }
+    }
+
+    /// Processes multiple L1 batches in sequence and returns aggregated metadata: the final root hash and leaf count,
+    /// and writes deduplicated by key. Each batch still creates a separate tree version, exactly as if
+    /// [`Self::process_l1_batch()`] was called for each batch; however, per-batch metadata (e.g., witnesses
+    /// in the full mode) is discarded as soon as the batch is processed.
+    pub fn process_l1_batches_aggregated(
+        &mut self,
+        batches: &[&[TreeInstruction<StorageKey>]],
+    ) -> AggregatedMetadata {
+        let mut write_indices = HashMap::new();
+        let mut writes = vec![];
+        for &storage_logs in batches {
+            self.process_l1_batch(storage_logs);
+            for entry in Self::filter_write_instructions(storage_logs) {
+                match write_indices.entry(entry.key) {
+                    hash_map::Entry::Occupied(occupied) => writes[*occupied.get()] = entry,
+                    hash_map::Entry::Vacant(vacant) => {
+                        vacant.insert(writes.len());
+                        writes.push(entry);
+                    }
+                }
+            }
+        }
+
+        AggregatedMetadata {
+            root_hash: self.tree.latest_root_hash(),
+            leaf_count: self.tree.latest_root().leaf_count(),
+            writes,
+        }
+    }
+
+    fn save_if_pending_limit_reached(&mut self) {
+        let pending_version_count = self.tree.db.patched_versions().len();
+        if pending_version_count >= self.max_pending_versions {
+            tracing::warn!(
+                "Merkle tree has {pending_version_count} unsaved versions, which reaches the limit \
+                 ({max}); saving the tree",
+                max = self.max_pending_versions
+            );
+            self.save();
+        }
+    }
+
+    /// Applies raw writes comprising a single L1 batch and returns the resulting root hash. Each write
+    /// is a tuple of a hashed key, value and enumeration index of the key. This can be used to reconstruct
+    /// the tree state from persisted initial and repeated writes without the original storage logs.
+    ///
+    /// Writes are applied in the same way as write instructions in [`Self::process_l1_batch()`]:
+    /// the provided enumeration index is assigned to newly inserted keys, and is ignored for updated keys
+    /// (they retain the index assigned on insertion). Thus, to reproduce the tree state, indices of initial writes
+    /// must be consecutive, starting from the current number of leaves in the tree plus 1.
+    /// Like with `process_l1_batch()`, changes are not persisted until [`Self::save()`] is called,
+    /// and the tree is saved if the [limit on unsaved versions](Self::set_max_pending_versions()) is reached.
+    pub fn apply_writes(&mut self, writes: &[(Key, ValueHash, u64)]) -> ValueHash {
+        self.save_if_pending_limit_reached();
+
+        let l1_batch_number = self.next_l1_batch_number();
+        tracing::info!(
+            "Extending Merkle tree with batch #{l1_batch_number} with {write_count} raw writes",
+            write_count = writes.len()
+        );
+        let entries: Vec<_> = writes
+            .iter()
+            .map(|&(key, value, leaf_index)| TreeEntry::new(key, leaf_index, value))
+            .collect();
+        let output = if let Some(thread_pool) = self.thread_pool.for_batch(entries.len()) {
+            thread_pool.install(|| self.tree.extend(entries.clone()))
+        } else {
+            self.tree.extend(entries.clone())
+        };
+        let writes = output.logs.iter().copied().zip(&entries);
+        self.emit_batch_event(l1_batch_number, output.root_hash, writes);
+
+        tracing::info!(
+            "Processed batch #{l1_batch_number}; root hash is {root_hash}, \
+             {leaf_count} leaves in total",
+            root_hash = output.root_hash,
+            leaf_count = output.leaf_count,
+        );
+        output.root_hash
+    }
+
+    /// Processes an iterator of storage logs comprising a single L1 batch. Unlike [`Self::process_l1_batch()`],
+    /// this method never saves the tree implicitly.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the [limit on unsaved versions](Self::set_max_pending_versions()) is reached;
+    /// in this case, the tree is not modified. Also returns an error if the witness for the batch exceeds
+    /// the [size limit](Self::set_max_witness_bytes()). In this case, the batch is reverted before returning
+    /// the error, so the tree is not modified either (although witness chunks may have been already
+    /// [flushed](Self::set_witness_flush()) for the batch).
+    pub fn try_process_l1_batch(
+        &mut self,
+        storage_logs: &[TreeInstruction<StorageKey>],
+    ) -> Result<TreeMetadata, ProcessL1BatchError> {
+        let pending_version_count = self.tree.db.patched_versions().len();
+        if pending_version_count >= self.max_pending_versions {
+            let err = PendingLimitExceeded {
+                pending_version_count,
+                max_pending_versions: self.max_pending_versions,
+            };
+            return Err(err.into());
+        }
+        Ok(self.process_l1_batch_inner(storage_logs, self.max_witness_bytes)?)
+    }
+
+    /// Processes an L1 batch in the full mode and returns proofs for all keys written in the batch
+    /// at the resulting tree version, in addition to the batch metadata. Proofs are ordered by the first
+    /// write of the corresponding key in `storage_logs`; each key is included once, including keys
+    /// with no-op updates. Like with other processed data, keys in the proofs are hashed.
+    ///
+    /// Proofs are built from the nodes of the new tree version that are still held in memory,
+    /// so no RocksDB I/O is necessary for the written keys. Still, this requires an additional
+    /// traversal of the Merkle paths for the written keys, comparable to building the witness,
+    /// and the returned proofs can take significant RAM for large batches.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tree is not in the full mode.
+    pub fn process_l1_batch_full_with_entry_proofs(
+        &mut self,
+        storage_logs: &[TreeInstruction<StorageKey>],
+    ) -> (TreeMetadata, Vec<TreeEntryWithProof>) {
+        assert!(
+            matches!(self.mode, TreeMode::Full),
+            "Entry proofs can only be produced by a tree in the full mode"
+        );
+        let metadata = self.process_l1_batch(storage_logs);
+        let version = self
+            .tree
+            .latest_version()
+            .expect("tree has no versions after processing an L1 batch");
+
+        let mut written_keys = HashSet::new();
+        let keys: Vec<_> = storage_logs
+            .iter()
+            .filter_map(|instruction| match instruction {
+                TreeInstruction::Write(entry) => Some(ZkSyncTree::hash_storage_key(&entry.key)),
+                TreeInstruction::Read(_) => None,
+            })
+            .filter(|key| written_keys.insert(*key))
+            .collect();
+        let proofs = if let Some(thread_pool) = self.thread_pool.for_batch(keys.len()) {
+            thread_pool.install(|| self.tree.entries_with_proofs(version, &keys))
+        } else {
+            self.tree.entries_with_proofs(version, &keys)
+        };
+        let proofs = proofs.expect("just processed tree version is missing");
+        (metadata, proofs)
+    }
+
+    fn process_l1_batch_inner(
+        &mut self,
+        storage_logs: &[TreeInstruction<StorageKey>],
+        max_witness_bytes: usize,
+    ) -> Result<TreeMetadata, WitnessTooLarge> {
+        let mode_label = match self.mode {
+            TreeMode::Full => TreeModeLabel::Full,
+            TreeMode::Lightweight => TreeModeLabel::Lightweight,
+            TreeMode::LightweightWithReads => TreeModeLabel::LightweightWithReads,
+        };
+        TREE_METRICS.batch_instruction_count[&mode_label].observe(storage_logs.len());
+
+        match self.mode {
+            TreeMode::Full => self.process_l1_batch_full(storage_logs, max_witness_bytes),
+            TreeMode::Lightweight => Ok(self.process_l1_batch_lightweight(storage_logs)),
+            TreeMode::LightweightWithReads => {
+                Ok(self.process_l1_batch_lightweight_with_reads(storage_logs))
+            }
+        }
+    }
+
+    fn process_l1_batch_full(
+        &mut self,
+        instructions: &[TreeInstruction<StorageKey>],
+        max_witness_bytes: usize,
+    ) -> Result<TreeMetadata, WitnessTooLarge> {
+        let batch = self.prepare_full_batch(instructions);
+        let l1_batch_number = batch.l1_batch_number;
+        if self.lazy_witnesses {
+            return Ok(self.process_l1_batch_with_lazy_witness(l1_batch_number, batch.instructions));
+        }
+
+        tracing::info!(
+            "Extending Merkle tree with batch #{l1_batch_number} with {instr_count} ops ({read_count} reads, \
+             {write_count} writes) in full mode",
+            instr_count = instructions.len(),
+            read_count = batch.read_count,
+            write_count = batch.write_count
+        );
+
+        let output = self.extend_with_proofs(&batch.instructions);
+        let witness = if let Some((flush_every, sink)) = &mut self.witness_flush {
+            let mut consume_chunk = |chunk| sink.consume(l1_batch_number, chunk);
+            let last_chunk = build_witness(
+                &self.tree.hasher,
+                batch.starting_leaf_count,
+                &output,
+                &batch.instructions,
+                !self.witness_without_paths,
+                max_witness_bytes,
+                Some((*flush_every, &mut consume_chunk)),
+            );
+            last_chunk.map(|(chunk, stats)| {
+                consume_chunk(chunk);
+                (None, stats)
+            })
+        } else {
+            let witness = build_witness(
+                &self.tree.hasher,
+                batch.starting_leaf_count,
+                &output,
+                &batch.instructions,
+                !self.witness_without_paths,
+                max_witness_bytes,
+                None,
+            );
+            witness.map(|(witness, stats)| (Some(witness), stats))
+        };
+        let (witness, witness_stats) = match witness {
+            Ok(witness) => witness,
+            Err(err) => {
+                tracing::warn!(
+                    "Reverting batch #{l1_batch_number} since its witness is too large: {err}"
+                );
+                self.tree
+                    .truncate_recent_versions(u64::from(l1_batch_number.0));
+                return Err(err);
+            }
+        };
+
+        let metadata = self.finish_full_batch(&batch, &output);
+        Ok(TreeMetadata {
+            witness,
+            witness_stats: Some(witness_stats),
+            ..metadata
+        })
+    }
+
+    /// Computes batch-level data shared by all ways to process an L1 batch in the full mode
+    /// and reports instruction metrics.
+    fn prepare_full_batch(&self, instructions: &[TreeInstruction<StorageKey>]) -> FullBatch {
+        let read_count = instructions
+            .iter()
+            .filter(|instr| matches!(instr, TreeInstruction::Read(_)))
+            .count();
+        let write_count = instructions.len() - read_count;
+        TREE_METRICS.read_instructions.inc_by(read_count as u64);
+        TREE_METRICS.write_instructions.inc_by(write_count as u64);
+
+        FullBatch {
+            l1_batch_number: self.next_l1_batch_number(),
+            starting_leaf_count: self.tree.latest_root().leaf_count(),
+            starting_root_hash: self.tree.latest_root_hash(),
+            read_count,
+            write_count,
+            instructions: instructions
+                .iter()
+                .map(|instr| instr.map_key(ZkSyncTree::hash_storage_key))
+                .collect(),
+        }
+    }
+
+    /// Emits a [`BatchEvent`] for a batch processed in the full mode and returns its metadata
+    /// without the witness.
+    fn finish_full_batch(
+        &mut self,
+        batch: &FullBatch,
+        output: &BlockOutputWithProofs,
+    ) -> TreeMetadata {
+        let l1_batch_number = batch.l1_batch_number;
+        let root_hash = output.root_hash().unwrap_or(batch.starting_root_hash);
+        let writes =
+            output
+                .logs
+                .iter()
+                .zip(&batch.instructions)
+                .filter_map(|(log, instruction)| match instruction {
+                    TreeInstruction::Write(entry) => Some((log.base, entry)),
+                    TreeInstruction::Read(_) => None,
+                });
+        let noop_writes = count_noop_writes(writes.clone());
+        self.emit_batch_event(l1_batch_number, root_hash, writes);
+
+        tracing::info!(
+            "Processed batch #{l1_batch_number}; root hash is {root_hash}, \
+             {leaf_count} leaves in total",
+            leaf_count = output.leaf_count,
+        );
+
+        TreeMetadata {
+            root_hash,
+            rollup_last_leaf_index: output.leaf_count + 1,
+            witness: None,
+            read_count: batch.read_count,
+            write_count: batch.write_count,
+            noop_writes,
+            logs: None,
+            witness_stats: None,
+        }
+    }
+
+    /// Processes an L1 batch in the full mode, handing over each log of the batch witness to `sink` as soon
+    /// as it's built instead of accumulating the witness in memory. This allows to serialize large witnesses
+    /// incrementally (e.g., to object storage) and reduces peak RAM usage. The returned metadata has no witness.
+    ///
+    /// Unlike in [`PrepareBasicCircuitsJob`], each log has a full Merkle path (i.e., logs are the same as ones
+    /// returned by [`PrepareBasicCircuitsJob::into_merkle_paths()`]), unless
+    /// [witnesses without paths](Self::set_witness_without_paths()) are enabled. No-op updates are omitted.
+    /// [Lazy witnesses](Self::set_lazy_witnesses()), [witness flushing](Self::set_witness_flush())
+    /// and the [witness size limit](Self::set_max_witness_bytes()) do not apply to this method.
+    ///
+    /// Like [`Self::process_l1_batch()`], the tree is saved before processing the batch
+    /// if the [limit on unsaved versions](Self::set_max_pending_versions()) is reached.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tree is not in the full mode.
+    pub fn process_l1_batch_full_streaming(
+        &mut self,
+        instructions: &[TreeInstruction<StorageKey>],
+        mut sink: impl FnMut(StorageLogMetadata),
+    ) -> TreeMetadata {
+        assert!(
+            matches!(self.mode, TreeMode::Full),
+            "Witnesses can only be streamed by a tree in the full mode"
+        );
+        self.save_if_pending_limit_reached();
+
+        TREE_METRICS.batch_instruction_count[&TreeModeLabel::Full].observe(instructions.len());
+        let batch = self.prepare_full_batch(instructions);
+        tracing::info!(
+            "Extending Merkle tree with batch #{l1_batch_number} with {instr_count} ops ({read_count} reads, \
+             {write_count} writes) in full mode with streamed witness",
+            l1_batch_number = batch.l1_batch_number,
+            instr_count = instructions.len(),
+            read_count = batch.read_count,
+            write_count = batch.write_count
+        );
+
+        let output = self.extend_with_proofs(&batch.instructions);
+        let include_paths = !self.witness_without_paths;
+        for (log, instruction) in output.logs.iter().zip(&batch.instructions) {
+            if let Some(log) = witness_log(&self.tree.hasher, log, instruction, include_paths) {
+                sink(log);
+            }
+        }
+        self.finish_full_batch(&batch, &output)
+    }
+
+    fn extend_with_proofs(&mut self, instructions: &[TreeInstruction]) -> BlockOutputWithProofs {
+        if let Some(thread_pool) = self.thread_pool.for_batch(instructions.len()) {
+            thread_pool.install(|| self.tree.extend_with_proofs(instructions.to_vec()))
+        } else {
+            self.tree.extend_with_proofs(instructions.to_vec())
+        }
+    }
+
+    fn process_l1_batch_with_lazy_witness(
+        &mut self,
+        l1_batch_number: L1BatchNumber,
+        instructions: Vec<TreeInstruction>,
+    ) -> TreeMetadata {
+        tracing::info!(
+            "Extending Merkle tree with batch #{l1_batch_number} with {instr_count} ops \
+             in full mode with lazy witness",
+            instr_count = instructions.len()
+        );
+
+        let version = u64::from(l1_batch_number.0);
+        let instruction_count = instructions.len();
+        let raw_inputs = serialization::serialize_instructions(&instructions);
+
+        let entries = instructions
+            .into_iter()
+            .filter_map(|instruction| match instruction {
+                TreeInstruction::Write(entry) => Some(entry),
+                TreeInstruction::Read(_) => None,
+            });
+        let entries: Vec<_> = entries.collect();
+        let read_count = instruction_count - entries.len();
+        let output = if let Some(thread_pool) = self.thread_pool.for_batch(entries.len()) {
+            thread_pool.install(|| self.tree.extend(entries.clone()))
+        } else {
+            self.tree.extend(entries.clone())
+        };
+        self.tree.db.add_witness_inputs(version, raw_inputs);
+        let writes = output.logs.iter().copied().zip(&entries);
+        let noop_writes = count_noop_writes(writes.clone());
+        self.emit_batch_event(l1_batch_number, output.root_hash, writes);
+
+        tracing::info!(
+            "Processed batch #{l1_batch_number}; root hash is {root_hash}, \
+             {leaf_count} leaves in total",
+            root_hash = output.root_hash,
+            leaf_count = output.leaf_count,
+        );
+
+        TreeMetadata {
+            root_hash: output.root_hash,
+            rollup_last_leaf_index: output.leaf_count + 1,
+            witness: None,
+            read_count,
+            write_count: entries.len(),
+            noop_writes,
+            logs: None,
+            witness_stats: None,
+        }
+    }
+
+    fn process_l1_batch_lightweight(
+        &mut self,
+        instructions: &[TreeInstruction<StorageKey>],
+    ) -> TreeMetadata {
+        let kvs = Self::filter_write_instructions(instructions);
+        let l1_batch_number = self.next_l1_batch_number();
+        tracing::info!(
+            "Extending Merkle tree with batch #{l1_batch_number} with {kv_count} writes \
+             in lightweight mode",
+            kv_count = kvs.len()
+        );
+
+        let kvs_with_derived_key: Vec<_> = kvs
+            .iter()
+            .map(|entry| entry.map_key(ZkSyncTree::hash_storage_key))
+            .collect();
+
+        let output =
+            if let Some(thread_pool) = self.thread_pool.for_batch(kvs_with_derived_key.len()) {
+                thread_pool.install(|| self.tree.extend(kvs_with_derived_key.clone()))
+            } else {
+                self.tree.extend(kvs_with_derived_key.clone())
+            };
+        let writes = output.logs.iter().copied().zip(&kvs_with_derived_key);
+        let noop_writes = count_noop_writes(writes.clone());
+        self.emit_batch_event(l1_batch_number, output.root_hash, writes);
+
+        tracing::info!(
+            "Processed batch #{l1_batch_number}; root hash is {root_hash}, \
+             {leaf_count} leaves in total",
+            root_hash = output.root_hash,
+            leaf_count = output.leaf_count,
+        );
+
+        TreeMetadata {
+            root_hash: output.root_hash,
+            rollup_last_leaf_index: output.leaf_count + 1,
+            witness: None,
+            // Reads are filtered out early, so they are counted based on the original instructions.
+            read_count: instructions.len() - kvs.len(),
+            write_count: kvs.len(),
+            noop_writes,
+            logs: None,
+            witness_stats: None,
+        }
+    }
+
+    fn process_l1_batch_lightweight_with_reads(
+        &mut self,
+        instructions: &[TreeInstruction<StorageKey>],
+    ) -> TreeMetadata {
+        let l1_batch_number = self.next_l1_batch_number();
+        let instructions_with_hashed_keys: Vec<_> = instructions
+            .iter()
+            .map(|instr| instr.map_key(ZkSyncTree::hash_storage_key))
+            .collect();
+        let (mut read_keys, mut entries) = (vec![], vec![]);
+        for instruction in &instructions_with_hashed_keys {
+            match instruction {
+                TreeInstruction::Write(entry) => entries.push(*entry),
+                TreeInstruction::Read(key) => read_keys.push(*key),
+            }
+        }
+        let read_count = read_keys.len();
+        tracing::info!(
+            "Extending Merkle tree with batch #{l1_batch_number} with {read_count} reads and {write_count} writes \
+             in lightweight mode with reads",
+            write_count = entries.len()
+        );
+
+        // Reads are resolved against the tree state before the batch; writes preceding a read in the batch
+        // are accounted for when merging logs below.
+        let prev_entries = match self.tree.latest_version() {
+            Some(version) => self
+                .tree
+                .entries(version, &read_keys)
+                .expect("latest tree version is missing"),
+            None => read_keys.iter().copied().map(TreeEntry::empty).collect(),
+        };
+        let output = if let Some(thread_pool) = self.thread_pool.for_batch(entries.len()) {
+            thread_pool.install(|| self.tree.extend(entries.clone()))
+        } else {
+            self.tree.extend(entries.clone())
+        };
+        let writes = output.logs.iter().copied().zip(&entries);
+        let noop_writes = count_noop_writes(writes.clone());
+        self.emit_batch_event(l1_batch_number, output.root_hash, writes);
+
+        let mut write_logs = output.logs.iter().copied();
+        let mut prev_entries = prev_entries.into_iter();
+        let mut written_entries = HashMap::new();
+        let logs = instructions_with_hashed_keys
+            .iter()
+            .map(|instruction| match instruction {
+                TreeInstruction::Write(entry) => {
+                    let log = write_logs
+                        .next()
+                        .expect("fewer logs than write instructions");
+                    let leaf_index = match log {
+                        TreeLogEntry::Updated { leaf_index, .. } => leaf_index,
+                        _ => entry.leaf_index,
+                    };
+                    written_entries.insert(entry.key, (entry.value, leaf_index));
+                    log
+                }
+                TreeInstruction::Read(key) => {
+                    let prev_entry = prev_entries.next().expect("fewer entries than reads");
+                    if let Some(&(value, leaf_index)) = written_entries.get(key) {
+                        TreeLogEntry::Read { leaf_index, value }
+                    } else if prev_entry.is_empty() {
+                        TreeLogEntry::ReadMissingKey
+                    } else {
+                        TreeLogEntry::Read {
+                            leaf_index: prev_entry.leaf_index,
+                            value: prev_entry.value,
+                        }
+                    }
+                }
+            })
+            .collect();
+
+        tracing::info!(
+            "Processed batch #{l1_batch_number}; root hash is {root_hash}, \
+             {leaf_count} leaves in total",
+            root_hash = output.root_hash,
+            leaf_count = output.leaf_count,
+        );
+
+        TreeMetadata {
+            root_hash: output.root_hash,
+            rollup_last_leaf_index: output.leaf_count + 1,
+            witness: None,
+            read_count,
+            write_count: entries.len(),
+            noop_writes,
+            logs: Some(logs),
+            witness_stats: None,
+        }
+    }
+
+    /// Computes the root hash that the tree would have after processing the next L1 batch consisting
+    /// of `instructions`, without modifying the tree (even in RAM). The instructions are applied to an in-memory
+    /// overlay on top of the current tree state (including unsaved changes), which is discarded afterwards.
+    /// Thus, this method doesn't influence [`Self::next_l1_batch_number()`] or changes pending to be saved.
+    ///
+    /// This can be used to validate a proposed L1 batch before processing it.
+    pub fn peek_root_hash(&self, instructions: &[TreeInstruction<StorageKey>]) -> ValueHash {
+        self.preview_filtered_root(instructions, |_| true)
+    }
+
+    /// Computes the root hash that the tree would have after processing the next L1 batch consisting
+    /// only of `instructions` matching the `predicate`. Only write instructions influence the root hash.
+    /// The instructions are applied to an in-memory overlay on top of the current tree state
+    /// (including unsaved changes); the tree itself is not modified.
+    ///
+    /// This is useful for debugging how specific storage slots influence the tree root.
+    pub fn preview_filtered_root(
+        &self,
+        instructions: &[TreeInstruction<StorageKey>],
+        predicate: impl Fn(&TreeInstruction<StorageKey>) -> bool,
+    ) -> ValueHash {
+        let filtered_instructions: Vec<_> = instructions
+            .iter()
+            .filter(|&instruction| predicate(instruction))
+            .copied()
+            .collect();
+        let kvs = Self::filter_write_instructions(&filtered_instructions);
+        let kvs_with_derived_key: Vec<_> = kvs
+            .iter()
+            .map(|entry| entry.map_key(ZkSyncTree::hash_storage_key))
+            .collect();
+
+        let output =
+            if let Some(thread_pool) = self.thread_pool.for_batch(kvs_with_derived_key.len()) {
+                thread_pool.install(|| self.tree.preview_extend(kvs_with_derived_key))
+            } else {
+                self.tree.preview_extend(kvs_with_derived_key)
+            };
+        output.root_hash
+    }
+
+    fn emit_batch_event<'a>(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        root_hash: ValueHash,
+        writes: impl Iterator<Item = (TreeLogEntry, &'a TreeEntry)>,
+    ) {
+        if self.batch_events.receiver_count() == 0 {
+            return;
+        }
+
+        let (mut initial_writes, mut repeated_writes) = (0, 0);
+        for (log, entry) in writes {
+            match log {
+                TreeLogEntry::Inserted => initial_writes += 1,
+                TreeLogEntry::Updated { previous_value, .. } if previous_value != entry.value => {
+                    repeated_writes += 1;
+                }
+                _ => { /* no-op update */ }
+            }
+        }
+        let event = BatchEvent {
+            l1_batch_number,
+            root_hash,
+            initial_writes,
+            repeated_writes,
+        };
+        // Sending can only fail if all receivers were dropped concurrently, which is fine.
+        self.batch_events.send(event).ok();
+    }
+
+    fn filter_write_instructions(
+        instructions: &[TreeInstruction<StorageKey>],
+    ) -> Vec<TreeEntry<StorageKey>> {
+        let kvs = instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                TreeInstruction::Write(entry) => Some(*entry),
+                TreeInstruction::Read(_) => None,
+            });
+        kvs.collect()
+    }
+
+    /// Reverts the tree to a previous state.
+    ///
+    /// This method will overwrite all unsaved changes in the tree.
+    pub fn revert_logs(&mut self, last_l1_batch_to_keep: L1BatchNumber) {
+        self.wait_for_background_save();
+        self.tree.db.reset();
+        let retained_version_count = u64::from(last_l1_batch_to_keep.0 + 1);
+        let version_count = self.tree.latest_version().map_or(0, |version| version + 1);
+        let reverted_version_count = version_count.saturating_sub(retained_version_count);
+        if reverted_version_count > 0 {
+            tracing::warn!(
+                "Reverting tree to L1 batch #{last_l1_batch_to_keep}; truncating \
+                 {reverted_version_count} versions (L1 batches #{retained_version_count}..{version_count})"
+            );
+            TREE_METRICS
+                .reverted_versions
+                .inc_by(reverted_version_count);
+        }
+        self.tree.truncate_recent_versions(retained_version_count);
+        if reverted_version_count > 0 {
+            // As with the truncation, removing witness inputs is only persisted on save.
+            self.tree.db.remove_witness_inputs(retained_version_count);
+        }
+    }
+
+    /// Same as [`Self::revert_logs()`], but additionally returns hashed keys of all entries that differ between
+    /// the discarded latest tree state (including unsaved changes) and the retained state, i.e., that were inserted
+    /// or changed their value in the reverted L1 batches. No-op updates are not included. Keys are ordered
+    /// by the enumeration index of their entries in the discarded state.
+    ///
+    /// The diff is computed by traversing the tree nodes created in the reverted L1 batches, so its cost is roughly
+    /// proportional to the number of changed entries rather than to the tree size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tree version for `last_l1_batch_to_keep` is missing (e.g., was pruned).
+    pub fn revert_logs_with_diff(&mut self, last_l1_batch_to_keep: L1BatchNumber) -> Vec<Key> {
+        let retained_version = u64::from(last_l1_batch_to_keep.0);
+        let changed_keys = match self.tree.latest_version() {
+            Some(latest_version) if latest_version > retained_version => {
+                let entries = self
+                    .tree
+                    .changed_entries(Some(retained_version), latest_version)
+                    .unwrap_or_else(|err| panic!("Failed computing diff for tree revert: {err}"));
+                entries.into_iter().map(|entry| entry.key).collect()
+            }
+            _ => vec![],
+        };
+        self.revert_logs(last_l1_batch_to_keep);
+        changed_keys
+    }
+
+    /// Reverts the tree to the latest saved version with the specified root hash, e.g. one committed on L1,
+    /// and returns the L1 batch number corresponding to this version. Versions are looked up in the same way
+    /// as in [`ZkSyncTreeReader::root_exists()`]; if several versions have the specified root hash
+    /// (e.g., because of empty L1 batches), the latest one is retained.
+    ///
+    /// Like [`Self::revert_logs()`], this method will overwrite all unsaved changes in the tree.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no retained saved version has the specified root hash. In this case,
+    /// the tree is not modified.
+    pub fn revert_to_root(
+        &mut self,
+        root_hash: ValueHash,
+    ) -> Result<L1BatchNumber, RootNotFoundError> {
+        let l1_batch_number = self.reader().root_exists(root_hash).ok().flatten();
+        let l1_batch_number = l1_batch_number.ok_or(RootNotFoundError { root_hash })?;
+        self.revert_logs(l1_batch_number);
+        Ok(l1_batch_number)
+    }
+
+    /// Saves the accumulated changes in the tree to RocksDB. If a [background save](Self::begin_save())
+    /// is in progress, it is finished first.
+    ///
+    /// All pending tree versions are written in a single atomic RocksDB write batch, so readers
+    /// never observe a partially saved state. Saving doesn't block readers; existing
+    /// [snapshots](ZkSyncTreeSnapshot) continue to observe the version they were created for.
+    pub fn save(&mut self) {
+        self.wait_for_background_save();
+        let mut l1_batch_numbers = self.tree.db.patched_versions();
+        l1_batch_numbers.sort_unstable();
+        tracing::info!("Flushing L1 batches #{l1_batch_numbers:?} to RocksDB");
+        self.tree.db.flush();
+    }
+
+    /// Starts saving the accumulated changes in the tree to RocksDB on a background thread. Unlike [`Self::save()`],
+    /// this method doesn't wait for changes to be written, so the tree can continue processing L1 batches
+    /// in the meantime. Saving must be completed via [`Self::finish_save()`] (or implicitly by calling `save()`).
+    ///
+    /// While saving is in progress, the tree holds two sets of changes in RAM: ones being saved, and ones
+    /// accumulated after calling this method. Thus, RAM consumption can be up to twice as large as when
+    /// using `save()`. Changes being saved remain visible to the tree; as with `save()`, they become visible
+    /// to [readers](Self::reader()) once they are written to RocksDB.
+    ///
+    /// Witness inputs for [lazy witnesses](Self::set_lazy_witnesses()) are saved together with the other changes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a background save is already in progress.
+    pub fn begin_save(&mut self) {
+        assert!(
+            self.background_save.is_none(),
+            "background save is already in progress; call `finish_save()` first"
+        );
+        if let Some(save) = self.start_background_save() {
+            thread::Builder::new()
+                .name("merkle-tree-save".to_owned())
+                .spawn(save)
+                .expect("failed spawning Merkle tree save thread");
+        }
+    }
+
+    /// Freezes the accumulated changes and returns a closure writing them to RocksDB, or `None` if there are
+    /// no changes to save. The save is registered as being in progress; the closure must be run on a background thread.
+    fn start_background_save(&mut self) -> Option<impl FnOnce() + Send + 'static> {
+        let mut l1_batch_numbers = self.tree.db.patched_versions();
+        l1_batch_numbers.sort_unstable();
+        tracing::info!("Flushing L1 batches #{l1_batch_numbers:?} to RocksDB in background");
+
+        let patch = self.tree.db.freeze()?;
+        let db = self.tree.db.inner().clone();
+        let (background_save, save) = BackgroundSave::new(db, patch);
+        self.background_save = Some(background_save);
+        Some(save)
+    }
+
+    /// Async version of [`Self::save()`]. RocksDB writes are performed on the blocking thread pool of the `tokio` runtime
+    /// (via [`tokio::task::spawn_blocking()`]), so this method doesn't block the async runtime while changes are flushed.
+    /// If a background save is in progress, it is finished first.
+    ///
+    /// If the returned future is dropped before completion, saving continues in the background;
+    /// it can be completed via [`Self::finish_save()`] or any saving method.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if saving has failed (e.g., because of a RocksDB I/O error). As with [`Self::finish_save()`],
+    /// unsaved changes are retained in RAM in this case, so saving can be retried.
+    pub async fn save_async(&mut self) -> Result<(), BackgroundSaveError> {
+        self.background_save_done().await;
+        self.wait_for_background_save();
+        if let Some(save) = self.start_background_save() {
+            tokio::task::spawn_blocking(save);
+            // ^ The task outcome is transmitted via the `BackgroundSave` channels, so the handle is not needed.
+            self.background_save_done().await;
+        }
+        self.finish_save()
+    }
+
+    async fn background_save_done(&mut self) {
+        if let Some(save) = &mut self.background_save {
+            (&mut save.done).await.ok();
+            // ^ The sender is never used; it's only dropped when saving terminates.
+        }
+    }
+
+    /// Waits until the save started via [`Self::begin_save()`] is completed. Does nothing if no background save
+    /// is in progress.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if saving has failed (e.g., because of a RocksDB I/O error). In this case, the changes
+    /// that were being saved are retained in RAM together with the changes accumulated after calling `begin_save()`,
+    /// as if `begin_save()` was never called. Thus, saving can be retried.
+    pub fn finish_save(&mut self) -> Result<(), BackgroundSaveError> {
+        let Some(save) = self.background_save.take() else {
+            return Ok(());
+        };
+        let result = save.result.recv().unwrap_or_else(|_| {
+            // The sender is dropped without sending only if the saving task was cancelled before running.
+            Err(Box::new("Merkle tree save task was cancelled"))
+        });
+        self.tree.db.unfreeze(result.is_ok());
+        result.map_err(|panic| BackgroundSaveError {
+            message: panic_message(&*panic),
+        })
+    }
+
+    fn wait_for_background_save(&mut self) {
+        if let Err(err) = self.finish_save() {
+            tracing::warn!("{err}; changes that were being saved are retained in RAM");
+        }
+    }
+
+    /// Resets the tree to the latest database state. If a [background save](Self::begin_save())
+    /// is in progress, it is finished first.
+    pub fn reset(&mut self) {
+        self.wait_for_background_save();
+        self.tree.db.reset();
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "(unknown panic)".to_owned()
+    }
+}
+
+/// Approximate serialized size of a [`StorageLogMetadata`] excluding Merkle path hashes: 3 hashes, 2 flags,
+/// Merkle path length, hashed key and enumeration index.
+const WITNESS_LOG_BASE_SIZE: usize = 3 * 32 + 2 + 8 + 32 + 8;
+
+/// Builds a witness for an L1 batch based on the proofs output by the tree. `instructions` must have hashed keys.
+/// If `include_paths` is not set, Merkle paths in the witness are left empty. If `flush` is specified,
+/// witness chunks with the specified number of logs are handed over to the provided closure as they are built,
+/// and the returned witness only contains the remaining logs. If the approximate size of the returned witness
+/// (or a flushed chunk) exceeds `max_size`, building is aborted. Empty subtree hashes in Merkle paths
+/// are computed using `hasher`, which must be the hasher of the tree. Returned statistics cover all logs,
+/// including ones in flushed chunks.
+fn build_witness(
+    hasher: &dyn HashTree,
+    starting_leaf_count: u64,
+    output: &BlockOutputWithProofs,
+    instructions: &[TreeInstruction],
+    include_paths: bool,
+    max_size: usize,
+    mut flush: Option<(usize, &mut dyn FnMut(PrepareBasicCircuitsJob))>,
+) -> Result<(PrepareBasicCircuitsJob, WitnessStats), WitnessTooLarge> {
+    let next_enumeration_index = starting_leaf_count + 1;
+    let mut witness = PrepareBasicCircuitsJob::new(next_enumeration_index);
+    let capacity = flush
+        .as_ref()
+        .map_or(output.logs.len(), |(flush_every, _)| {
+            output.logs.len().min(*flush_every)
+        });
+    witness.reserve(capacity);
+    let mut chunk_len = 0;
+    let mut size = 0;
+    let mut stats = WitnessStats::default();
+    for (log, instruction) in output.logs.iter().zip(instructions) {
+        let Some(log) = witness_log(hasher, log, instruction, include_paths) else {
+            continue; // A no-op update that must be omitted from the produced `witness`.
+        };
+        let retained_hash_count = witness.push_merkle_path(log);
+        stats.push_path(retained_hash_count);
+        size += WITNESS_LOG_BASE_SIZE + retained_hash_count * 32;
+        if size > max_size {
+            return Err(WitnessTooLarge { size, max_size });
+        }
+
+        if let Some((flush_every, consume_chunk)) = &mut flush {
+            chunk_len += 1;
+            if chunk_len == *flush_every {
+                let mut new_witness = PrepareBasicCircuitsJob::new(next_enumeration_index);
+                new_witness.reserve(capacity);
+                consume_chunk(mem::replace(&mut witness, new_witness));
+                chunk_len = 0;
+                size = 0;
+            }
+        }
+    }
+    Ok((witness, stats))
+}
+
+/// Converts a log output by the tree into a witness log with the full Merkle path (or an empty path
+/// if `include_paths` is not set). Returns `None` for no-op updates, which must be omitted from witnesses.
+/// `instruction` must have a hashed key.
+fn witness_log(
+    hasher: &dyn HashTree,
+    log: &TreeLogEntryWithProof,
+    instruction: &TreeInstruction,
+    include_paths: bool,
+) -> Option<StorageLogMetadata> {
+    let merkle_paths = if include_paths {
+        let empty_levels_end = TREE_DEPTH - log.merkle_path.len();
+        let empty_subtree_hashes = (0..empty_levels_end).map(|i| hasher.empty_subtree_hash(i));
+        let merkle_paths = log.merkle_path.iter().copied();
+        empty_subtree_hashes
+            .chain(merkle_paths)
+            .map(|hash| hash.0)
+            .collect()
+    } else {
+        vec![]
+    };
+
+    let value_written = match instruction {
+        TreeInstruction::Write(entry) => entry.value.0,
+        TreeInstruction::Read(_) => [0_u8; 32],
+    };
+    Some(StorageLogMetadata {
+        root_hash: log.root_hash.0,
+        is_write: !log.base.is_read(),
+        first_write: matches!(log.base, TreeLogEntry::Inserted),
+        merkle_paths,
+        leaf_hashed_key: instruction.key(),
+        leaf_enumeration_index: match instruction {
+            TreeInstruction::Write(entry) => entry.leaf_index,
+            TreeInstruction::Read(_) => match log.base {
+                TreeLogEntry::Read { leaf_index, .. } => leaf_index,
+                TreeLogEntry::ReadMissingKey => 0,
+                _ => unreachable!(
+                    "Read instructions always transform to Read / ReadMissingKey log entries"
+                ),
+            },
+        },
+        value_written,
+        value_read: match log.base {
+            TreeLogEntry::Updated { previous_value, .. } => {
+                if previous_value.0 == value_written {
+                    return None;
+                }
+                previous_value.0
+            }
+            TreeLogEntry::Read { value, .. } => value.0,
+            TreeLogEntry::Inserted | TreeLogEntry::ReadMissingKey => [0_u8; 32],
+        },
+    })
+}
+
+/// Counts no-op updates among the provided writes.
+fn count_noop_writes<'a>(writes: impl Iterator<Item = (TreeLogEntry, &'a TreeEntry)>) -> usize {
+    writes
+        .filter(|(log, entry)| {
+            matches!(log, TreeLogEntry::Updated { previous_value, .. } if *previous_value == entry.value)
+        })
+        .count()
+}
+
+/// Splits `items` into chunks to be processed in parallel, one chunk per thread of the current `rayon` thread pool.
+fn par_chunks_per_thread<T: Sync>(items: &[T]) -> rayon::slice::Chunks<'_, T> {
+    let thread_count = rayon::current_num_threads();
+    let chunk_size = (items.len() + thread_count - 1) / thread_count;
+    items.par_chunks(chunk_size.max(1))
+}
+
+/// Readonly handle to a [`ZkSyncTree`].
+///
+/// The reader is cheaply cloneable: cloning doesn't access RocksDB and only copies a few [`Arc`]s.
+#[derive(Debug)]
+pub struct ZkSyncTreeReader<H = Blake2Hasher>(
+    MerkleTree<RocksDBWrapper, H>,
+    Option<Arc<ConcurrencyLimiter>>,
+);
+
+// While cloning `MerkleTree` is logically unsound, cloning a reader is reasonable since it is readonly.
+// Clones share the concurrency limit, if any. The tree is not reloaded via `MerkleTree::with_hasher()`
+// since it reads the tree manifest from RocksDB, and the manifest was already checked when creating this reader.
+impl<H: Clone> Clone for ZkSyncTreeReader<H> {
+    fn clone(&self) -> Self {
+        let tree = MerkleTree {
+            db: self.0.db.clone(),
+            hasher: self.0.hasher.clone(),
+        };
+        Self(tree, self.1.clone())
+    }
+}
+
+impl<H: HashTree + Clone> ZkSyncTreeReader<H> {
+    /// Maximum number of attempts for proof computations that are retried if the tree changes concurrently.
+    const MAX_PROOF_ATTEMPTS: usize = 3;
+
+    /// Returns the current root hash of this tree.
+    pub fn root_hash(&self) -> ValueHash {
+        self.0.latest_root_hash()
+    }
+
+    /// Returns the root hash of the tree after the specified L1 batch. For an empty tree version,
+    /// returns the hash of the empty tree.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree version for the L1 batch is missing (e.g., was pruned or doesn't exist yet).
+    pub fn root_hash_at(
+        &self,
+        l1_batch_number: L1BatchNumber,
+    ) -> Result<ValueHash, NoVersionError> {
+        let version = u64::from(l1_batch_number.0);
+        let (root_hash, _) = self.0.root_info(version)?;
+        Ok(root_hash)
+    }
+
+    /// Returns the next L1 batch number that should be processed by the tree.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn next_l1_batch_number(&self) -> L1BatchNumber {
+        let number = self.0.latest_version().map_or(0, |version| {
+            u32::try_from(version + 1).expect("integer overflow for L1 batch number")
+        });
+        L1BatchNumber(number)
+    }
+
+    /// Returns the number of leaves in the tree.
+    pub fn leaf_count(&self) -> u64 {
+        self.0.latest_root().leaf_count()
+    }
+
+    /// Samples RocksDB block cache hits and misses and reports them as metrics. This should be called
+    /// periodically; it is a no-op if RocksDB statistics are not enabled for the tree database
+    /// (see [`RocksDBOptions::enable_statistics`](zksync_storage::RocksDBOptions::enable_statistics)).
+    pub fn report_block_cache_stats(&self) {
+        if let Some(stats) = self.0.db.block_cache_stats() {
+            TREE_METRICS.observe_block_cache_stats(stats);
+        }
+    }
+
+    /// Creates a [snapshot](ZkSyncTreeSnapshot) pinned to the latest tree version flushed to RocksDB.
+    pub fn snapshot(&self) -> ZkSyncTreeSnapshot<H> {
+        let pinned_root = self
+            .0
+            .latest_version()
+            .and_then(|version| Some((version, self.0.root(version)?)));
+        let root_hash = pinned_root.as_ref().map_or_else(
+            || self.0.hasher.empty_tree_hash(),
+            |(_, root)| self.0.root_hash_and_leaf_count(root).0,
+        );
+        ZkSyncTreeSnapshot {
+            reader: self.clone(),
+            pinned_root,
+            root_hash,
+        }
+    }
+
+    /// Reads entries together with Merkle proofs with the specified keys from the tree. The entries are returned
+    /// in the same order as requested.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub fn entries_with_proofs(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        keys: &[Key],
+    ) -> Result<Vec<TreeEntryWithProof>, NoVersionError> {
+        let version = u64::from(l1_batch_number.0);
+        let _permit = self.acquire_proof_permit();
+        self.0.entries_with_proofs(version, keys)
+    }
+
+    /// Reads entries together with Merkle proofs with the specified keys at the greatest retained tree version
+    /// not exceeding the specified L1 batch. Returns the L1 batch number of the used version together with
+    /// the entries, which are returned in the same order as requested. This is useful for audits that can tolerate
+    /// slightly older data, e.g. if the requested version is not created yet or was removed by pruning.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no retained tree version at or before the specified L1 batch.
+    pub fn entries_with_proofs_at_or_before(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        keys: &[Key],
+    ) -> Result<(L1BatchNumber, Vec<TreeEntryWithProof>), NoVersionError> {
+        let requested_version = u64::from(l1_batch_number.0);
+        // Retained versions form a contiguous range ending at the latest version (pruning only removes
+        // the oldest versions), so the only candidate is the latest version not exceeding the requested one.
+        let version = self
+            .0
+            .latest_version()
+            .map_or(requested_version, |latest| latest.min(requested_version));
+        let _permit = self.acquire_proof_permit();
+        let entries = self.0.entries_with_proofs(version, keys)?;
+        let l1_batch_number = u32::try_from(version).expect("L1 batch number overflow");
+        // ^ Cannot fail since `version <= requested_version`
+        Ok((L1BatchNumber(l1_batch_number), entries))
+    }
+
+    /// Reads entries together with Merkle proofs with the specified keys at several L1 batches, e.g. to check
+    /// how the entries changed over time. The outer `Vec` in the returned value corresponds to `l1_batch_numbers`,
+    /// and the inner one to `keys`; both are in the same order as requested. Proofs for each distinct L1 batch
+    /// are loaded once, even if the batch is repeated in `l1_batch_numbers`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree version for any of the L1 batches is missing. All versions are checked
+    /// before loading proofs, so a missing version is reported without doing any extra work.
+    pub fn entries_with_proofs_multi(
+        &self,
+        l1_batch_numbers: &[L1BatchNumber],
+        keys: &[Key],
+    ) -> Result<Vec<Vec<TreeEntryWithProof>>, NoVersionError> {
+        let versions: Vec<_> = l1_batch_numbers
+            .iter()
+            .map(|l1_batch_number| u64::from(l1_batch_number.0))
+            .collect();
+        for &version in &versions {
+            load_root(&self.0.db, version)?;
+        }
+
+        let _permit = self.acquire_proof_permit();
+        let mut entries_by_version = HashMap::with_capacity(versions.len());
+        for &version in &versions {
+            if let hash_map::Entry::Vacant(entry) = entries_by_version.entry(version) {
+                entry.insert(self.0.entries_with_proofs(version, keys)?);
+            }
+        }
+        Ok(versions
+            .iter()
+            .map(|version| entries_by_version[version].clone())
+            .collect())
+    }
+
+    /// Reads entries together with Merkle proofs with the specified keys from the tree and encodes them
+    /// in a versioned binary format. Encoded entries can be decoded using [`decode_entries_with_proofs()`].
+    ///
+    /// # Format
+    ///
+    /// The encoding starts with a format version byte (currently, 0), followed by the number of entries (LEB128)
+    /// and the entries in the same order as requested. Each entry is encoded as:
+    ///
+    /// - Hashed key (32 bytes, big-endian)
+    /// - Value hash (32 bytes)
+    /// - Enumeration index (LEB128)
+    /// - Number of Merkle path hashes (LEB128), followed by the hashes (32 bytes each) ordered
+    ///   as in [`TreeEntryWithProof::merkle_path`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub fn entries_with_proofs_encoded(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        keys: &[Key],
+    ) -> Result<Vec<u8>, NoVersionError> {
+        let entries = self.entries_with_proofs(l1_batch_number, keys)?;
+        Ok(serialization::serialize_entries_with_proofs(&entries))
+    }
+
+    /// Reads entries together with Merkle proofs with the specified keys from the latest tree version.
+    /// The entries are returned in the same order as requested, together with the L1 batch number
+    /// corresponding to the tree version used. Unlike using [`Self::next_l1_batch_number()`] followed by
+    /// [`Self::entries_with_proofs()`], this is not affected by the tree advancing or being pruned
+    /// between the calls: if the latest version is pruned or reverted concurrently, reading is retried a few times
+    /// with the new latest version.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree is empty, or if the latest version is missing after all retries.
+    pub fn latest_entries_with_proofs(
+        &self,
+        keys: &[Key],
+    ) -> Result<(L1BatchNumber, Vec<TreeEntryWithProof>), LatestEntriesError> {
+        let _permit = self.acquire_proof_permit();
+        let mut attempt = 1;
+        loop {
+            let version = self.0.latest_version().ok_or(EmptyTreeError)?;
+            match self.0.entries_with_proofs(version, keys) {
+                Ok(entries) => return Ok((Self::l1_batch_number(version), entries)),
+                Err(err) if attempt == Self::MAX_PROOF_ATTEMPTS => return Err(err.into()),
+                Err(err) => {
+                    // The version was pruned or reverted concurrently; retry with the new latest version.
+                    tracing::debug!(
+                        "Retrying getting latest entries with proofs (attempt {attempt}): {err}"
+                    );
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Reads the value for the specified key from the tree, or `None` if the key is not present in the tree.
+    /// This is the cheapest way to read a single value: unlike [`Self::entries_with_proofs()`],
+    /// it doesn't hash anything or return the leaf index. The `value_reads` example benchmarks this method
+    /// against [`Self::entry()`] and [`Self::entries_with_proofs()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub fn value(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        key: Key,
+    ) -> Result<Option<ValueHash>, NoVersionError> {
+        let version = u64::from(l1_batch_number.0);
+        self.0.value(version, key)
+    }
+
+    /// Reads the value and enumeration index for the specified key from the tree, or `None` if the key
+    /// is not present in the tree. This is the natural granularity for constructing storage writes;
+    /// unlike [`Self::entries_with_proofs()`], it doesn't build a Merkle proof.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub fn entry(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        key: Key,
+    ) -> Result<Option<TreeEntry>, NoVersionError> {
+        let version = u64::from(l1_batch_number.0);
+        let entry = self.entry_at_version(version, key)?;
+        Ok((!entry.is_empty()).then_some(entry))
+    }
+
+    /// Batched version of [`Self::entry()`]. The entries are returned in the same order as requested.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub fn entries(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        keys: &[Key],
+    ) -> Result<Vec<Option<TreeEntry>>, NoVersionError> {
+        let version = u64::from(l1_batch_number.0);
+        let entries = self.0.entries(version, keys)?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| (!entry.is_empty()).then_some(entry))
+            .collect())
+    }
+
+    /// Returns the enumeration index of the leaf with the specified hashed key at the latest tree version,
+    /// or `None` if the key is not present in the tree (or the tree is empty). This allows to check
+    /// whether a write of the key is initial or repeated, and to verify enumeration indices in witnesses.
+    /// Like [`Self::entry()`], this only reads the leaf and doesn't build a Merkle proof.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the latest tree version was removed concurrently (e.g., by a revert).
+    pub fn leaf_index(&self, key: Key) -> Result<Option<u64>, NoVersionError> {
+        let Some(latest_version) = self.0.latest_version() else {
+            return Ok(None);
+        };
+        let entry = self.entry_at_version(latest_version, key)?;
+        Ok((!entry.is_empty()).then_some(entry.leaf_index))
+    }
+
+    /// Resolves enumeration indices for the specified storage keys at the specified L1 batch. Keys are hashed
+    /// internally; indices are returned in the same order as requested, with `None` for keys absent from the tree.
+    ///
+    /// Keys are hashed and looked up in parallel using the current `rayon` thread pool.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub fn enumeration_indices_for_storage_keys(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        keys: &[StorageKey],
+    ) -> Result<Vec<Option<u64>>, NoVersionError> {
+        let version = u64::from(l1_batch_number.0);
+        if keys.is_empty() {
+            self.0.entries(version, &[])?;
+            return Ok(vec![]);
+        }
+
+        let chunks: Vec<_> = par_chunks_per_thread(keys)
+            .map(|chunk| {
+                let hashed_keys: Vec<_> = chunk.iter().map(ZkSyncTree::hash_storage_key).collect();
+                let entries = self.0.entries(version, &hashed_keys)?;
+                Ok(entries
+                    .into_iter()
+                    .map(|entry| (!entry.is_empty()).then_some(entry.leaf_index))
+                    .collect::<Vec<_>>())
+            })
+            .collect::<Result<_, NoVersionError>>()?;
+        Ok(chunks.into_iter().flatten().collect())
+    }
+
+    /// Checks whether each of the provided leaves is present in the tree at the specified L1 batch.
+    /// A leaf is a pair of a hashed key and the expected value; a missing key is considered to have
+    /// the zero value. For each leaf, a Merkle proof is loaded from the tree, and the check passes
+    /// if the value in the proof matches the expected value and the proof verifies against
+    /// the root hash of the tree version. This packages the common flow for auditing a sample of leaves.
+    ///
+    /// Checks are split among threads of the current `rayon` thread pool; results are returned
+    /// in the same order as the input leaves.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub fn verify_leaves_in_root(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        leaves: &[(Key, ValueHash)],
+    ) -> Result<Vec<bool>, NoVersionError> {
+        let version = u64::from(l1_batch_number.0);
+        let root_hash = self.0.root_hash(version).ok_or_else(|| NoVersionError {
+            missing_version: version,
+            version_count: self.0.latest_version().map_or(0, |version| version + 1),
+        })?;
+        if leaves.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let _permit = self.acquire_proof_permit();
+        let chunk_results = par_chunks_per_thread(leaves).map(|chunk| {
+            let keys: Vec<_> = chunk.iter().map(|&(key, _)| key).collect();
+            let entries = self.0.entries_with_proofs(version, &keys)?;
+            let results = entries
+                .into_iter()
+                .zip(chunk)
+                .map(|(entry, &(_, expected_value))| {
+                    let hasher: &dyn HashTree = &self.0.hasher;
+                    entry.base.value == expected_value
+                        && hasher.fold_merkle_path(&entry.merkle_path, entry.base) == root_hash
+                });
+            Ok(results.collect::<Vec<_>>())
+        });
+        let chunk_results: Vec<_> = chunk_results.collect::<Result<_, NoVersionError>>()?;
+        Ok(chunk_results.into_iter().flatten().collect())
+    }
+
+    /// Returns hashes of the tree nodes on the path from the root to the leaf with the specified key:
+    /// the root node, internal nodes and the leaf itself (in this order). Unlike Merkle proofs
+    /// returned by [`Self::entries_with_proofs()`], which consist of sibling hashes, this returns hashes
+    /// of the nodes themselves, which is useful for debugging proof verification failures. Since internal nodes
+    /// in the tree storage span 4 levels of the binary Merkle tree, the number of returned hashes is
+    /// much less than the tree depth.
+    ///
+    /// Returns an empty vector if the key is not present in the tree.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub fn path_node_hashes(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        key: Key,
+    ) -> Result<Vec<ValueHash>, NoVersionError> {
+        let version = u64::from(l1_batch_number.0);
+        let _permit = self.acquire_proof_permit();
+        self.0.path_node_hashes(version, key)
+    }
+
+    /// Returns the number of initial and repeated writes (in this order) in the specified L1 batch.
+    /// The counts are derived from the tree nodes created for the batch, which is cheaper than
+    /// loading the full lists of writes. No-op updates (i.e., ones writing the same value) are not counted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree version for the L1 batch or for the preceding L1 batch is missing
+    /// (e.g., pruned).
+    pub fn write_counts(
+        &self,
+        l1_batch_number: L1BatchNumber,
+    ) -> Result<(usize, usize), NoVersionError> {
+        let version = u64::from(l1_batch_number.0);
+        self.0.write_counts(version)
+    }
+
+    /// Returns the net state diff between the specified L1 batches as a list of `(key, value, enumeration_index)`
+    /// tuples for keys inserted or updated after `from` up to and including `to`. If a key was written several times,
+    /// only its value at `to` is returned; no-op updates are omitted. Writes are ordered by enumeration index,
+    /// so initial writes come last in the order of insertion. Thus, the diff can be passed to [`ZkSyncTree::apply_writes()`]
+    /// to bring a tree at `from` to the state at `to` without replaying storage logs.
+    ///
+    /// The diff is computed by traversing only tree nodes modified after `from`, but it is collected in RAM,
+    /// so the distance between `from` and `to` should be bounded for trees with many updates.
+    /// The diff is empty if `from >= to`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree version for either of the L1 batches is missing (e.g., pruned).
+    pub fn state_diff(
+        &self,
+        from: L1BatchNumber,
+        to: L1BatchNumber,
+    ) -> Result<Vec<(Key, ValueHash, u64)>, NoVersionError> {
+        let entries = self
+            .0
+            .changed_entries(Some(u64::from(from.0)), u64::from(to.0))?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| (entry.key, entry.value, entry.leaf_index))
+            .collect())
+    }
+
+    /// Reads entries together with Merkle proofs with the specified keys from the tree version
+    /// with the specified root hash. This allows obtaining proofs verifiable against a known root
+    /// (e.g., one committed on L1) without knowing the corresponding L1 batch number.
+    /// All retained tree versions are scanned, starting from the latest one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no retained tree version has the specified root hash.
+    pub fn entries_with_proofs_against_root(
+        &self,
+        expected_root: ValueHash,
+        keys: &[Key],
+    ) -> Result<Vec<TreeEntryWithProof>, RootNotFoundError> {
+        let version = self.0.latest_version().and_then(|latest_version| {
+            self.find_version_by_root_hash(latest_version, expected_root)
+        });
+        let not_found_err = || RootNotFoundError {
+            root_hash: expected_root,
+        };
+        let version = version.ok_or_else(not_found_err)?;
+        let _permit = self.acquire_proof_permit();
+        // The version may be pruned concurrently, in which case it's no longer retained.
+        self.0
+            .entries_with_proofs(version, keys)
+            .map_err(|_| not_found_err())
+    }
+
+    /// Returns a Merkle proof for the specified key at the specified L1 batch together with the root hash
+    /// and the number of leaves of the tree version the proof was built for. Unlike calling [`Self::entries_with_proofs()`]
+    /// and reading the root hash separately, this guarantees that the proof and the returned root hash come
+    /// from the same tree state, even if the L1 batch is concurrently reverted and processed again.
+    ///
+    /// The proof and the root info are built from a single root node read. Since nodes below the root can still
+    /// be overwritten if the L1 batch is concurrently reverted and processed again, the proof is checked against
+    /// the root hash, and the computation is retried a few times on a mismatch.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree version for the L1 batch is missing, or if the proof doesn't match
+    /// the root hash after all retries (e.g., because the tree is corrupted).
+    #[allow(clippy::missing_panics_doc)]
+    pub fn proof_with_state(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        key: Key,
+    ) -> Result<(TreeEntryWithProof, ValueHash, u64), ProofWithStateError> {
+        let version = u64::from(l1_batch_number.0);
+        let hasher: &dyn HashTree = &self.0.hasher;
+        let _permit = self.acquire_proof_permit();
+        let mut attempt = 1;
+        loop {
+            let (mut entries, root_hash, leaf_count) =
+                self.0.entries_with_proofs_and_root_info(version, &[key])?;
+            let entry = entries
+                .pop()
+                .expect("entry for the requested key is missing");
+            let proof_root_hash = hasher.fold_merkle_path(&entry.merkle_path, entry.base);
+            if proof_root_hash == root_hash {
+                return Ok((entry, root_hash, leaf_count));
+            } else if attempt == Self::MAX_PROOF_ATTEMPTS {
+                return Err(ProofWithStateError::RootHashMismatch {
+                    expected: root_hash,
+                    actual: proof_root_hash,
+                });
+            }
+            // The L1 batch may have been reverted and processed again during the read; retry.
+            tracing::debug!(
+                "Retrying getting proof with state for L1 batch #{l1_batch_number} (attempt {attempt}): \
+                 proof doesn't match root hash"
+            );
+            attempt += 1;
+        }
+    }
+
+    /// Returns the latest L1 batch for which the tree root hash equals `root_hash`, or `None` if there is
+    /// no such batch among retained tree versions. This can be used to check whether a historical commitment
+    /// corresponds to a tree state.
+    ///
+    /// Retained versions are scanned starting from the latest one, so the cost of this method is linear
+    /// in the number of versions newer than the found one (or in the number of retained versions if
+    /// the root hash is not found); checking a version requires reading and hashing its root node.
+    /// Versions removed by pruning are not checked, so `None` doesn't mean that the root hash never existed
+    /// in the tree history; it only means that the hash doesn't correspond to any retained version.
+    /// If the tree is empty (has no versions), returns `None`.
+    ///
+    /// A persistent root hash → version index is intentionally not maintained. It would add a write for each
+    /// processed L1 batch and would need to be kept in sync with reverts and pruning, while lookups are expected
+    /// to be rare and usually target recent versions, for which the scan is cheap.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the latest tree version was removed concurrently (e.g., by a revert).
+    pub fn root_exists(
+        &self,
+        root_hash: ValueHash,
+    ) -> Result<Option<L1BatchNumber>, NoVersionError> {
+        let Some(latest_version) = self.0.latest_version() else {
+            return Ok(None);
+        };
+        // Unlike older versions, the latest version is loaded with error checking, so that its concurrent removal
+        // isn't confused with the root hash being absent.
+        let (latest_root_hash, _) = self.0.root_info(latest_version)?;
+        if latest_root_hash == root_hash {
+            return Ok(Some(Self::l1_batch_number(latest_version)));
+        }
+        let Some(prev_version) = latest_version.checked_sub(1) else {
+            return Ok(None);
+        };
+        Ok(self
+            .find_version_by_root_hash(prev_version, root_hash)
+            .map(Self::l1_batch_number))
+    }
+
+    /// Prewarms the RocksDB cache for subsequent [`Self::entries_with_proofs()`] calls with the specified keys.
+    /// This traverses all tree nodes on the Merkle paths of the keys, i.e. performs the same I/O as
+    /// `entries_with_proofs()`, but skips hashing and discards the loaded nodes. The work is split among
+    /// threads of the current `rayon` thread pool.
+    ///
+    /// This is purely an optimization; it does not influence the correctness of the following calls.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub fn prewarm_proofs(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        keys: &[Key],
+    ) -> Result<(), NoVersionError> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let version = u64::from(l1_batch_number.0);
+        let _permit = self.acquire_proof_permit();
+        // Loading entries loads all internal nodes on the paths to the requested keys, which are exactly
+        // the nodes necessary to build proofs.
+        par_chunks_per_thread(keys).try_for_each(|chunk| self.0.entries(version, chunk).map(drop))
+    }
+
+    /// Scans the tree database for orphaned nodes, i.e. nodes that are not reachable from any of the tree roots
+    /// present in the database, and thus will never be removed by the pruner. This method is read-only
+    /// and can be run out-of-band, but it is slow and memory-intensive for large trees since it traverses
+    /// all nodes in the database.
+    pub fn audit_orphans(&self) -> OrphanReport {
+        let report = OrphanReport::new(&self.0.db);
+        tracing::info!(
+            "Audited Merkle tree nodes: {orphan_count} / {node_count} nodes are orphaned \
+             (~{orphan_byte_size} bytes)",
+            orphan_count = report.orphan_count,
+            node_count = report.node_count,
+            orphan_byte_size = report.orphan_byte_size
+        );
+        report
+    }
+
+    /// Returns the latest L1 batch at which the value of the specified key has changed, or `None`
+    /// if the key is not present in the tree. Initial writes are considered changes as well.
+    ///
+    /// The batch is found using binary search across retained tree versions, so this method performs
+    /// `O(log n)` key lookups (each with the cost comparable to building a Merkle proof), where `n` is
+    /// the number of retained versions. As a consequence, if the key value was changed and then reverted
+    /// to a value it had before, the returned batch is one at which the value has changed, but not necessarily
+    /// the most recent one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value of the key didn't change in the retained versions, and older tree
+    /// versions are pruned, so the batch cannot be determined.
+    pub fn last_changed_version(&self, key: Key) -> Result<Option<L1BatchNumber>, NoVersionError> {
+        let Some(latest_version) = self.0.latest_version() else {
+            return Ok(None);
+        };
+        let latest_entry = self.entry_at_version(latest_version, key)?;
+        if latest_entry.is_empty() {
+            return Ok(None);
+        }
+
+        let first_version = self.0.first_retained_version(latest_version);
+        if self.entry_at_version(first_version, key)? == latest_entry {
+            if first_version == 0 {
+                return Ok(Some(L1BatchNumber(0)));
+            }
+            let version_count = latest_version + 1;
+            return Err(NoVersionError {
+                missing_version: first_version - 1,
+                version_count,
+            });
+        }
+
+        // Invariant: the key has a different value at `lo` and the latest value at `hi`.
+        let (mut lo, mut hi) = (first_version, latest_version);
+        while hi - lo > 1 {
+            let mid = lo + (hi - lo) / 2;
+            if self.entry_at_version(mid, key)? == latest_entry {
+                hi = mid;
+            } else {
+                lo = mid;
+            }
+        }
+        Ok(Some(Self::l1_batch_number(hi)))
+    }
+
+    fn entry_at_version(&self, version: u64, key: Key) -> Result<TreeEntry, NoVersionError> {
+        let mut entries = self.0.entries(version, &[key])?;
+        Ok(entries.pop().unwrap())
+        // ^ `unwrap()` is safe: exactly one entry is requested
+    }
+
+    /// Finds the latest retained version with the specified root hash by scanning versions starting from `latest_version`.
+    fn find_version_by_root_hash(&self, latest_version: u64, root_hash: ValueHash) -> Option<u64> {
+        // Versions are retained contiguously, so the scan can stop at the first missing version.
+        (0..=latest_version)
+            .rev()
+            .map_while(|version| Some((version, self.0.root_hash(version)?)))
+            .find_map(|(version, hash)| (hash == root_hash).then_some(version))
+    }
+
+    fn l1_batch_number(version: u64) -> L1BatchNumber {
+        L1BatchNumber(u32::try_from(version).expect("integer overflow for L1 batch number"))
+    }
+
+    /// Returns the depth of the leaf with the specified key, i.e. the number of non-empty levels
+    /// in its Merkle path, or `None` if the key is not present in the tree. This allows estimating
+    /// the size of a proof returned by [`Self::entries_with_proofs()`] without building it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub fn key_depth(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        key: Key,
+    ) -> Result<Option<usize>, NoVersionError> {
+        let version = u64::from(l1_batch_number.0);
+        let _permit = self.acquire_proof_permit();
+        self.0.merkle_path_len(version, key)
+    }
+
+    /// Returns proofs for the specified `new_key` and its neighbors in the tree, i.e. entries
+    /// with the closest keys less than and greater than `new_key`. Together, these proofs show
+    /// that `new_key` is absent from the tree and where it would be inserted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn insertion_proof(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        new_key: Key,
+    ) -> Result<InsertionProof, NoVersionError> {
+        let version = u64::from(l1_batch_number.0);
+        let _permit = self.acquire_proof_permit();
+        let (low_key, high_key) = self.0.neighbor_keys(version, new_key)?;
+        let keys: Vec<_> = [Some(new_key), low_key, high_key]
+            .into_iter()
+            .flatten()
+            .collect();
+        let mut proofs = self.0.entries_with_proofs(version, &keys)?.into_iter();
+        let entry = proofs.next().unwrap();
+        // ^ `unwrap()` is safe: `keys` always contains `new_key`
+        let low_neighbor = low_key.and_then(|_| proofs.next());
+        let high_neighbor = high_key.and_then(|_| proofs.next());
+        Ok(InsertionProof {
+            entry,
+            low_neighbor,
+            high_neighbor,
+        })
+    }
+
+    /// Reconstructs the witness for the specified L1 batch processed with [lazy witnesses](ZkSyncTree::set_lazy_witnesses()).
+    /// Returns `None` if there are no persisted witness inputs for the batch, or if the tree versions required
+    /// for reconstruction are missing (e.g., pruned).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the persisted witness inputs are malformed.
+    pub fn reconstruct_witness(
+        &self,
+        l1_batch_number: L1BatchNumber,
+    ) -> Option<PrepareBasicCircuitsJob> {
+        let version = u64::from(l1_batch_number.0);
+        let raw_inputs = self.0.db.witness_inputs(version)?;
+        let instructions = serialization::deserialize_instructions(&raw_inputs)
+            .map_err(|err| err.with_context(ErrorContext::WitnessInputs(version)))
+            .unwrap_or_else(|err| panic!("{err}"));
+        let expected_root_hash = self.0.root_hash(version)?;
+        let (witness, root_hash) = self.replay_l1_batch(version, &instructions).ok()?;
+        if root_hash != expected_root_hash {
+            // The inputs are outdated. Inputs are removed on reverts, so this can only happen for inputs persisted
+            // before this was implemented, or if the database was modified externally.
+            tracing::warn!(
+                "Witness inputs for L1 batch #{l1_batch_number} are outdated: expected root hash \
+                 {expected_root_hash:?}, got {root_hash:?}"
+            );
+            return None;
+        }
+        Some(witness)
+    }
+
+    /// Rebuilds the witness for an already processed L1 batch by replaying the provided instructions
+    /// on top of the previous tree version. The tree is not modified. This allows obtaining a witness
+    /// for a batch processed in the lightweight mode, e.g. if the batch was later selected for proving.
+    /// The witness is identical to one produced by processing the batch in the full mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree versions for the L1 batch or the preceding L1 batch are missing (e.g., pruned),
+    /// or if replaying the instructions doesn't result in the root hash of the L1 batch.
+    pub fn rebuild_witness(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        instructions: &[TreeInstruction<StorageKey>],
+    ) -> Result<PrepareBasicCircuitsJob, RebuildWitnessError> {
+        let version = u64::from(l1_batch_number.0);
+        let (expected_root_hash, _) = self.0.root_info(version)?;
+        let instructions: Vec<_> = instructions
+            .iter()
+            .map(|instr| instr.map_key(ZkSyncTree::hash_storage_key))
+            .collect();
+        let (witness, root_hash) = self.replay_l1_batch(version, &instructions)?;
+        if root_hash != expected_root_hash {
+            return Err(RebuildWitnessError::RootHashMismatch {
+                expected: expected_root_hash,
+                actual: root_hash,
+            });
+        }
+        Ok(witness)
+    }
+
+    /// Replays an L1 batch with the specified `instructions` (which must have hashed keys) on top of the previous
+    /// tree version; changes are kept in memory only. Returns the witness for the batch and the resulting root hash.
+    fn replay_l1_batch(
+        &self,
+        version: u64,
+        instructions: &[TreeInstruction],
+    ) -> Result<(PrepareBasicCircuitsJob, ValueHash), NoVersionError> {
+        if version > 0 {
+            // Check that the previous version is not pruned.
+            load_root(&self.0.db, version - 1)?;
+        }
+        let _permit = self.acquire_proof_permit();
+
+        let mut tree =
+            MerkleTree::with_hasher(Patched::new(self.0.db.clone()), self.0.hasher.clone());
+        tree.truncate_recent_versions(version);
+        let starting_leaf_count = tree.latest_root().leaf_count();
+        let starting_root_hash = tree.latest_root_hash();
+        let output = tree.extend_with_proofs(instructions.to_vec());
+        let root_hash = output.root_hash().unwrap_or(starting_root_hash);
+
+        let witness = build_witness(
+            &self.0.hasher,
+            starting_leaf_count,
+            &output,
+            instructions,
+            true,
+            usize::MAX,
+            None,
+        );
+        let (witness, _) = witness.expect("witness size is unlimited");
+        Ok((witness, root_hash))
+    }
+}
+
+/// Consistent readonly view of a [`ZkSyncTree`] pinned to the latest tree version flushed to RocksDB
+/// at the time of its creation. Created via [`ZkSyncTreeReader::snapshot()`] or [`ZkSyncTree::snapshot()`].
+///
+/// # Consistency
+///
+/// A snapshot pins the root node of the tree version, and all reads start from this root. Tree versions
+/// are never modified after being written, and each [`ZkSyncTree::save()`] call persists all pending versions
+/// in a single atomic RocksDB write batch. Hence, a snapshot never observes a partially flushed state,
+/// and saving the tree does not block or influence existing snapshots: they continue serving data
+/// for the pinned version, while snapshots created after the save observe the newly flushed versions.
+///
+/// The only way a version can change is being reverted and then created anew (e.g., if a reverted L1 batch
+/// is processed again with different contents). Since the new version overwrites nodes of the old one,
+/// each read checks after loading nodes that the tree version still has the pinned root hash, and returns
+/// an error otherwise. Thus, returned data always corresponds to [`Self::root_hash()`]. The pinned version
+/// may also become unavailable if it is pruned; in this case, reads return an error as well.
+#[derive(Debug, Clone)]
+pub struct ZkSyncTreeSnapshot<H = Blake2Hasher> {
+    reader: ZkSyncTreeReader<H>,
+    pinned_root: Option<(u64, Root)>,
+    root_hash: ValueHash,
+}
+
+impl<H: HashTree + Clone> ZkSyncTreeSnapshot<H> {
+    /// Returns the L1 batch number this snapshot is pinned to, or `None` if the tree was empty
+    /// when the snapshot was created.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn l1_batch_number(&self) -> Option<L1BatchNumber> {
+        self.pinned_root.as_ref().map(|&(version, _)| {
+            L1BatchNumber(u32::try_from(version).expect("integer overflow for L1 batch number"))
+        })
+    }
+
+    /// Returns the root hash of the tree at the pinned version.
+    pub fn root_hash(&self) -> ValueHash {
+        self.root_hash
+    }
+
+    /// Reads entries together with Merkle proofs with the specified keys from the pinned tree version.
+    /// The entries are returned in the same order as requested; their proofs can be verified against
+    /// [`Self::root_hash()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the snapshot was created for an empty tree, or if the pinned version
+    /// was pruned or replaced since then.
+    pub fn entries_with_proofs(
+        &self,
+        keys: &[Key],
+    ) -> Result<Vec<TreeEntryWithProof>, SnapshotError> {
+        let Some((version, root)) = &self.pinned_root else {
+            return Err(EmptyTreeError.into());
+        };
+        let version = *version;
+        let _permit = self.reader.acquire_proof_permit();
+        let entries = self
+            .reader
+            .0
+            .entries_with_proofs_for_root(version, root.clone(), keys);
+
+        // Overwriting a version is atomic, so if any of the loaded nodes belongs to a new version,
+        // the root check below will observe the new root.
+        let (actual_root_hash, _) = self.reader.0.root_info(version)?;
+        if actual_root_hash != self.root_hash {
+            return Err(SnapshotError::VersionReplaced {
+                version,
+                expected: self.root_hash,
+                actual: actual_root_hash,
+            });
+        }
+        Ok(entries)
+    }
+}
@@ -0,0 +1,91 @@
+//! RocksDB tuning presets for different tree workloads.
+
+use std::{path::Path, time::Duration};
+
+use zksync_storage::{rocksdb, RocksDB, RocksDBOptions, StalledWritesRetries};
+
+use super::ZkSyncTree;
+use crate::RocksDBWrapper;
+
+const MIB: usize = 1_024 * 1_024;
+
+/// RocksDB tuning profile for the tree database used by [`ZkSyncTree::with_profile()`].
+///
+/// Regardless of the profile, RocksDB uses Bloom filters with 10 bits per key for all column families.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub enum TreeWorkloadProfile {
+    /// Profile for read-heavy workloads, such as serving Merkle proofs to light clients. Applies the following settings:
+    ///
+    /// - 1 GiB block cache
+    /// - Indices and Bloom filters are loaded into RAM on startup rather than managed by the block cache
+    /// - Default memtable capacity
+    /// - Multi-get chunk size of 500 keys, so that reads are parallelized among `rayon` threads
+    ReadOptimized,
+    /// Profile for write-heavy workloads, such as the tree updated by the state keeper. Applies the following settings:
+    ///
+    /// - 128 MiB block cache
+    /// - Indices and Bloom filters are managed by the block cache, bounding RAM usage
+    /// - 512 MiB memtable capacity for the tree column family, so that RocksDB compacts data less frequently
+    ///   and experiences fewer write stalls
+    /// - 30 s timeout for stalled writes
+    /// - No multi-get chunking
+    WriteOptimized,
+    /// Custom RocksDB options. No multi-get chunking is applied.
+    Custom(RocksDBOptions),
+}
+
+impl TreeWorkloadProfile {
+    /// Returns RocksDB options corresponding to this profile.
+    pub fn rocksdb_options(&self) -> RocksDBOptions {
+        match self {
+            Self::ReadOptimized => RocksDBOptions {
+                block_cache_capacity: Some(1_024 * MIB),
+                include_indices_and_filters_in_block_cache: false,
+                large_memtable_capacity: None,
+                ..RocksDBOptions::default()
+            },
+            Self::WriteOptimized => RocksDBOptions {
+                block_cache_capacity: Some(128 * MIB),
+                include_indices_and_filters_in_block_cache: true,
+                large_memtable_capacity: Some(512 * MIB),
+                stalled_writes_retries: StalledWritesRetries::new(Duration::from_secs(30)),
+                ..RocksDBOptions::default()
+            },
+            Self::Custom(options) => *options,
+        }
+    }
+
+    fn multi_get_chunk_size(&self) -> usize {
+        match self {
+            Self::ReadOptimized => 500,
+            Self::WriteOptimized | Self::Custom(_) => usize::MAX,
+        }
+    }
+
+    /// Opens the tree database at the specified directory with settings from this profile.
+    ///
+    /// # Errors
+    ///
+    /// Propagates RocksDB I/O errors.
+    pub fn open_db(&self, path: &Path) -> Result<RocksDBWrapper, rocksdb::Error> {
+        let db = RocksDB::with_options(path, self.rocksdb_options())?;
+        let mut db = RocksDBWrapper::from(db);
+        db.set_multi_get_chunk_size(self.multi_get_chunk_size());
+        Ok(db)
+    }
+}
+
+impl ZkSyncTree {
+    /// Creates a tree with the full processing mode, opening its database at the specified directory
+    /// with settings from the specified `profile`. To create a tree with the lightweight mode, open
+    /// the database via [`TreeWorkloadProfile::open_db()`] and pass it to [`Self::new_lightweight()`].
+    ///
+    /// # Errors
+    ///
+    /// Propagates RocksDB I/O errors.
+    pub fn with_profile(path: &Path, profile: TreeWorkloadProfile) -> Result<Self, rocksdb::Error> {
+        let db = profile.open_db(path)?;
+        Ok(Self::new(db))
+    }
+}
@@ -0,0 +1,68 @@
+//! Consistency checks for bundles of Merkle proofs.
+
+use zksync_crypto::hasher::blake2::Blake2Hasher;
+
+use crate::{
+    types::{TreeEntryWithProof, ValueHash},
+    HashTree,
+};
+
+/// Error returned by [`verify_proofs_consistent()`].
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ProofInconsistencyError {
+    /// No proofs were provided.
+    #[error("no proofs provided")]
+    Empty,
+    /// Proof specifies a non-default value for a missing key.
+    #[error("proof #{index} has zero leaf index, but non-default value")]
+    InvalidMissingValue {
+        /// 0-based index of the proof in the bundle.
+        index: usize,
+    },
+    /// Proof restores a root hash different from the one restored from the first proof in the bundle.
+    #[error(
+        "proof #{index} restores root hash {actual:?}, while previous proofs restore {expected:?}"
+    )]
+    RootMismatch {
+        /// 0-based index of the first divergent proof in the bundle.
+        index: usize,
+        /// Root hash restored from the first proof.
+        expected: ValueHash,
+        /// Root hash restored from the divergent proof.
+        actual: ValueHash,
+    },
+}
+
+/// Checks that all proofs in the bundle (e.g., received from an untrusted source) restore the same tree root hash,
+/// i.e., were produced for the same tree version. Unlike [`TreeEntryWithProof::verify()`], this doesn't require
+/// the trusted root hash; the common root hash is returned instead so that it can be checked by the caller.
+///
+/// # Errors
+///
+/// Returns an error if the bundle is empty, or if a proof is invalid or restores a different root hash.
+/// In the latter case, the error reports the first divergent proof.
+pub fn verify_proofs_consistent(
+    proofs: &[TreeEntryWithProof],
+) -> Result<ValueHash, ProofInconsistencyError> {
+    let hasher: &dyn HashTree = &Blake2Hasher;
+    let mut common_root_hash = None;
+    for (index, proof) in proofs.iter().enumerate() {
+        if proof.base.leaf_index == 0 && !proof.base.value.is_zero() {
+            return Err(ProofInconsistencyError::InvalidMissingValue { index });
+        }
+        let root_hash = hasher.fold_merkle_path(&proof.merkle_path, proof.base);
+        match common_root_hash {
+            None => common_root_hash = Some(root_hash),
+            Some(expected) if expected != root_hash => {
+                return Err(ProofInconsistencyError::RootMismatch {
+                    index,
+                    expected,
+                    actual: root_hash,
+                });
+            }
+            Some(_) => { /* root hash matches */ }
+        }
+    }
+    common_root_hash.ok_or(ProofInconsistencyError::Empty)
+}
@@ -0,0 +1,163 @@
+//! Commitments to contiguous ranges of leaf enumeration indices.
+
+use anyhow::ensure;
+use zksync_crypto::hasher::{blake2::Blake2Hasher, Hasher};
+use zksync_types::L1BatchNumber;
+
+use super::ZkSyncTreeReader;
+use crate::{
+    getters::load_root,
+    types::{TreeEntry, TreeEntryWithProof, ValueHash, KEY_SIZE},
+    HashTree, NoVersionError,
+};
+
+/// Error producing a [`RangeCommitment`].
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum RangeCommitmentError {
+    /// Tree version for the requested L1 batch is missing.
+    #[error(transparent)]
+    NoVersion(#[from] NoVersionError),
+    /// Requested range of enumeration indices is empty or is not fully contained in the tree.
+    #[error(
+        "invalid range of enumeration indices {from_index}..={to_index}; the tree has {leaf_count} leaves"
+    )]
+    InvalidRange {
+        /// Start of the requested range (inclusive).
+        from_index: u64,
+        /// End of the requested range (inclusive).
+        to_index: u64,
+        /// Number of leaves in the tree version.
+        leaf_count: u64,
+    },
+}
+
+/// Commitment to the tree leaves with enumeration indices in a certain range, together with proofs
+/// that these are all leaves in the range. Returned by [`ZkSyncTreeReader::range_commitment()`].
+///
+/// # Format
+///
+/// A commitment to leaves with enumeration indices `from_index..=to_index` is the Blake2s-256 hash
+/// of the concatenated leaves ordered by increasing index. Each leaf is encoded as its key
+/// (32 bytes, big-endian), value hash (32 bytes) and enumeration index (8 bytes, big-endian).
+///
+/// Enumeration indices are assigned to leaves sequentially starting from 1 and are never reused,
+/// so a set of leaves with all indices in the range, each with a valid Merkle proof, is complete.
+/// Thus, a commitment is accompanied by proofs for all leaves in the range rather than only
+/// the boundary ones.
+#[derive(Debug)]
+pub struct RangeCommitment {
+    /// Start of the committed range of enumeration indices (inclusive).
+    pub from_index: u64,
+    /// End of the committed range of enumeration indices (inclusive).
+    pub to_index: u64,
+    /// Commitment to the leaves in the range.
+    pub hash: ValueHash,
+    /// Leaves in the range ordered by enumeration index, together with their Merkle proofs.
+    pub entries: Vec<TreeEntryWithProof>,
+}
+
+impl RangeCommitment {
+    fn compute_hash<'a>(entries: impl Iterator<Item = &'a TreeEntry>) -> ValueHash {
+        let mut bytes = vec![];
+        for entry in entries {
+            let mut key_bytes = [0_u8; KEY_SIZE];
+            entry.key.to_big_endian(&mut key_bytes);
+            bytes.extend_from_slice(&key_bytes);
+            bytes.extend_from_slice(entry.value.as_bytes());
+            bytes.extend_from_slice(&entry.leaf_index.to_be_bytes());
+        }
+        Blake2Hasher.hash_bytes(&bytes)
+    }
+
+    /// Verifies this commitment against the trusted root hash of the tree.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entries do not cover the committed range, if any of the Merkle proofs
+    /// is invalid, or if the commitment hash doesn't match the entries.
+    pub fn verify(&self, trusted_root_hash: ValueHash) -> anyhow::Result<()> {
+        ensure!(
+            self.from_index > 0 && self.from_index <= self.to_index,
+            "invalid range of enumeration indices"
+        );
+        let expected_len = self.to_index - self.from_index + 1;
+        ensure!(
+            self.entries.len() as u64 == expected_len,
+            "unexpected number of entries: expected {expected_len}, got {}",
+            self.entries.len()
+        );
+
+        let hasher: &dyn HashTree = &Blake2Hasher;
+        for (entry, expected_index) in self.entries.iter().zip(self.from_index..) {
+            ensure!(
+                entry.base.leaf_index == expected_index,
+                "unexpected enumeration index for entry: expected {expected_index}, got {}",
+                entry.base.leaf_index
+            );
+            let root_hash = hasher.fold_merkle_path(&entry.merkle_path, entry.base);
+            ensure!(
+                root_hash == trusted_root_hash,
+                "root hash mismatch for entry with enumeration index {expected_index}"
+            );
+        }
+
+        let hash = Self::compute_hash(self.entries.iter().map(|entry| &entry.base));
+        ensure!(hash == self.hash, "commitment hash mismatch");
+        Ok(())
+    }
+}
+
+impl<H: HashTree + Clone> ZkSyncTreeReader<H> {
+    /// Computes a [commitment](RangeCommitment) to the leaves with enumeration indices in `from_index..=to_index`
+    /// at the specified L1 batch.
+    ///
+    /// Since the tree is not indexed by enumeration indices, this traverses all tree leaves,
+    /// so it is slow for large trees and should be used out-of-band.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree version for the L1 batch is missing, or if the range is empty
+    /// or is not fully contained in the tree (enumeration indices start from 1).
+    pub fn range_commitment(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        from_index: u64,
+        to_index: u64,
+    ) -> Result<RangeCommitment, RangeCommitmentError> {
+        let version = u64::from(l1_batch_number.0);
+        let _permit = self.acquire_proof_permit();
+        let root = load_root(&self.0.db, version)?;
+        let leaf_count = root.leaf_count();
+        if from_index == 0 || from_index > to_index || to_index > leaf_count {
+            return Err(RangeCommitmentError::InvalidRange {
+                from_index,
+                to_index,
+                leaf_count,
+            });
+        }
+
+        #[allow(clippy::cast_possible_truncation)] // the range is bounded by the number of leaves
+        let mut keys = vec![None; (to_index - from_index + 1) as usize];
+        for entry in self.leaves_from_root(root) {
+            if (from_index..=to_index).contains(&entry.leaf_index) {
+                #[allow(clippy::cast_possible_truncation)] // checked above
+                let offset = (entry.leaf_index - from_index) as usize;
+                keys[offset] = Some(entry.key);
+            }
+        }
+        let keys: Vec<_> = keys
+            .into_iter()
+            .map(|key| key.expect("leaf enumeration indices are not contiguous"))
+            .collect();
+
+        let entries = self.0.entries_with_proofs(version, &keys)?;
+        let hash = RangeCommitment::compute_hash(entries.iter().map(|entry| &entry.base));
+        Ok(RangeCommitment {
+            from_index,
+            to_index,
+            hash,
+            entries,
+        })
+    }
+}
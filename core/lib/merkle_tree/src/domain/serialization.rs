@@ -0,0 +1,641 @@
+//! Binary serialization of [`TreeMetadata`].
+//!
+//! # Format
+//!
+//! The encoding starts with a format version byte (currently, [`METADATA_FORMAT_VERSION`]), followed by:
+//!
+//! - Root hash (32 bytes)
+//! - `rollup_last_leaf_index` (LEB128)
+//! - Witness presence flag (1 byte; 0 or 1), optionally followed by the witness:
+//!   - Next enumeration index (LEB128)
+//!   - Number of storage logs (LEB128), followed by the logs
+//! - `read_count`, `write_count` and `noop_writes` (LEB128 each)
+//! - Tree logs presence flag (1 byte; 0 or 1), optionally followed by a length-prefixed (LEB128) list of logs.
+//!   Each log is encoded as a tag byte (0 for insertions, 1 for updates, 2 for reads, 3 for reads of missing keys);
+//!   updates and reads additionally contain the leaf index (LEB128) and the previous / read value (32 bytes).
+//! - Witness stats presence flag (1 byte; 0 or 1), optionally followed by `entries`, `total_hashes`,
+//!   `max_path_len` and `min_path_len` (LEB128 each)
+//!
+//! Metadata encoded with format version 0 ends after the witness. When such metadata is restored,
+//! instruction counts are set to 0, and tree logs and witness stats are set to `None`.
+//!
+//! Each storage log is encoded as:
+//!
+//! - Root hash (32 bytes)
+//! - Bit flags (1 byte): `is_write` (bit 0) and `first_write` (bit 1)
+//! - Leaf hashed key (32 bytes, big-endian)
+//! - Leaf enumeration index (LEB128)
+//! - Written value (32 bytes)
+//! - Read value (32 bytes)
+//! - Number of skipped Merkle path hashes (LEB128), followed by a length-prefixed (LEB128) list
+//!   of the remaining hashes (32 bytes each)
+//!
+//! As with [`PrepareBasicCircuitsJob`], only the first log contains a full Merkle path; for other logs,
+//! the hashes shared with the first log are skipped.
+//!
+//! Witness inputs (i.e., instructions with hashed keys) persisted for lazy witness reconstruction
+//! are encoded as a format version byte, followed by a length-prefixed (LEB128) list of instructions.
+//! Each instruction is encoded as a tag byte (0 for reads, 1 for writes) and a key (32 bytes, big-endian);
+//! writes additionally contain the written value (32 bytes) and the leaf index (LEB128).
+//!
+//! Entries with proofs (as returned by [`ZkSyncTreeReader::entries_with_proofs_encoded()`]) are encoded
+//! as a format version byte, followed by a length-prefixed (LEB128) list of entries. Each entry is encoded as:
+//!
+//! - Leaf hashed key (32 bytes, big-endian)
+//! - Value hash (32 bytes)
+//! - Leaf enumeration index (LEB128)
+//! - Length-prefixed (LEB128) list of Merkle path hashes (32 bytes each), ordered in the same way
+//!   as in [`TreeEntryWithProof::merkle_path`]
+
+use zksync_prover_interface::inputs::{PrepareBasicCircuitsJob, StorageLogMetadata};
+
+#[cfg(doc)]
+use super::ZkSyncTreeReader;
+use super::{TreeMetadata, WitnessStats};
+use crate::{
+    errors::{DeserializeError, DeserializeErrorKind, ErrorContext},
+    types::{
+        Key, TreeEntry, TreeEntryWithProof, TreeInstruction, TreeLogEntry, ValueHash, HASH_SIZE,
+        KEY_SIZE,
+    },
+};
+
+/// Current version of the binary format for witness inputs and entries with proofs.
+const FORMAT_VERSION: u8 = 0;
+/// Current version of the binary format for [`TreeMetadata`]. Version 1 adds instruction counts, tree logs
+/// and witness stats; metadata encoded with version 0 can still be restored.
+const METADATA_FORMAT_VERSION: u8 = 1;
+
+const IS_WRITE_FLAG: u8 = 1;
+const FIRST_WRITE_FLAG: u8 = 2;
+
+fn read_bytes<const N: usize>(bytes: &mut &[u8]) -> Result<[u8; N], DeserializeErrorKind> {
+    if bytes.len() < N {
+        return Err(DeserializeErrorKind::UnexpectedEof);
+    }
+    let (head, rest) = bytes.split_at(N);
+    *bytes = rest;
+    Ok(head.try_into().unwrap())
+    // ^ `unwrap()` is safe by construction
+}
+
+fn read_u64(bytes: &mut &[u8]) -> Result<u64, DeserializeErrorKind> {
+    leb128::read::unsigned(bytes).map_err(DeserializeErrorKind::Leb128)
+}
+
+fn read_len(bytes: &mut &[u8]) -> Result<usize, DeserializeErrorKind> {
+    let len = read_u64(bytes)?;
+    usize::try_from(len).map_err(|_| DeserializeErrorKind::UnexpectedEof)
+}
+
+fn write_u64(buffer: &mut Vec<u8>, value: u64) {
+    leb128::write::unsigned(buffer, value).unwrap();
+    // ^ `unwrap()` is safe; writing to a `Vec<u8>` always succeeds
+}
+
+impl TreeMetadata {
+    /// Serializes this metadata (including the witness, if any) into a compact, versioned
+    /// binary format. The serialized metadata can be restored using [`Self::from_bytes()`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = vec![METADATA_FORMAT_VERSION];
+        buffer.extend_from_slice(self.root_hash.as_bytes());
+        write_u64(&mut buffer, self.rollup_last_leaf_index);
+
+        if let Some(witness) = &self.witness {
+            buffer.push(1);
+            Self::serialize_witness(&mut buffer, witness);
+        } else {
+            buffer.push(0);
+        }
+        write_u64(&mut buffer, self.read_count as u64);
+        write_u64(&mut buffer, self.write_count as u64);
+        write_u64(&mut buffer, self.noop_writes as u64);
+
+        if let Some(logs) = &self.logs {
+            buffer.push(1);
+            write_u64(&mut buffer, logs.len() as u64);
+            for log in logs {
+                Self::serialize_tree_log(&mut buffer, log);
+            }
+        } else {
+            buffer.push(0);
+        }
+
+        if let Some(stats) = &self.witness_stats {
+            buffer.push(1);
+            write_u64(&mut buffer, stats.entries as u64);
+            write_u64(&mut buffer, stats.total_hashes as u64);
+            write_u64(&mut buffer, stats.max_path_len as u64);
+            write_u64(&mut buffer, stats.min_path_len as u64);
+        } else {
+            buffer.push(0);
+        }
+        buffer
+    }
+
+    fn serialize_witness(buffer: &mut Vec<u8>, witness: &PrepareBasicCircuitsJob) {
+        write_u64(buffer, witness.next_enumeration_index());
+
+        let logs = witness.clone().into_merkle_paths();
+        write_u64(buffer, logs.len() as u64);
+        let mut first_path: Option<Vec<[u8; HASH_SIZE]>> = None;
+        for log in logs {
+            let skipped_len = first_path.as_ref().map_or(0, |first_path| {
+                let hash_pairs = log.merkle_paths.iter().zip(first_path);
+                hash_pairs
+                    .position(|(hash, first_path_hash)| hash != first_path_hash)
+                    .unwrap_or(log.merkle_paths.len())
+            });
+            Self::serialize_log(buffer, &log, skipped_len);
+            if first_path.is_none() {
+                first_path = Some(log.merkle_paths);
+            }
+        }
+    }
+
+    fn serialize_tree_log(buffer: &mut Vec<u8>, log: &TreeLogEntry) {
+        match log {
+            TreeLogEntry::Inserted => buffer.push(0),
+            TreeLogEntry::Updated {
+                leaf_index,
+                previous_value,
+            } => {
+                buffer.push(1);
+                write_u64(buffer, *leaf_index);
+                buffer.extend_from_slice(previous_value.as_bytes());
+            }
+            TreeLogEntry::Read { leaf_index, value } => {
+                buffer.push(2);
+                write_u64(buffer, *leaf_index);
+                buffer.extend_from_slice(value.as_bytes());
+            }
+            TreeLogEntry::ReadMissingKey => buffer.push(3),
+        }
+    }
+
+    fn serialize_log(buffer: &mut Vec<u8>, log: &StorageLogMetadata, skipped_len: usize) {
+        buffer.extend_from_slice(&log.root_hash);
+        let mut flags = 0;
+        if log.is_write {
+            flags |= IS_WRITE_FLAG;
+        }
+        if log.first_write {
+            flags |= FIRST_WRITE_FLAG;
+        }
+        buffer.push(flags);
+
+        let mut key_bytes = [0_u8; KEY_SIZE];
+        log.leaf_hashed_key.to_big_endian(&mut key_bytes);
+        buffer.extend_from_slice(&key_bytes);
+        write_u64(buffer, log.leaf_enumeration_index);
+        buffer.extend_from_slice(&log.value_written);
+        buffer.extend_from_slice(&log.value_read);
+
+        let hashes = &log.merkle_paths[skipped_len..];
+        write_u64(buffer, skipped_len as u64);
+        write_u64(buffer, hashes.len() as u64);
+        for hash in hashes {
+            buffer.extend_from_slice(hash);
+        }
+    }
+
+    /// Restores metadata serialized using [`Self::to_bytes()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` are malformed or use an unsupported format version.
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, DeserializeError> {
+        Self::deserialize(&mut bytes).map_err(|err| err.with_context(ErrorContext::TreeMetadata))
+    }
+
+    fn deserialize(bytes: &mut &[u8]) -> Result<Self, DeserializeError> {
+        let [format_version] = read_bytes::<1>(bytes)?;
+        if format_version > METADATA_FORMAT_VERSION {
+            return Err(DeserializeErrorKind::UnsupportedFormatVersion(format_version).into());
+        }
+        let root_hash = ValueHash(read_bytes(bytes)?);
+        let rollup_last_leaf_index = read_u64(bytes)?;
+
+        let witness = match read_bytes::<1>(bytes)? {
+            [0] => None,
+            [1] => Some(Self::deserialize_witness(bytes)?),
+            [flag] => return Err(DeserializeErrorKind::InvalidFlags(flag).into()),
+        };
+        let mut metadata = Self {
+            root_hash,
+            rollup_last_leaf_index,
+            witness,
+            read_count: 0,
+            write_count: 0,
+            noop_writes: 0,
+            logs: None,
+            witness_stats: None,
+        };
+        if format_version > 0 {
+            metadata.read_count = read_len(bytes)?;
+            metadata.write_count = read_len(bytes)?;
+            metadata.noop_writes = read_len(bytes)?;
+            metadata.logs = match read_bytes::<1>(bytes)? {
+                [0] => None,
+                [1] => Some(Self::deserialize_tree_logs(bytes)?),
+                [flag] => return Err(DeserializeErrorKind::InvalidFlags(flag).into()),
+            };
+            metadata.witness_stats = match read_bytes::<1>(bytes)? {
+                [0] => None,
+                [1] => Some(WitnessStats {
+                    entries: read_len(bytes)?,
+                    total_hashes: read_len(bytes)?,
+                    max_path_len: read_len(bytes)?,
+                    min_path_len: read_len(bytes)?,
+                }),
+                [flag] => return Err(DeserializeErrorKind::InvalidFlags(flag).into()),
+            };
+        }
+        if !bytes.is_empty() {
+            return Err(DeserializeErrorKind::TrailingBytes.into());
+        }
+        Ok(metadata)
+    }
+
+    fn deserialize_tree_logs(bytes: &mut &[u8]) -> Result<Vec<TreeLogEntry>, DeserializeErrorKind> {
+        let log_count = read_len(bytes)?;
+        let mut logs = Vec::with_capacity(log_count.min(bytes.len()));
+        for _ in 0..log_count {
+            let log = match read_bytes::<1>(bytes)? {
+                [0] => TreeLogEntry::Inserted,
+                [1] => TreeLogEntry::Updated {
+                    leaf_index: read_u64(bytes)?,
+                    previous_value: ValueHash(read_bytes(bytes)?),
+                },
+                [2] => TreeLogEntry::Read {
+                    leaf_index: read_u64(bytes)?,
+                    value: ValueHash(read_bytes(bytes)?),
+                },
+                [3] => TreeLogEntry::ReadMissingKey,
+                [tag] => return Err(DeserializeErrorKind::InvalidFlags(tag)),
+            };
+            logs.push(log);
+        }
+        Ok(logs)
+    }
+
+    fn deserialize_witness(bytes: &mut &[u8]) -> Result<PrepareBasicCircuitsJob, DeserializeError> {
+        let next_enumeration_index = read_u64(bytes)?;
+        let log_count = read_len(bytes)?;
+        let mut witness = PrepareBasicCircuitsJob::new(next_enumeration_index);
+        let mut first_path: Option<Vec<[u8; HASH_SIZE]>> = None;
+        for idx in 0..log_count {
+            let log = Self::deserialize_log(bytes, first_path.as_deref())
+                .map_err(|err| err.with_context(ErrorContext::WitnessLog(idx)))?;
+            if first_path.is_none() {
+                first_path = Some(log.merkle_paths.clone());
+            }
+            witness.push_merkle_path(log);
+        }
+        Ok(witness)
+    }
+
+    fn deserialize_log(
+        bytes: &mut &[u8],
+        first_path: Option<&[[u8; HASH_SIZE]]>,
+    ) -> Result<StorageLogMetadata, DeserializeErrorKind> {
+        let root_hash = read_bytes(bytes)?;
+        let [flags] = read_bytes::<1>(bytes)?;
+        if flags & !(IS_WRITE_FLAG | FIRST_WRITE_FLAG) != 0 {
+            return Err(DeserializeErrorKind::InvalidFlags(flags));
+        }
+        let leaf_hashed_key = Key::from_big_endian(&read_bytes::<KEY_SIZE>(bytes)?);
+        let leaf_enumeration_index = read_u64(bytes)?;
+        let value_written = read_bytes(bytes)?;
+        let value_read = read_bytes(bytes)?;
+
+        let skipped_len = read_len(bytes)?;
+        let hash_count = read_len(bytes)?;
+        let skipped_hashes = first_path.unwrap_or_default();
+        if skipped_hashes.len() < skipped_len || bytes.len() / HASH_SIZE < hash_count {
+            return Err(DeserializeErrorKind::UnexpectedEof);
+        }
+        let mut merkle_paths = Vec::with_capacity(skipped_len + hash_count);
+        merkle_paths.extend_from_slice(&skipped_hashes[..skipped_len]);
+        for _ in 0..hash_count {
+            merkle_paths.push(read_bytes(bytes)?);
+        }
+
+        Ok(StorageLogMetadata {
+            root_hash,
+            is_write: flags & IS_WRITE_FLAG != 0,
+            first_write: flags & FIRST_WRITE_FLAG != 0,
+            merkle_paths,
+            leaf_hashed_key,
+            leaf_enumeration_index,
+            value_written,
+            value_read,
+        })
+    }
+}
+
+pub(super) fn serialize_instructions(instructions: &[TreeInstruction]) -> Vec<u8> {
+    let mut buffer = vec![FORMAT_VERSION];
+    write_u64(&mut buffer, instructions.len() as u64);
+    let mut key_bytes = [0_u8; KEY_SIZE];
+    for instruction in instructions {
+        instruction.key().to_big_endian(&mut key_bytes);
+        match instruction {
+            TreeInstruction::Read(_) => {
+                buffer.push(0);
+                buffer.extend_from_slice(&key_bytes);
+            }
+            TreeInstruction::Write(entry) => {
+                buffer.push(1);
+                buffer.extend_from_slice(&key_bytes);
+                buffer.extend_from_slice(entry.value.as_bytes());
+                write_u64(&mut buffer, entry.leaf_index);
+            }
+        }
+    }
+    buffer
+}
+
+pub(super) fn deserialize_instructions(
+    mut bytes: &[u8],
+) -> Result<Vec<TreeInstruction>, DeserializeErrorKind> {
+    let bytes = &mut bytes;
+    let [format_version] = read_bytes::<1>(bytes)?;
+    if format_version != FORMAT_VERSION {
+        return Err(DeserializeErrorKind::UnsupportedFormatVersion(
+            format_version,
+        ));
+    }
+    let instruction_count = read_len(bytes)?;
+    let mut instructions = Vec::with_capacity(instruction_count.min(bytes.len()));
+    for _ in 0..instruction_count {
+        let [tag] = read_bytes::<1>(bytes)?;
+        let key = Key::from_big_endian(&read_bytes::<KEY_SIZE>(bytes)?);
+        let instruction = match tag {
+            0 => TreeInstruction::Read(key),
+            1 => {
+                let value = ValueHash(read_bytes(bytes)?);
+                let leaf_index = read_u64(bytes)?;
+                TreeInstruction::Write(TreeEntry::new(key, leaf_index, value))
+            }
+            _ => return Err(DeserializeErrorKind::InvalidFlags(tag)),
+        };
+        instructions.push(instruction);
+    }
+    if !bytes.is_empty() {
+        return Err(DeserializeErrorKind::TrailingBytes);
+    }
+    Ok(instructions)
+}
+
+pub(super) fn serialize_entries_with_proofs(entries: &[TreeEntryWithProof]) -> Vec<u8> {
+    let mut buffer = vec![FORMAT_VERSION];
+    write_u64(&mut buffer, entries.len() as u64);
+    let mut key_bytes = [0_u8; KEY_SIZE];
+    for entry in entries {
+        entry.base.key.to_big_endian(&mut key_bytes);
+        buffer.extend_from_slice(&key_bytes);
+        buffer.extend_from_slice(entry.base.value.as_bytes());
+        write_u64(&mut buffer, entry.base.leaf_index);
+        write_u64(&mut buffer, entry.merkle_path.len() as u64);
+        for hash in &entry.merkle_path {
+            buffer.extend_from_slice(hash.as_bytes());
+        }
+    }
+    buffer
+}
+
+/// Decodes entries with proofs encoded by [`ZkSyncTreeReader::entries_with_proofs_encoded()`].
+///
+/// # Errors
+///
+/// Returns an error if `bytes` are malformed or use an unsupported format version.
+pub fn decode_entries_with_proofs(
+    mut bytes: &[u8],
+) -> Result<Vec<TreeEntryWithProof>, DeserializeError> {
+    let bytes = &mut bytes;
+    let [format_version] = read_bytes::<1>(bytes)?;
+    if format_version != FORMAT_VERSION {
+        return Err(DeserializeErrorKind::UnsupportedFormatVersion(format_version).into());
+    }
+    let entry_count = read_len(bytes)?;
+    let mut entries = Vec::with_capacity(entry_count.min(bytes.len()));
+    for idx in 0..entry_count {
+        let entry = deserialize_entry_with_proof(bytes)
+            .map_err(|err| err.with_context(ErrorContext::EntryWithProof(idx)))?;
+        entries.push(entry);
+    }
+    if !bytes.is_empty() {
+        return Err(DeserializeErrorKind::TrailingBytes.into());
+    }
+    Ok(entries)
+}
+
+fn deserialize_entry_with_proof(
+    bytes: &mut &[u8],
+) -> Result<TreeEntryWithProof, DeserializeErrorKind> {
+    let key = Key::from_big_endian(&read_bytes::<KEY_SIZE>(bytes)?);
+    let value = ValueHash(read_bytes(bytes)?);
+    let leaf_index = read_u64(bytes)?;
+    let path_len = read_len(bytes)?;
+    let mut merkle_path = Vec::with_capacity(path_len.min(bytes.len() / HASH_SIZE));
+    for _ in 0..path_len {
+        merkle_path.push(ValueHash(read_bytes(bytes)?));
+    }
+    Ok(TreeEntry::new(key, leaf_index, value).with_merkle_path(merkle_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_log(idx: u8, is_write: bool) -> StorageLogMetadata {
+        let mut merkle_paths = vec![[0_u8; HASH_SIZE]; 256];
+        // Make the paths diverge at different levels.
+        for hash in &mut merkle_paths[(200 + usize::from(idx))..] {
+            *hash = [idx; HASH_SIZE];
+        }
+        StorageLogMetadata {
+            root_hash: [idx; HASH_SIZE],
+            is_write,
+            first_write: is_write && idx % 2 == 0,
+            merkle_paths,
+            leaf_hashed_key: Key::from(u64::from(idx) << 40),
+            leaf_enumeration_index: u64::from(idx) * 1_000,
+            value_written: if is_write { [idx; 32] } else { [0; 32] },
+            value_read: [idx + 1; 32],
+        }
+    }
+
+    fn mock_metadata(witness: Option<PrepareBasicCircuitsJob>) -> TreeMetadata {
+        TreeMetadata {
+            root_hash: ValueHash::repeat_byte(0x42),
+            rollup_last_leaf_index: 1_000_000,
+            witness,
+            read_count: 0,
+            write_count: 0,
+            noop_writes: 0,
+            logs: None,
+            witness_stats: None,
+        }
+    }
+
+    fn assert_metadata_eq(restored: &TreeMetadata, metadata: &TreeMetadata) {
+        assert_eq!(restored.root_hash, metadata.root_hash);
+        assert_eq!(
+            restored.rollup_last_leaf_index,
+            metadata.rollup_last_leaf_index
+        );
+        assert_eq!(restored.read_count, metadata.read_count);
+        assert_eq!(restored.write_count, metadata.write_count);
+        assert_eq!(restored.noop_writes, metadata.noop_writes);
+        assert_eq!(restored.logs, metadata.logs);
+        assert_eq!(restored.witness_stats, metadata.witness_stats);
+    }
+
+    #[test]
+    fn serializing_metadata_without_witness() {
+        let metadata = mock_metadata(None);
+        let bytes = metadata.to_bytes();
+        assert_eq!(bytes[0], METADATA_FORMAT_VERSION);
+        assert_eq!(bytes.len(), 1 + 32 + 3 + 1 + 3 + 1 + 1);
+
+        let restored = TreeMetadata::from_bytes(&bytes).unwrap();
+        assert_metadata_eq(&restored, &metadata);
+        assert!(restored.witness.is_none());
+    }
+
+    #[test]
+    fn serializing_metadata_with_counts_logs_and_stats() {
+        let metadata = TreeMetadata {
+            read_count: 2,
+            write_count: 3,
+            noop_writes: 1,
+            logs: Some(vec![
+                TreeLogEntry::Inserted,
+                TreeLogEntry::Updated {
+                    leaf_index: 5,
+                    previous_value: ValueHash::repeat_byte(1),
+                },
+                TreeLogEntry::Read {
+                    leaf_index: 1_000,
+                    value: ValueHash::repeat_byte(2),
+                },
+                TreeLogEntry::ReadMissingKey,
+                TreeLogEntry::Updated {
+                    leaf_index: 7,
+                    previous_value: ValueHash::zero(),
+                },
+            ]),
+            witness_stats: Some(WitnessStats {
+                entries: 4,
+                total_hashes: 300,
+                max_path_len: 256,
+                min_path_len: 10,
+            }),
+            ..mock_metadata(None)
+        };
+        let bytes = metadata.to_bytes();
+        let restored = TreeMetadata::from_bytes(&bytes).unwrap();
+        assert_metadata_eq(&restored, &metadata);
+    }
+
+    #[test]
+    fn deserializing_legacy_metadata() {
+        let mut bytes = vec![0];
+        bytes.extend_from_slice(&[0x42; 32]);
+        write_u64(&mut bytes, 1_000_000);
+        bytes.push(0);
+
+        let restored = TreeMetadata::from_bytes(&bytes).unwrap();
+        assert_metadata_eq(&restored, &mock_metadata(None));
+        assert!(restored.witness.is_none());
+    }
+
+    #[test]
+    fn serializing_metadata_with_witness() {
+        let logs: Vec<_> = (0..10).map(|idx| mock_log(idx, idx % 3 != 0)).collect();
+        let mut witness = PrepareBasicCircuitsJob::new(42);
+        for log in logs.clone() {
+            witness.push_merkle_path(log);
+        }
+        let metadata = TreeMetadata {
+            rollup_last_leaf_index: 100,
+            read_count: 4,
+            write_count: 6,
+            ..mock_metadata(Some(witness))
+        };
+        let bytes = metadata.to_bytes();
+        // Check that Merkle paths are stored in the compact form.
+        let full_len = logs.len() * 256 * HASH_SIZE;
+        assert!(bytes.len() < full_len / 2, "{}", bytes.len());
+
+        let restored = TreeMetadata::from_bytes(&bytes).unwrap();
+        assert_metadata_eq(&restored, &metadata);
+        let restored_witness = restored.witness.unwrap();
+        assert_eq!(restored_witness.next_enumeration_index(), 42);
+        let restored_logs: Vec<_> = restored_witness.into_merkle_paths().collect();
+        assert_eq!(restored_logs, logs);
+    }
+
+    #[test]
+    fn serializing_instructions() {
+        let instructions = vec![
+            TreeInstruction::Read(Key::from(1)),
+            TreeInstruction::write(Key::MAX, 42, ValueHash::repeat_byte(1)),
+            TreeInstruction::write(Key::from(1) << 128, 1_000_000, ValueHash::zero()),
+            TreeInstruction::Read(Key::zero()),
+        ];
+        let bytes = serialize_instructions(&instructions);
+        assert_eq!(bytes[..2], [FORMAT_VERSION, 4]);
+        let restored = deserialize_instructions(&bytes).unwrap();
+        assert_eq!(restored, instructions);
+
+        deserialize_instructions(&bytes[..bytes.len() - 1]).unwrap_err();
+    }
+
+    #[test]
+    fn serializing_entries_with_proofs() {
+        let entries = [
+            TreeEntry::new(Key::from(123), 1, ValueHash::repeat_byte(1))
+                .with_merkle_path(vec![ValueHash::repeat_byte(2); 10]),
+            TreeEntry::empty(Key::from(456)).with_merkle_path(vec![]),
+        ];
+        let bytes = serialize_entries_with_proofs(&entries);
+        let restored = decode_entries_with_proofs(&bytes).unwrap();
+        assert_eq!(restored.len(), entries.len());
+        for (restored, entry) in restored.iter().zip(&entries) {
+            assert_eq!(restored.base, entry.base);
+            assert_eq!(restored.merkle_path, entry.merkle_path);
+        }
+
+        let err = decode_entries_with_proofs(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert!(err.to_string().contains("entry #1 with proof"), "{err}");
+        let mut bytes_with_trailing_data = bytes;
+        bytes_with_trailing_data.push(0);
+        let err = decode_entries_with_proofs(&bytes_with_trailing_data).unwrap_err();
+        assert!(err.to_string().contains("trailing bytes"), "{err}");
+    }
+
+    #[test]
+    fn deserialization_errors() {
+        let metadata = mock_metadata(Some(PrepareBasicCircuitsJob::new(1)));
+        let bytes = metadata.to_bytes();
+
+        let err = TreeMetadata::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert!(err.to_string().contains("tree metadata"), "{err}");
+
+        let mut bytes_with_bogus_version = bytes.clone();
+        bytes_with_bogus_version[0] = 0xff;
+        let err = TreeMetadata::from_bytes(&bytes_with_bogus_version).unwrap_err();
+        assert!(
+            err.to_string().contains("unsupported format version"),
+            "{err}"
+        );
+
+        let mut bytes_with_trailing_data = bytes;
+        bytes_with_trailing_data.push(0);
+        let err = TreeMetadata::from_bytes(&bytes_with_trailing_data).unwrap_err();
+        assert!(err.to_string().contains("trailing bytes"), "{err}");
+    }
+}
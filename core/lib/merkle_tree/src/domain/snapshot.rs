@@ -0,0 +1,282 @@
+//! Streaming export and import of tree snapshots.
+//!
+//! # Format
+//!
+//! A snapshot starts with a header:
+//!
+//! - Magic bytes [`MAGIC`] (8 bytes)
+//! - Format version byte (currently, [`FORMAT_VERSION`])
+//! - L1 batch number (LEB128)
+//! - Number of leaves (LEB128)
+//! - Root hash (32 bytes)
+//!
+//! The header is followed by all tree leaves ordered by increasing key. Each leaf is encoded
+//! as its key (32 bytes, big-endian), value hash (32 bytes) and leaf index (LEB128).
+//! Since leaves are ordered, a snapshot can be both written and imported in a streaming fashion,
+//! without buffering the entire tree in RAM.
+
+use std::{
+    io::{self, Read, Write},
+    iter,
+};
+
+use zksync_types::L1BatchNumber;
+
+use super::{ZkSyncTree, ZkSyncTreeReader};
+use crate::{
+    getters::{load_child, load_root},
+    recovery::MerkleTreeRecovery,
+    types::{Key, Nibbles, Node, Root, TreeEntry, ValueHash, HASH_SIZE, KEY_SIZE},
+    Database, HashTree, NoVersionError, RocksDBWrapper,
+};
+
+/// Magic bytes at the start of a snapshot.
+const MAGIC: [u8; 8] = *b"ZKMTSNAP";
+/// Current version of the snapshot format.
+const FORMAT_VERSION: u8 = 0;
+/// Number of leaves imported in a single chunk.
+const IMPORT_CHUNK_SIZE: u64 = 100_000;
+
+/// Error exporting a tree snapshot.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ExportError {
+    /// Exported tree version is missing.
+    #[error(transparent)]
+    NoVersion(#[from] NoVersionError),
+    /// I/O error writing the snapshot.
+    #[error("I/O error writing snapshot: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Error importing a tree snapshot.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ImportError {
+    /// I/O error reading the snapshot.
+    #[error("I/O error reading snapshot: {0}")]
+    Io(#[from] io::Error),
+    /// Snapshot doesn't start with the expected magic bytes.
+    #[error("snapshot does not start with expected magic bytes")]
+    InvalidMagic,
+    /// Unsupported version of the snapshot format.
+    #[error("unsupported snapshot format version: {0}")]
+    UnsupportedFormatVersion(u8),
+    /// Snapshot is malformed.
+    #[error("malformed snapshot: {0}")]
+    Malformed(&'static str),
+    /// Tree database to import the snapshot to is not empty.
+    #[error("tree database is not empty")]
+    NonEmptyDatabase,
+    /// Root hash of the imported tree differs from the one in the snapshot header.
+    #[error("root hash mismatch after import: expected {expected:?}, got {actual:?}")]
+    RootHashMismatch {
+        /// Root hash from the snapshot header.
+        expected: ValueHash,
+        /// Root hash of the imported tree.
+        actual: ValueHash,
+    },
+}
+
+impl From<leb128::read::Error> for ImportError {
+    fn from(err: leb128::read::Error) -> Self {
+        match err {
+            leb128::read::Error::IoError(err) => Self::Io(err),
+            leb128::read::Error::Overflow => Self::Malformed("LEB128-encoded value overflow"),
+        }
+    }
+}
+
+impl<H: HashTree + Clone> ZkSyncTreeReader<H> {
+    /// Iterates over all leaves of the tree at the specified L1 batch in the key order. This can be used to export
+    /// the full tree state (e.g., to seed a new node). Leaves are loaded lazily as the iterator is advanced,
+    /// so the tree is never fully loaded into RAM.
+    ///
+    /// # Consistency
+    ///
+    /// The iterator doesn't use a RocksDB snapshot; instead, it relies on tree nodes being immutable once written.
+    /// Processing new L1 batches only adds nodes with newer versions, so the iteration is consistent
+    /// even if newer tree versions are written concurrently. However, the iterator will panic
+    /// if the iterated version is removed concurrently by pruning or a revert.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree version for the L1 batch is missing.
+    pub fn leaves(
+        &self,
+        l1_batch_number: L1BatchNumber,
+    ) -> Result<impl Iterator<Item = TreeEntry> + '_, NoVersionError> {
+        let version = u64::from(l1_batch_number.0);
+        let root = load_root(&self.0.db, version)?;
+        Ok(self.leaves_from_root(root))
+    }
+
+    /// Iterates over all leaves reachable from `root` in the key order.
+    pub(super) fn leaves_from_root(&self, root: Root) -> impl Iterator<Item = TreeEntry> + '_ {
+        let mut nodes = match root {
+            Root::Filled { node, .. } => vec![(Nibbles::EMPTY, node)],
+            Root::Empty => vec![],
+        };
+        // Depth-first traversal with children visited in the nibble order yields leaves ordered by key.
+        iter::from_fn(move || {
+            while let Some((nibbles, node)) = nodes.pop() {
+                match node {
+                    Node::Leaf(leaf) => {
+                        return Some(TreeEntry::new(
+                            leaf.full_key,
+                            leaf.leaf_index,
+                            leaf.value_hash,
+                        ));
+                    }
+                    Node::Internal(internal) => {
+                        let children: Vec<_> = internal.children().collect();
+                        for (nibble, child_ref) in children.into_iter().rev() {
+                            nodes.push(load_child(
+                                &self.0.db,
+                                nibbles,
+                                nibble,
+                                child_ref.is_leaf,
+                                child_ref.version,
+                            ));
+                        }
+                    }
+                }
+            }
+            None
+        })
+    }
+
+    /// Exports all leaves of the tree at the specified L1 batch into a versioned binary snapshot.
+    /// Leaves are traversed in the key order and written to `writer` as they are loaded, so the tree
+    /// is never fully loaded into RAM. The snapshot can be imported using [`ZkSyncTree::import_snapshot()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree version for the L1 batch is missing, or on I/O errors.
+    pub fn export_snapshot(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        mut writer: impl Write,
+    ) -> Result<(), ExportError> {
+        let version = u64::from(l1_batch_number.0);
+        let root = load_root(&self.0.db, version)?;
+        // The version may be pruned concurrently, but this is improbable.
+        let root_hash = self.0.root_hash(version).ok_or_else(|| NoVersionError {
+            missing_version: version,
+            version_count: self.0.latest_version().map_or(0, |version| version + 1),
+        })?;
+        let leaf_count = root.leaf_count();
+        tracing::info!(
+            "Exporting snapshot of Merkle tree at L1 batch #{l1_batch_number} with {leaf_count} leaves, \
+             root hash {root_hash:?}"
+        );
+
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[FORMAT_VERSION])?;
+        leb128::write::unsigned(&mut writer, version)?;
+        leb128::write::unsigned(&mut writer, leaf_count)?;
+        writer.write_all(root_hash.as_bytes())?;
+
+        for entry in self.leaves_from_root(root) {
+            let mut key_bytes = [0_u8; KEY_SIZE];
+            entry.key.to_big_endian(&mut key_bytes);
+            writer.write_all(&key_bytes)?;
+            writer.write_all(entry.value.as_bytes())?;
+            leb128::write::unsigned(&mut writer, entry.leaf_index)?;
+        }
+        writer.flush().map_err(Into::into)
+    }
+}
+
+impl ZkSyncTree {
+    /// Imports a snapshot produced by [`ZkSyncTreeReader::export_snapshot()`] into the provided database.
+    /// The snapshot is read from `reader` and processed in chunks, so it doesn't need to be fully buffered.
+    /// After a successful import, the database contains a single tree version corresponding to
+    /// the exported L1 batch.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database is not empty, the snapshot is malformed, or if the root hash
+    /// of the imported tree doesn't match the one recorded in the snapshot. In the latter two cases,
+    /// the database may be left in a partially imported state and should be discarded.
+    pub fn import_snapshot(db: RocksDBWrapper, mut reader: impl Read) -> Result<(), ImportError> {
+        if db
+            .manifest()
+            .is_some_and(|manifest| manifest.version_count > 0)
+        {
+            return Err(ImportError::NonEmptyDatabase);
+        }
+
+        let mut magic = [0_u8; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(ImportError::InvalidMagic);
+        }
+        let mut format_version = [0_u8; 1];
+        reader.read_exact(&mut format_version)?;
+        if format_version[0] != FORMAT_VERSION {
+            return Err(ImportError::UnsupportedFormatVersion(format_version[0]));
+        }
+        let version = leb128::read::unsigned(&mut reader)?;
+        let leaf_count = leb128::read::unsigned(&mut reader)?;
+        let mut root_hash = [0_u8; HASH_SIZE];
+        reader.read_exact(&mut root_hash)?;
+        let expected_root_hash = ValueHash::from(root_hash);
+        tracing::info!(
+            "Importing snapshot of Merkle tree at L1 batch #{version} with {leaf_count} leaves, \
+             root hash {expected_root_hash:?}"
+        );
+
+        let mut recovery = MerkleTreeRecovery::new(db, version);
+        let mut prev_key = None;
+        let mut remaining_leaf_count = leaf_count;
+        while remaining_leaf_count > 0 {
+            let chunk_size = remaining_leaf_count.min(IMPORT_CHUNK_SIZE);
+            #[allow(clippy::cast_possible_truncation)] // chunk size is quite small
+            let mut entries = Vec::with_capacity(chunk_size as usize);
+            for _ in 0..chunk_size {
+                let entry = read_entry(&mut reader)?;
+                if prev_key.is_some_and(|prev_key| prev_key >= entry.key) {
+                    return Err(ImportError::Malformed("leaves are not ordered by key"));
+                }
+                prev_key = Some(entry.key);
+                entries.push(entry);
+            }
+            recovery.extend_linear(entries);
+            remaining_leaf_count -= chunk_size;
+            tracing::debug!(
+                "Imported {} / {leaf_count} leaves",
+                leaf_count - remaining_leaf_count
+            );
+        }
+        if reader.read(&mut [0_u8; 1])? > 0 {
+            return Err(ImportError::Malformed(
+                "unexpected data after the last leaf",
+            ));
+        }
+
+        let root_hash = recovery.root_hash();
+        if root_hash != expected_root_hash {
+            return Err(ImportError::RootHashMismatch {
+                expected: expected_root_hash,
+                actual: root_hash,
+            });
+        }
+        recovery.finalize();
+        tracing::info!("Imported snapshot of Merkle tree at L1 batch #{version}");
+        Ok(())
+    }
+}
+
+fn read_entry(reader: &mut impl Read) -> Result<TreeEntry, ImportError> {
+    let mut key = [0_u8; KEY_SIZE];
+    reader.read_exact(&mut key)?;
+    let mut value = [0_u8; HASH_SIZE];
+    reader.read_exact(&mut value)?;
+    let leaf_index = leb128::read::unsigned(reader)?;
+    Ok(TreeEntry::new(
+        Key::from_big_endian(&key),
+        leaf_index,
+        ValueHash::from(value),
+    ))
+}
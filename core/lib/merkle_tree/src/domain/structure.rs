@@ -0,0 +1,221 @@
+//! Structure statistics for the tree.
+
+use zksync_types::L1BatchNumber;
+
+use super::ZkSyncTreeReader;
+use crate::{
+    getters::{load_child, load_root},
+    types::{Key, Nibbles, Node, Root},
+    HashTree, NoVersionError,
+};
+
+/// Structure statistics for a tree version returned by [`ZkSyncTreeReader::structure_stats()`]
+/// and [`ZkSyncTreeReader::sampled_structure_stats()`]. Depths are measured in nibbles, i.e. as the number
+/// of internal nodes on the path from the root to a leaf.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TreeStructureStats {
+    /// Number of internal nodes in the tree, including the root node.
+    pub internal_node_count: u64,
+    /// Number of leaves in the tree.
+    pub leaf_count: u64,
+    /// Average depth of a leaf.
+    pub avg_leaf_depth: f64,
+    /// Maximum depth of a leaf.
+    pub max_leaf_depth: usize,
+}
+
+/// Nibble depth of subtrees used as sampling units in [`ZkSyncTreeReader::sampled_structure_stats()`].
+const SAMPLING_DEPTH: usize = 2;
+
+#[derive(Debug, Default)]
+struct NodeCounts {
+    internal_node_count: u64,
+    leaf_count: u64,
+    leaf_depth_sum: u64,
+}
+
+impl<H: HashTree + Clone> ZkSyncTreeReader<H> {
+    /// Computes structure statistics for the tree at the specified L1 batch, which can be used to track
+    /// proof size trends and RocksDB growth.
+    ///
+    /// Statistics are computed in a single pass over the tree, but this pass loads all tree nodes,
+    /// so it is slow for large trees and should be used out-of-band. Use [`Self::sampled_structure_stats()`]
+    /// to get an estimate at a fraction of the cost.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree version for the L1 batch is missing.
+    pub fn structure_stats(
+        &self,
+        l1_batch_number: L1BatchNumber,
+    ) -> Result<TreeStructureStats, NoVersionError> {
+        self.collect_structure_stats(l1_batch_number, 1)
+    }
+
+    /// Estimates structure statistics for the tree at the specified L1 batch by only traversing every `sample_every`-th
+    /// subtree rooted at the second tree level (there are up to 256 such subtrees); the remaining subtrees are assumed
+    /// to have the same shape on average. Thus, the cost of this method is approximately `1 / sample_every`
+    /// of the cost of [`Self::structure_stats()`].
+    ///
+    /// The leaf count is exact since it's stored at the tree root. The number of internal nodes and the average
+    /// leaf depth are estimated, and the maximum leaf depth is a lower bound of the actual value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree version for the L1 batch is missing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample_every` is zero.
+    pub fn sampled_structure_stats(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        sample_every: usize,
+    ) -> Result<TreeStructureStats, NoVersionError> {
+        assert!(sample_every > 0, "sampling interval must be positive");
+        self.collect_structure_stats(l1_batch_number, sample_every)
+    }
+
+    /// Finds the deepest leaf in the tree at the specified L1 batch. Returns the key of the leaf and its depth
+    /// measured as in [`Self::key_depth()`] (i.e., as the number of non-empty levels in its Merkle path),
+    /// or `None` if the tree is empty. If there are several deepest leaves, the one with the least key is returned.
+    ///
+    /// Since tree keys are hashed, the tree should be balanced; for a healthy tree, the maximum depth should be
+    /// close to `log2(leaf_count)`. A substantially greater depth indicates a tree shape anomaly inflating proof sizes.
+    /// This method traverses all tree nodes, so it is slow for large trees and should be used out-of-band.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree version for the L1 batch is missing.
+    pub fn deepest_leaf(
+        &self,
+        l1_batch_number: L1BatchNumber,
+    ) -> Result<Option<(Key, usize)>, NoVersionError> {
+        let version = u64::from(l1_batch_number.0);
+        let Root::Filled { node, .. } = load_root(&self.0.db, version)? else {
+            return Ok(None);
+        };
+
+        let mut deepest_leaf: Option<(Key, usize)> = None;
+        // Each node is accompanied by its depth in the binary tree; it's only meaningful for leaves.
+        let mut nodes = vec![(Nibbles::EMPTY, node, 0)];
+        while let Some((nibbles, node, depth)) = nodes.pop() {
+            match node {
+                Node::Leaf(leaf) => {
+                    let is_deeper = deepest_leaf.map_or(true, |(key, max_depth)| {
+                        depth > max_depth || (depth == max_depth && leaf.full_key < key)
+                    });
+                    if is_deeper {
+                        deepest_leaf = Some((leaf.full_key, depth));
+                    }
+                }
+                Node::Internal(internal) => {
+                    let sibling_nibbles: Vec<_> =
+                        internal.children().map(|(nibble, _)| nibble).collect();
+                    for (nibble, child_ref) in internal.children() {
+                        let child_depth = nibbles.nibble_count() * 4
+                            + Self::depth_in_internal_node(nibble, &sibling_nibbles);
+                        let (child_nibbles, child) = load_child(
+                            &self.0.db,
+                            nibbles,
+                            nibble,
+                            child_ref.is_leaf,
+                            child_ref.version,
+                        );
+                        nodes.push((child_nibbles, child, child_depth));
+                    }
+                }
+            }
+        }
+        Ok(deepest_leaf)
+    }
+
+    /// Returns the depth of the child with the specified `nibble` in the binary subtree of an internal node,
+    /// i.e. the number of levels until the child is separated from all its siblings.
+    fn depth_in_internal_node(nibble: u8, sibling_nibbles: &[u8]) -> usize {
+        sibling_nibbles
+            .iter()
+            .filter(|&&other| other != nibble)
+            .map(|&other| (nibble ^ other).leading_zeros() as usize - 3)
+            // ^ Nibbles occupy the 4 lower bits, so the common bit prefix has `leading_zeros() - 4` bits
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[allow(clippy::cast_precision_loss)] // precision loss is acceptable for estimates
+    fn collect_structure_stats(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        sample_every: usize,
+    ) -> Result<TreeStructureStats, NoVersionError> {
+        let version = u64::from(l1_batch_number.0);
+        let root = load_root(&self.0.db, version)?;
+        let leaf_count = root.leaf_count();
+        let Root::Filled { node, .. } = root else {
+            return Ok(TreeStructureStats::default());
+        };
+
+        // Nodes above `SAMPLING_DEPTH` are always traversed, so their counts are exact.
+        let mut exact_counts = NodeCounts::default();
+        let mut sampled_counts = NodeCounts::default();
+        let (mut subtree_count, mut sampled_subtree_count) = (0_usize, 0_usize);
+        let mut max_leaf_depth = 0;
+        let mut nodes = vec![(Nibbles::EMPTY, node)];
+        while let Some((nibbles, node)) = nodes.pop() {
+            let depth = nibbles.nibble_count();
+            let counts = if depth < SAMPLING_DEPTH {
+                &mut exact_counts
+            } else {
+                &mut sampled_counts
+            };
+            match node {
+                Node::Leaf(_) => {
+                    counts.leaf_count += 1;
+                    counts.leaf_depth_sum += depth as u64;
+                    max_leaf_depth = max_leaf_depth.max(depth);
+                }
+                Node::Internal(internal) => {
+                    counts.internal_node_count += 1;
+                    for (nibble, child_ref) in internal.children() {
+                        if depth + 1 == SAMPLING_DEPTH {
+                            subtree_count += 1;
+                            if (subtree_count - 1) % sample_every != 0 {
+                                continue;
+                            }
+                            sampled_subtree_count += 1;
+                        }
+                        nodes.push(load_child(
+                            &self.0.db,
+                            nibbles,
+                            nibble,
+                            child_ref.is_leaf,
+                            child_ref.version,
+                        ));
+                    }
+                }
+            }
+        }
+
+        let scale = if sampled_subtree_count == 0 {
+            0.0
+        } else {
+            subtree_count as f64 / sampled_subtree_count as f64
+        };
+        let estimated_internal_node_count = exact_counts.internal_node_count as f64
+            + sampled_counts.internal_node_count as f64 * scale;
+        let estimated_leaf_count =
+            exact_counts.leaf_count as f64 + sampled_counts.leaf_count as f64 * scale;
+        let estimated_leaf_depth_sum =
+            exact_counts.leaf_depth_sum as f64 + sampled_counts.leaf_depth_sum as f64 * scale;
+
+        // The estimate is positive and is reasonably small.
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let internal_node_count = estimated_internal_node_count.round() as u64;
+        Ok(TreeStructureStats {
+            internal_node_count,
+            leaf_count,
+            avg_leaf_depth: estimated_leaf_depth_sum / estimated_leaf_count,
+            max_leaf_depth,
+        })
+    }
+}
@@ -0,0 +1,68 @@
+//! Tests for the domain-specific tree wrapper that rely on crate internals (e.g., failure injection).
+
+use std::ops;
+
+use tempfile::TempDir;
+use zksync_types::{AccountTreeId, Address, H256};
+
+use super::*;
+
+fn gen_instructions(indices: ops::Range<u64>) -> Vec<TreeInstruction<StorageKey>> {
+    indices
+        .map(|i| {
+            let key = StorageKey::new(
+                AccountTreeId::new(Address::repeat_byte(0x11)),
+                H256::from_low_u64_be(i),
+            );
+            TreeInstruction::Write(TreeEntry::new(key, i + 1, H256::from_low_u64_be(i + 1)))
+        })
+        .collect()
+}
+
+fn create_tree() -> (TempDir, RocksDBWrapper, ZkSyncTree) {
+    let dir = TempDir::new().expect("failed creating temporary dir for RocksDB");
+    let db = RocksDBWrapper::new(dir.path()).unwrap();
+    let tree = ZkSyncTree::new_lightweight(db.clone());
+    (dir, db, tree)
+}
+
+#[test]
+fn failed_background_save_can_be_retried() {
+    let (_dir, db, mut tree) = create_tree();
+    let reader = tree.reader();
+    tree.process_l1_batch(&gen_instructions(0..50));
+    db.set_write_failure(true);
+    tree.begin_save();
+    // Changes accumulated after starting the save must be retained as well.
+    let root_hash = tree.process_l1_batch(&gen_instructions(50..80)).root_hash;
+
+    let err = tree.finish_save().unwrap_err();
+    assert!(err.message.contains("Injected failure"), "{err}");
+    assert_eq!(reader.next_l1_batch_number(), L1BatchNumber(0));
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+
+    db.set_write_failure(false);
+    tree.save();
+    assert_eq!(reader.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(reader.root_hash(), root_hash);
+}
+
+#[tokio::test]
+async fn failed_async_save_can_be_retried() {
+    let (_dir, db, mut tree) = create_tree();
+    let reader = tree.reader();
+    let root_hash = tree.process_l1_batch(&gen_instructions(0..50)).root_hash;
+    db.set_write_failure(true);
+
+    let err = tree.save_async().await.unwrap_err();
+    assert!(err.message.contains("Injected failure"), "{err}");
+    assert_eq!(reader.next_l1_batch_number(), L1BatchNumber(0));
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(1));
+    assert_eq!(tree.root_hash(), root_hash);
+
+    db.set_write_failure(false);
+    tree.save_async().await.unwrap();
+    assert_eq!(reader.next_l1_batch_number(), L1BatchNumber(1));
+    assert_eq!(reader.root_hash(), root_hash);
+}
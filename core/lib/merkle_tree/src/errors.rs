@@ -2,7 +2,7 @@
 
 use std::{error, fmt, str::Utf8Error};
 
-use crate::types::NodeKey;
+use crate::types::{NodeKey, ValueHash};
 
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
@@ -22,6 +22,15 @@ pub enum DeserializeErrorKind {
     /// Bit mask specifying a child kind in an internal tree node is invalid.
     #[error("invalid bit mask specifying a child kind in an internal tree node")]
     InvalidChildKind,
+    /// Unsupported version of a versioned binary format.
+    #[error("unsupported format version: {0}")]
+    UnsupportedFormatVersion(u8),
+    /// Invalid bit flags.
+    #[error("invalid bit flags: {0:#b}")]
+    InvalidFlags(u8),
+    /// Input has unexpected bytes after the end of encoded data.
+    #[error("unexpected trailing bytes")]
+    TrailingBytes,
 
     /// Missing required tag in the tree manifest.
     #[error("missing required tag `{0}` in tree manifest")]
@@ -73,6 +82,14 @@ pub enum ErrorContext {
     LeafIndex,
     /// Version of a child in an internal node.
     Version,
+    /// Serialized tree metadata.
+    TreeMetadata,
+    /// Storage log with the specified 0-based index in a witness.
+    WitnessLog(usize),
+    /// Inputs for lazy witness reconstruction at the specified tree version.
+    WitnessInputs(u64),
+    /// Entry with the specified 0-based index in encoded entries with proofs.
+    EntryWithProof(usize),
 }
 
 impl fmt::Display for ErrorContext {
@@ -87,6 +104,12 @@ impl fmt::Display for ErrorContext {
             Self::LeafCount => formatter.write_str("number of leaf nodes"),
             Self::LeafIndex => formatter.write_str("leaf index"),
             Self::Version => formatter.write_str("version of a child"),
+            Self::TreeMetadata => formatter.write_str("tree metadata"),
+            Self::WitnessLog(idx) => write!(formatter, "storage log #{idx} in witness"),
+            Self::WitnessInputs(version) => {
+                write!(formatter, "witness inputs at version {version}")
+            }
+            Self::EntryWithProof(idx) => write!(formatter, "entry #{idx} with proof"),
         }
     }
 }
@@ -144,21 +167,30 @@ pub struct NoVersionError {
     pub version_count: u64,
 }
 
+impl NoVersionError {
+    /// Checks whether the missing version was removed by pruning, as opposed to not being created yet.
+    /// Since pruning only removes the oldest versions, a missing version is pruned if and only if
+    /// it is less than the current number of versions.
+    pub fn is_pruned(&self) -> bool {
+        self.missing_version < self.version_count
+    }
+}
+
 impl fmt::Display for NoVersionError {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
         let &Self {
             missing_version,
             version_count,
         } = self;
-        if missing_version >= version_count {
+        if self.is_pruned() {
             write!(
                 formatter,
-                "version {missing_version} does not exist in Merkle tree; it has {version_count} versions"
+                "version {missing_version} was pruned from Merkle tree"
             )
         } else {
             write!(
                 formatter,
-                "version {missing_version} was pruned from Merkle tree"
+                "version {missing_version} does not exist in Merkle tree; it has {version_count} versions"
             )
         }
     }
@@ -166,6 +198,241 @@ impl fmt::Display for NoVersionError {
 
 impl error::Error for NoVersionError {}
 
+/// Error processing an L1 batch because the tree has too many unsaved versions.
+#[derive(Debug)]
+pub struct PendingLimitExceeded {
+    /// Current number of unsaved versions in the tree.
+    pub pending_version_count: usize,
+    /// Maximum allowed number of unsaved versions.
+    pub max_pending_versions: usize,
+}
+
+impl fmt::Display for PendingLimitExceeded {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let &Self {
+            pending_version_count,
+            max_pending_versions,
+        } = self;
+        write!(
+            formatter,
+            "Merkle tree has {pending_version_count} unsaved versions, which reaches the limit \
+             ({max_pending_versions}); the tree must be saved before processing more L1 batches"
+        )
+    }
+}
+
+impl error::Error for PendingLimitExceeded {}
+
+/// Error processing the genesis L1 batch for a tree that already contains it.
+#[derive(Debug)]
+pub struct GenesisAlreadyExists {
+    /// Current number of versions in the tree.
+    pub version_count: u64,
+}
+
+impl fmt::Display for GenesisAlreadyExists {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "Merkle tree already contains the genesis L1 batch; it has {} versions",
+            self.version_count
+        )
+    }
+}
+
+impl error::Error for GenesisAlreadyExists {}
+
+/// Error looking up a tree version with the specified root hash.
+#[derive(Debug)]
+pub struct RootNotFoundError {
+    /// Requested root hash.
+    pub root_hash: ValueHash,
+}
+
+impl fmt::Display for RootNotFoundError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "no retained Merkle tree version has root hash {:?}",
+            self.root_hash
+        )
+    }
+}
+
+impl error::Error for RootNotFoundError {}
+
+/// Error returned by [`ZkSyncTree::try_verify_consistency()`](crate::domain::ZkSyncTree::try_verify_consistency()).
+#[derive(Debug)]
+pub struct ConsistencyError {
+    /// Checked tree version.
+    pub version: u64,
+    /// Human-readable description of the detected inconsistency.
+    pub description: String,
+}
+
+impl fmt::Display for ConsistencyError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "Merkle tree at version {} is inconsistent: {}",
+            self.version, self.description
+        )
+    }
+}
+
+impl error::Error for ConsistencyError {}
+
+/// Error returned by [`ZkSyncTree::try_process_l1_batch()`](crate::domain::ZkSyncTree::try_process_l1_batch())
+/// if the witness for the processed L1 batch exceeds the [configured limit](crate::domain::ZkSyncTree::set_max_witness_bytes()).
+#[derive(Debug)]
+pub struct WitnessTooLarge {
+    /// Witness size measured at the moment the limit was exceeded. Since the witness is not built further,
+    /// this is a lower bound of the full witness size.
+    pub size: usize,
+    /// Maximum witness size.
+    pub max_size: usize,
+}
+
+impl fmt::Display for WitnessTooLarge {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "witness size ({} bytes or more) exceeds the limit of {} bytes",
+            self.size, self.max_size
+        )
+    }
+}
+
+impl error::Error for WitnessTooLarge {}
+
+/// Error returned by [`ZkSyncTree::try_process_l1_batch()`](crate::domain::ZkSyncTree::try_process_l1_batch()).
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ProcessL1BatchError {
+    /// The limit on unsaved tree versions is reached.
+    #[error(transparent)]
+    PendingLimitExceeded(#[from] PendingLimitExceeded),
+    /// The witness for the processed L1 batch is too large.
+    #[error(transparent)]
+    WitnessTooLarge(#[from] WitnessTooLarge),
+}
+
+/// Error returned by [`ZkSyncTreeReader::rebuild_witness()`](crate::domain::ZkSyncTreeReader::rebuild_witness()).
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum RebuildWitnessError {
+    /// Tree version for the L1 batch or the preceding L1 batch is missing.
+    #[error(transparent)]
+    NoVersion(#[from] NoVersionError),
+    /// Replaying the provided instructions doesn't result in the root hash of the L1 batch,
+    /// i.e., the instructions don't correspond to the batch.
+    #[error(
+        "replaying instructions results in root hash {actual:?}, while the L1 batch has root hash {expected:?}"
+    )]
+    RootHashMismatch {
+        /// Root hash of the L1 batch.
+        expected: ValueHash,
+        /// Root hash obtained by replaying instructions.
+        actual: ValueHash,
+    },
+}
+
+/// Error returned by [`ZkSyncTree::new_checked()`](crate::domain::ZkSyncTree::new_checked()).
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum TreeOpenError {
+    /// Top levels of the latest tree version are inconsistent, e.g., because of a corruption
+    /// after an unclean shutdown.
+    #[error(transparent)]
+    Corrupted(#[from] ConsistencyError),
+}
+
+/// Error returned by [`ZkSyncTreeReader::proof_with_state()`](crate::domain::ZkSyncTreeReader::proof_with_state()).
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ProofWithStateError {
+    /// Tree version for the L1 batch is missing.
+    #[error(transparent)]
+    NoVersion(#[from] NoVersionError),
+    /// The Merkle proof persistently doesn't lead to the root hash of the tree version,
+    /// e.g., because the tree is corrupted.
+    #[error("Merkle proof results in root hash {actual:?}, while the tree version has root hash {expected:?}")]
+    RootHashMismatch {
+        /// Root hash of the tree version.
+        expected: ValueHash,
+        /// Root hash obtained by folding the Merkle proof.
+        actual: ValueHash,
+    },
+}
+
+/// Error returned by [`ZkSyncTreeReader::latest_entries_with_proofs()`](crate::domain::ZkSyncTreeReader::latest_entries_with_proofs()).
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum LatestEntriesError {
+    /// The tree has no versions.
+    #[error(transparent)]
+    EmptyTree(#[from] EmptyTreeError),
+    /// The latest tree version is persistently missing, e.g., because it's repeatedly reverted or pruned.
+    #[error(transparent)]
+    NoVersion(#[from] NoVersionError),
+}
+
+/// Error returned by [`ZkSyncTreeSnapshot::entries_with_proofs()`](crate::domain::ZkSyncTreeSnapshot::entries_with_proofs()).
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SnapshotError {
+    /// The snapshot was created for a tree without versions.
+    #[error(transparent)]
+    EmptyTree(#[from] EmptyTreeError),
+    /// The pinned tree version was removed, e.g., by pruning.
+    #[error(transparent)]
+    NoVersion(#[from] NoVersionError),
+    /// The pinned tree version was reverted and then created anew with different contents.
+    #[error(
+        "tree version {version} was replaced after the snapshot was created; its root hash changed \
+         from {expected:?} to {actual:?}"
+    )]
+    VersionReplaced {
+        /// Pinned tree version.
+        version: u64,
+        /// Root hash pinned by the snapshot.
+        expected: ValueHash,
+        /// Current root hash of the tree version.
+        actual: ValueHash,
+    },
+}
+
+/// Error saving tree changes in the background.
+#[derive(Debug)]
+pub struct BackgroundSaveError {
+    /// Message of the panic that has occurred while saving changes.
+    pub message: String,
+}
+
+impl fmt::Display for BackgroundSaveError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "failed saving Merkle tree changes in the background: {}",
+            self.message
+        )
+    }
+}
+
+impl error::Error for BackgroundSaveError {}
+
+/// Error accessing the latest version of a tree that has no versions.
+#[derive(Debug)]
+pub struct EmptyTreeError;
+
+impl fmt::Display for EmptyTreeError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("Merkle tree is empty")
+    }
+}
+
+impl error::Error for EmptyTreeError {}
+
 #[cfg(test)]
 mod tests {
     use zksync_types::U256;
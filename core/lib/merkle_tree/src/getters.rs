@@ -2,9 +2,10 @@
 
 use crate::{
     hasher::HasherWithStats,
+    metrics::TREE_METRICS,
     recovery::MerkleTreeRecovery,
     storage::{LoadAncestorsResult, SortedKeys, WorkingPatchSet},
-    types::{Nibbles, Node, ProfiledTreeOperation, TreeEntry, TreeEntryWithProof},
+    types::{LeafNode, Nibbles, Node, ProfiledTreeOperation, Root, TreeEntry, TreeEntryWithProof},
     Database, HashTree, Key, MerkleTree, NoVersionError, PruneDatabase, ValueHash,
 };
 
@@ -44,47 +45,461 @@ impl<DB: Database, H: HashTree> MerkleTree<DB, H> {
             &self.db,
             version,
             leaf_keys,
-            |patch_set, &leaf_key, longest_prefix| {
-                let (leaf, merkle_path) =
-                    patch_set.create_proof(&mut hasher, leaf_key, longest_prefix, 0);
-                let value = leaf
-                    .as_ref()
-                    .map_or_else(ValueHash::zero, |leaf| leaf.value_hash);
-                TreeEntry {
-                    key: leaf_key,
-                    value,
-                    leaf_index: leaf.map_or(0, |leaf| leaf.leaf_index),
-                }
-                .with_merkle_path(merkle_path.into_inner())
+            |patch_set, leaf_key, longest_prefix| {
+                create_entry_with_proof(&mut hasher, patch_set, leaf_key, longest_prefix)
             },
         )
     }
+
+    /// Same as [`Self::entries_with_proofs()`], but additionally returns the root hash and the number of leaves
+    /// for the tree `version`. Proofs and root info are obtained from a single root node read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub(crate) fn entries_with_proofs_and_root_info(
+        &self,
+        version: u64,
+        leaf_keys: &[Key],
+    ) -> Result<(Vec<TreeEntryWithProof>, ValueHash, u64), NoVersionError> {
+        let root = load_root(&self.db, version)?;
+        let (root_hash, leaf_count) = self.root_hash_and_leaf_count(&root);
+        let entries = self.entries_with_proofs_for_root(version, root, leaf_keys);
+        Ok((entries, root_hash, leaf_count))
+    }
+
+    /// Same as [`Self::entries_with_proofs()`], but uses the provided `root` of the tree `version`
+    /// instead of loading it from the database.
+    pub(crate) fn entries_with_proofs_for_root(
+        &self,
+        version: u64,
+        root: Root,
+        leaf_keys: &[Key],
+    ) -> Vec<TreeEntryWithProof> {
+        let mut hasher = HasherWithStats::new(&self.hasher);
+        let _profiling_guard = self
+            .db
+            .start_profiling(ProfiledTreeOperation::GetEntriesWithProofs);
+        transform_entries(
+            &self.db,
+            version,
+            root,
+            leaf_keys,
+            |patch_set, leaf_key, longest_prefix| {
+                create_entry_with_proof(&mut hasher, patch_set, leaf_key, longest_prefix)
+            },
+        )
+    }
+
+    /// Returns the root hash and the number of leaves for the specified tree `version`. Both values
+    /// are obtained from a single root node read, so they always correspond to each other.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub(crate) fn root_info(&self, version: u64) -> Result<(ValueHash, u64), NoVersionError> {
+        let root = load_root(&self.db, version)?;
+        Ok(self.root_hash_and_leaf_count(&root))
+    }
+
+    pub(crate) fn root_hash_and_leaf_count(&self, root: &Root) -> (ValueHash, u64) {
+        let root_hash = match root {
+            Root::Empty => self.hasher.empty_tree_hash(),
+            Root::Filled { node, .. } => node.hash(&mut HasherWithStats::new(&self.hasher), 0),
+        };
+        (root_hash, root.leaf_count())
+    }
+
+    /// Returns the length of the non-empty part of the Merkle path for the specified key,
+    /// or `None` if the key is not present in the tree. Unlike [`Self::entries_with_proofs()`],
+    /// this doesn't compute any hashes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub(crate) fn merkle_path_len(
+        &self,
+        version: u64,
+        leaf_key: Key,
+    ) -> Result<Option<usize>, NoVersionError> {
+        let _profiling_guard = self.db.start_profiling(ProfiledTreeOperation::GetEntries);
+        let mut lens = load_and_transform_entries(
+            &self.db,
+            version,
+            &[leaf_key],
+            |patch_set, &leaf_key, longest_prefix| {
+                patch_set.merkle_path_len(leaf_key, longest_prefix)
+            },
+        )?;
+        Ok(lens.pop().flatten())
+    }
+
+    /// Reads the value for the specified key, or `None` if the key is not present in the tree.
+    /// Unlike [`Self::entries()`], this follows the path to the key directly, without sorting keys
+    /// or maintaining a patch set of loaded nodes, which makes it cheaper for single-key lookups.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub(crate) fn value(
+        &self,
+        version: u64,
+        key: Key,
+    ) -> Result<Option<ValueHash>, NoVersionError> {
+        let _profiling_guard = self.db.start_profiling(ProfiledTreeOperation::GetEntries);
+        let Root::Filled { node, .. } = load_root(&self.db, version)? else {
+            return Ok(None);
+        };
+
+        let (mut nibbles, mut node) = (Nibbles::EMPTY, node);
+        loop {
+            let internal = match node {
+                Node::Leaf(leaf) => return Ok((leaf.full_key == key).then_some(leaf.value_hash)),
+                Node::Internal(internal) => internal,
+            };
+            let nibble = Nibbles::nibble(&key, nibbles.nibble_count());
+            let Some(child_ref) = internal.child_ref(nibble) else {
+                return Ok(None);
+            };
+            (nibbles, node) = load_child(
+                &self.db,
+                nibbles,
+                nibble,
+                child_ref.is_leaf,
+                child_ref.version,
+            );
+        }
+    }
+
+    /// Returns hashes of the tree nodes on the path from the root to the leaf with the specified key,
+    /// starting from the root. Returns an empty vector if the key is not present in the tree.
+    pub(crate) fn path_node_hashes(
+        &self,
+        version: u64,
+        key: Key,
+    ) -> Result<Vec<ValueHash>, NoVersionError> {
+        let Root::Filled { node, .. } = load_root(&self.db, version)? else {
+            return Ok(vec![]);
+        };
+
+        let mut hasher = HasherWithStats::new(&self.hasher);
+        let mut hashes = vec![];
+        let (mut nibbles, mut node) = (Nibbles::EMPTY, node);
+        loop {
+            hashes.push(node.hash(&mut hasher, nibbles.nibble_count() * 4));
+            let internal = match node {
+                Node::Leaf(leaf) if leaf.full_key == key => return Ok(hashes),
+                Node::Leaf(_) => return Ok(vec![]),
+                Node::Internal(internal) => internal,
+            };
+            let nibble = Nibbles::nibble(&key, nibbles.nibble_count());
+            let Some(child_ref) = internal.child_ref(nibble) else {
+                return Ok(vec![]);
+            };
+            (nibbles, node) = load_child(
+                &self.db,
+                nibbles,
+                nibble,
+                child_ref.is_leaf,
+                child_ref.version,
+            );
+        }
+    }
+
+    /// Finds the closest keys present in the tree that are less than and greater than
+    /// the specified key, respectively.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub(crate) fn neighbor_keys(
+        &self,
+        version: u64,
+        key: Key,
+    ) -> Result<(Option<Key>, Option<Key>), NoVersionError> {
+        let _profiling_guard = self.db.start_profiling(ProfiledTreeOperation::GetEntries);
+        let Root::Filled { node, .. } = load_root(&self.db, version)? else {
+            return Ok((None, None));
+        };
+        let lower = find_neighbor(&self.db, Nibbles::EMPTY, &node, &key, false);
+        let upper = find_neighbor(&self.db, Nibbles::EMPTY, &node, &key, true);
+        Ok((
+            lower.map(|leaf| leaf.full_key),
+            upper.map(|leaf| leaf.full_key),
+        ))
+    }
+
+    /// Counts initial and repeated writes in the specified tree version by comparing leaves
+    /// created in this version with the previous version. No-op updates are not counted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` or the previous version is missing.
+    pub(crate) fn write_counts(&self, version: u64) -> Result<(usize, usize), NoVersionError> {
+        let _profiling_guard = self.db.start_profiling(ProfiledTreeOperation::GetEntries);
+        let root = load_root(&self.db, version)?;
+        if version > 0 {
+            // Check that the previous version is not pruned.
+            load_root(&self.db, version - 1)?;
+        }
+        let Root::Filled { node, .. } = root else {
+            return Ok((0, 0));
+        };
+
+        // Leaves created in this version are reachable via nodes created in this version.
+        let mut leaves = vec![];
+        let mut nodes = vec![(Nibbles::EMPTY, node)];
+        while let Some((nibbles, node)) = nodes.pop() {
+            match node {
+                Node::Leaf(leaf) => leaves.push(leaf),
+                Node::Internal(internal) => {
+                    let new_children = internal
+                        .children()
+                        .filter(|(_, child_ref)| child_ref.version == version);
+                    for (nibble, child_ref) in new_children {
+                        nodes.push(load_child(
+                            &self.db,
+                            nibbles,
+                            nibble,
+                            child_ref.is_leaf,
+                            version,
+                        ));
+                    }
+                }
+            }
+        }
+
+        if version == 0 {
+            return Ok((leaves.len(), 0));
+        }
+        let keys: Vec<_> = leaves.iter().map(|leaf| leaf.full_key).collect();
+        let prev_entries = load_and_transform_entries(&self.db, version - 1, &keys, extract_entry)?;
+
+        let (mut initial_writes, mut repeated_writes) = (0, 0);
+        for (leaf, prev_entry) in leaves.iter().zip(&prev_entries) {
+            if prev_entry.is_empty() {
+                initial_writes += 1;
+            } else if prev_entry.value != leaf.value_hash {
+                repeated_writes += 1;
+            }
+            // Otherwise, the leaf was moved as a result of an insertion, or was subject to a no-op update.
+        }
+        Ok((initial_writes, repeated_writes))
+    }
+
+    /// Returns entries that were inserted or changed their value between `from_version` (exclusive)
+    /// and `to_version` (inclusive), with values as of `to_version`. If `from_version` is `None`, all entries
+    /// at `to_version` are returned. Entries are ordered by enumeration index.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either of the tree versions is missing.
+    pub(crate) fn changed_entries(
+        &self,
+        from_version: Option<u64>,
+        to_version: u64,
+    ) -> Result<Vec<TreeEntry>, NoVersionError> {
+        let _profiling_guard = self.db.start_profiling(ProfiledTreeOperation::GetEntries);
+        if let Some(from_version) = from_version {
+            load_root(&self.db, from_version)?;
+        }
+        let root = load_root(&self.db, to_version)?;
+        if from_version.map_or(false, |from_version| from_version >= to_version) {
+            return Ok(vec![]);
+        }
+        let Root::Filled { node, .. } = root else {
+            return Ok(vec![]);
+        };
+
+        // Subtrees not modified after `from_version` are shared with this version and can be skipped.
+        let mut leaves = vec![];
+        let mut nodes = vec![(Nibbles::EMPTY, node)];
+        while let Some((nibbles, node)) = nodes.pop() {
+            match node {
+                Node::Leaf(leaf) => leaves.push(leaf),
+                Node::Internal(internal) => {
+                    let new_children = internal.children().filter(|(_, child_ref)| {
+                        from_version.map_or(true, |from_version| child_ref.version > from_version)
+                    });
+                    for (nibble, child_ref) in new_children {
+                        nodes.push(load_child(
+                            &self.db,
+                            nibbles,
+                            nibble,
+                            child_ref.is_leaf,
+                            child_ref.version,
+                        ));
+                    }
+                }
+            }
+        }
+
+        let Some(from_version) = from_version else {
+            let mut entries: Vec<_> = leaves
+                .into_iter()
+                .map(|leaf| TreeEntry::new(leaf.full_key, leaf.leaf_index, leaf.value_hash))
+                .collect();
+            entries.sort_unstable_by_key(|entry| entry.leaf_index);
+            return Ok(entries);
+        };
+        let keys: Vec<_> = leaves.iter().map(|leaf| leaf.full_key).collect();
+        let prev_entries =
+            load_and_transform_entries(&self.db, from_version, &keys, extract_entry)?;
+        let mut entries: Vec<_> = leaves
+            .into_iter()
+            .zip(prev_entries)
+            // Filter out leaves moved as a result of an insertion, and no-op updates.
+            .filter(|(leaf, prev_entry)| {
+                prev_entry.is_empty() || prev_entry.value != leaf.value_hash
+            })
+            .map(|(leaf, _)| TreeEntry::new(leaf.full_key, leaf.leaf_index, leaf.value_hash))
+            .collect();
+        entries.sort_unstable_by_key(|entry| entry.leaf_index);
+        Ok(entries)
+    }
+}
+
+pub(crate) fn load_root(db: &impl Database, version: u64) -> Result<Root, NoVersionError> {
+    db.root(version).ok_or_else(|| {
+        let manifest = db.manifest().unwrap_or_default();
+        let err = NoVersionError {
+            missing_version: version,
+            version_count: manifest.version_count,
+        };
+        if err.is_pruned() {
+            TREE_METRICS.pruned_version_requests.inc();
+        }
+        err
+    })
+}
+
+pub(crate) fn load_child(
+    db: &impl Database,
+    nibbles: Nibbles,
+    nibble: u8,
+    is_leaf: bool,
+    version: u64,
+) -> (Nibbles, Node) {
+    let child_nibbles = nibbles.push(nibble).unwrap();
+    // ^ `unwrap()` is safe; there can be no internal nodes on the bottom-most tree level
+    let child = db.tree_node(&child_nibbles.with_version(version), is_leaf);
+    (child_nibbles, child.unwrap())
+    // ^ `unwrap()` is safe by construction
+}
+
+/// Finds the leaf with the closest key to `key` in the subtree rooted at `node`. Depending on `greater`,
+/// the found key is either less or greater than `key`.
+fn find_neighbor(
+    db: &impl Database,
+    nibbles: Nibbles,
+    node: &Node,
+    key: &Key,
+    greater: bool,
+) -> Option<LeafNode> {
+    let internal = match node {
+        Node::Leaf(leaf) => {
+            let is_neighbor = if greater {
+                leaf.full_key > *key
+            } else {
+                leaf.full_key < *key
+            };
+            return is_neighbor.then_some(*leaf);
+        }
+        Node::Internal(internal) => internal,
+    };
+
+    let key_nibble = Nibbles::nibble(key, nibbles.nibble_count());
+    if let Some(child_ref) = internal.child_ref(key_nibble) {
+        let (child_nibbles, child) = load_child(
+            db,
+            nibbles,
+            key_nibble,
+            child_ref.is_leaf,
+            child_ref.version,
+        );
+        if let Some(leaf) = find_neighbor(db, child_nibbles, &child, key, greater) {
+            return Some(leaf);
+        }
+    }
+
+    // The neighbor is the extreme leaf in the closest sibling subtree.
+    let mut siblings = internal.children().filter(|&(nibble, _)| {
+        if greater {
+            nibble > key_nibble
+        } else {
+            nibble < key_nibble
+        }
+    });
+    let (nibble, child_ref) = if greater {
+        siblings.next()?
+    } else {
+        siblings.last()?
+    };
+    let (mut nibbles, mut node) =
+        load_child(db, nibbles, nibble, child_ref.is_leaf, child_ref.version);
+    loop {
+        match node {
+            Node::Leaf(leaf) => return Some(leaf),
+            Node::Internal(internal) => {
+                let (nibble, child_ref) = if greater {
+                    internal.children().next().unwrap()
+                    // ^ `unwrap()` is safe by construction; all persisted internal nodes are not empty
+                } else {
+                    internal.last_child_ref()
+                };
+                (nibbles, node) =
+                    load_child(db, nibbles, nibble, child_ref.is_leaf, child_ref.version);
+            }
+        }
+    }
 }
 
 fn load_and_transform_entries<T>(
     db: &impl Database,
     version: u64,
     leaf_keys: &[Key],
-    mut transform: impl FnMut(&mut WorkingPatchSet, &Key, &Nibbles) -> T,
+    transform: impl FnMut(&mut WorkingPatchSet, &Key, &Nibbles) -> T,
 ) -> Result<Vec<T>, NoVersionError> {
-    let root = db.root(version).ok_or_else(|| {
-        let manifest = db.manifest().unwrap_or_default();
-        NoVersionError {
-            missing_version: version,
-            version_count: manifest.version_count,
-        }
-    })?;
+    let root = load_root(db, version)?;
+    Ok(transform_entries(db, version, root, leaf_keys, transform))
+}
+
+fn transform_entries<T>(
+    db: &impl Database,
+    version: u64,
+    root: Root,
+    leaf_keys: &[Key],
+    mut transform: impl FnMut(&mut WorkingPatchSet, &Key, &Nibbles) -> T,
+) -> Vec<T> {
     let sorted_keys = SortedKeys::new(leaf_keys.iter().copied());
     let mut patch_set = WorkingPatchSet::new(version, root);
     let LoadAncestorsResult {
         longest_prefixes, ..
     } = patch_set.load_ancestors(&sorted_keys, db);
 
-    Ok(leaf_keys
+    leaf_keys
         .iter()
         .zip(&longest_prefixes)
         .map(|(leaf_key, longest_prefix)| transform(&mut patch_set, leaf_key, longest_prefix))
-        .collect())
+        .collect()
+}
+
+fn create_entry_with_proof(
+    hasher: &mut HasherWithStats<'_>,
+    patch_set: &mut WorkingPatchSet,
+    &leaf_key: &Key,
+    longest_prefix: &Nibbles,
+) -> TreeEntryWithProof {
+    let (leaf, merkle_path) = patch_set.create_proof(hasher, leaf_key, longest_prefix, 0);
+    let value = leaf
+        .as_ref()
+        .map_or_else(ValueHash::zero, |leaf| leaf.value_hash);
+    TreeEntry {
+        key: leaf_key,
+        value,
+        leaf_index: leaf.map_or(0, |leaf| leaf.leaf_index),
+    }
+    .with_merkle_path(merkle_path.into_inner())
 }
 
 fn extract_entry(
@@ -116,7 +531,7 @@ impl<DB: PruneDatabase, H: HashTree> MerkleTreeRecovery<DB, H> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::PatchSet;
+    use crate::{MerkleTreePruner, PatchSet};
 
     #[test]
     fn entries_in_empty_tree() {
@@ -153,4 +568,55 @@ mod tests {
         assert!(entries[1].base.is_empty());
         entries[1].verify(&tree.hasher, output.root_hash);
     }
+
+    #[test]
+    fn merkle_path_len_matches_proofs() {
+        let mut tree = MerkleTree::new(PatchSet::default());
+        let keys = [
+            Key::from(0),
+            Key::from(1),
+            Key::from(1) << 255,
+            Key::from(0xf0) << 248,
+            Key::from(0xf1) << 248,
+            Key::from(987_654),
+        ];
+        let entries = keys
+            .iter()
+            .zip(1..)
+            .map(|(&key, leaf_index)| TreeEntry::new(key, leaf_index, ValueHash::repeat_byte(1)));
+        tree.extend(entries.collect());
+
+        let proofs = tree.entries_with_proofs(0, &keys).unwrap();
+        for (key, proof) in keys.iter().zip(&proofs) {
+            let len = tree.merkle_path_len(0, *key).unwrap();
+            assert_eq!(len, Some(proof.merkle_path.len()), "{key:?}");
+        }
+
+        let missing_key = Key::from(123);
+        assert_eq!(tree.merkle_path_len(0, missing_key).unwrap(), None);
+        assert!(tree.merkle_path_len(1, missing_key).is_err());
+    }
+
+    #[test]
+    fn requesting_pruned_versions() {
+        let mut db = PatchSet::default();
+        let mut tree = MerkleTree::new(&mut db);
+        let key = Key::from(987_654);
+        for i in 1..=3 {
+            tree.extend(vec![TreeEntry::new(key, 1, ValueHash::repeat_byte(i))]);
+        }
+        let (mut pruner, _handle) = MerkleTreePruner::new(&mut db, 0);
+        pruner.run_once().unwrap();
+
+        let tree = MerkleTree::new(db);
+        let prev_request_count = TREE_METRICS.pruned_version_requests.get();
+        let err = tree.entries(0, &[key]).unwrap_err();
+        assert!(err.is_pruned(), "{err}");
+        assert!(TREE_METRICS.pruned_version_requests.get() > prev_request_count);
+
+        let err = tree.entries(3, &[key]).unwrap_err();
+        assert!(!err.is_pruned(), "{err}");
+        let entries = tree.entries(2, &[key]).unwrap();
+        assert_eq!(entries[0].value, ValueHash::repeat_byte(3));
+    }
 }
@@ -1,6 +1,6 @@
 //! Hashing operations on the Merkle tree.
 
-use std::{fmt, iter};
+use std::{fmt, iter, sync::Arc};
 
 use once_cell::sync::Lazy;
 use zksync_crypto::hasher::{blake2::Blake2Hasher, Hasher};
@@ -54,9 +54,27 @@ impl<H: HashTree + ?Sized> HashTree for &H {
     }
 }
 
+impl<H: HashTree + ?Sized> HashTree for Arc<H> {
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+
+    fn hash_leaf(&self, value_hash: &ValueHash, leaf_index: u64) -> ValueHash {
+        (**self).hash_leaf(value_hash, leaf_index)
+    }
+
+    fn hash_branch(&self, lhs: &ValueHash, rhs: &ValueHash) -> ValueHash {
+        (**self).hash_branch(lhs, rhs)
+    }
+
+    fn empty_subtree_hash(&self, depth: usize) -> ValueHash {
+        (**self).empty_subtree_hash(depth)
+    }
+}
+
 impl dyn HashTree + '_ {
     /// Extends the provided `path` to length `TREE_DEPTH`.
-    fn extend_merkle_path<'a>(
+    pub(crate) fn extend_merkle_path<'a>(
         &'a self,
         path: &'a [ValueHash],
     ) -> impl Iterator<Item = ValueHash> + 'a {
@@ -65,7 +83,7 @@ impl dyn HashTree + '_ {
         empty_hashes.chain(path.iter().copied())
     }
 
-    fn fold_merkle_path(&self, path: &[ValueHash], entry: TreeEntry) -> ValueHash {
+    pub(crate) fn fold_merkle_path(&self, path: &[ValueHash], entry: TreeEntry) -> ValueHash {
         let mut hash = self.hash_leaf(&entry.value, entry.leaf_index);
         let full_path = self.extend_merkle_path(path);
         for (depth, adjacent_hash) in full_path.enumerate() {
@@ -49,9 +49,14 @@
 use zksync_crypto::hasher::blake2::Blake2Hasher;
 
 pub use crate::{
-    errors::NoVersionError,
+    errors::{
+        BackgroundSaveError, ConsistencyError, EmptyTreeError, GenesisAlreadyExists,
+        LatestEntriesError, NoVersionError, PendingLimitExceeded, ProcessL1BatchError,
+        ProofWithStateError, RebuildWitnessError, RootNotFoundError, SnapshotError, TreeOpenError,
+        WitnessTooLarge,
+    },
     hasher::{HashTree, TreeRangeDigest},
-    pruning::{MerkleTreePruner, MerkleTreePrunerHandle},
+    pruning::{MerkleTreePruner, MerkleTreePrunerHandle, OrphanReport},
     storage::{
         Database, MerkleTreeColumnFamily, PatchSet, Patched, PruneDatabase, PrunePatchSet,
         RocksDBWrapper,
@@ -182,6 +187,21 @@ impl<DB: Database, H: HashTree> MerkleTree<DB, H> {
         self.db.manifest()?.version_count.checked_sub(1)
     }
 
+    /// Returns the earliest tree version not removed by pruning. Relies on the fact that retained versions
+    /// are contiguous and end at `latest_version`.
+    pub(crate) fn first_retained_version(&self, latest_version: u64) -> u64 {
+        let (mut lo, mut hi) = (0, latest_version);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.root(mid).is_some() {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
+        }
+        lo
+    }
+
     /// Returns the root hash for the latest version of the tree.
     pub fn latest_root_hash(&self) -> ValueHash {
         let root_hash = self
@@ -222,6 +242,15 @@ impl<DB: Database, H: HashTree> MerkleTree<DB, H> {
         output
     }
 
+    /// Computes the output of [`Self::extend()`] with the specified `entries` without persisting
+    /// the new tree version.
+    pub(crate) fn preview_extend(&self, entries: Vec<TreeEntry>) -> BlockOutput {
+        let next_version = self.db.manifest().unwrap_or_default().version_count;
+        let storage = Storage::new(&self.db, &self.hasher, next_version, true);
+        let (output, _) = storage.extend(entries);
+        output
+    }
+
     /// Extends this tree by creating its new version, computing an authenticity Merkle proof
     /// for each provided instruction.
     ///
@@ -7,9 +7,12 @@ use std::{
 };
 
 use vise::{
-    Buckets, EncodeLabelSet, EncodeLabelValue, Family, Gauge, Global, Histogram, Metrics, Unit,
+    Buckets, Counter, EncodeLabelSet, EncodeLabelValue, Family, Gauge, Global, Histogram, Metrics,
+    Unit,
 };
 
+use zksync_storage::BlockCacheStats;
+
 use crate::types::Nibbles;
 
 #[derive(Debug, Metrics)]
@@ -22,6 +25,59 @@ pub(crate) struct GeneralMetrics {
 #[vise::register]
 pub(crate) static GENERAL_METRICS: Global<GeneralMetrics> = Global::new();
 
+/// Operation mode of the domain-specific tree wrapper.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
+#[metrics(label = "mode", rename_all = "snake_case")]
+pub(crate) enum TreeModeLabel {
+    Lightweight,
+    LightweightWithReads,
+    Full,
+}
+
+const INSTRUCTION_COUNT_BUCKETS: Buckets = Buckets::exponential(1.0..=1_048_576.0, 4.0);
+
+/// Metrics related to the domain-specific tree wrapper.
+#[derive(Debug, Metrics)]
+#[metrics(prefix = "merkle_tree")]
+pub(crate) struct TreeMetrics {
+    /// Total number of tree versions truncated when reverting the tree.
+    pub reverted_versions: Counter,
+    /// Total number of RocksDB block cache hits for the tree database. Sampled periodically
+    /// from RocksDB statistics, so it may lag behind the actual value.
+    pub block_cache_hits: Counter,
+    /// Total number of RocksDB block cache misses (i.e., block reads served from disk)
+    /// for the tree database. Sampled in the same way as `block_cache_hits`.
+    pub block_cache_misses: Counter,
+    /// Total number of requests for tree versions that were removed by pruning. Requests for versions
+    /// that do not exist yet are not counted. A growing value signals that pruning is too aggressive
+    /// for the access pattern of tree readers.
+    pub pruned_version_requests: Counter,
+    /// Current number of proof requests waiting for a concurrency permit on a tree reader
+    /// with a concurrency limit.
+    pub queued_proof_requests: Gauge<u64>,
+    /// Total number of read instructions in L1 batches processed by the tree in the full mode.
+    pub read_instructions: Counter,
+    /// Total number of write instructions in L1 batches processed by the tree in the full mode.
+    pub write_instructions: Counter,
+    /// Number of instructions (reads and writes) in an L1 batch processed by the tree.
+    #[metrics(buckets = INSTRUCTION_COUNT_BUCKETS)]
+    pub batch_instruction_count: Family<TreeModeLabel, Histogram<usize>>,
+}
+
+impl TreeMetrics {
+    /// Advances block cache counters to the sampled cumulative values. RocksDB statistics are reset
+    /// when the DB is reopened; in this case, counters stay the same until the sampled values exceed them.
+    pub fn observe_block_cache_stats(&self, stats: BlockCacheStats) {
+        let new_hits = stats.hits.saturating_sub(self.block_cache_hits.get());
+        self.block_cache_hits.inc_by(new_hits);
+        let new_misses = stats.misses.saturating_sub(self.block_cache_misses.get());
+        self.block_cache_misses.inc_by(new_misses);
+    }
+}
+
+#[vise::register]
+pub(crate) static TREE_METRICS: Global<TreeMetrics> = Global::new();
+
 const BYTE_SIZE_BUCKETS: Buckets = Buckets::exponential(65_536.0..=16.0 * 1_024.0 * 1_024.0, 2.0);
 
 #[derive(Debug, Metrics)]
@@ -321,6 +377,10 @@ struct PruningMetrics {
     /// Lower and upper boundaries on the new stale key versions deleted
     /// during a pruning iteration. The lower boundary is inclusive, the upper one is exclusive.
     deleted_stale_key_versions: Family<Bound, Gauge<u64>>,
+    /// Number of versions with stale keys remaining to be pruned up to the target retained version
+    /// after a pruning iteration. Persistently growing values signal that pruning is stalled or cannot keep up
+    /// with the tree.
+    pending_stale_key_versions: Gauge<u64>,
 }
 
 #[vise::register]
@@ -343,6 +403,11 @@ impl PruningStats {
             .set(self.deleted_stale_key_versions.start);
         PRUNING_METRICS.deleted_stale_key_versions[&Bound::End]
             .set(self.deleted_stale_key_versions.end);
+        let pending_versions =
+            (self.target_retained_version + 1).saturating_sub(self.deleted_stale_key_versions.end);
+        PRUNING_METRICS
+            .pending_stale_key_versions
+            .set(pending_versions);
     }
 }
 
@@ -355,6 +420,9 @@ pub(crate) struct PruningTimings {
     /// Time spent removing stale keys from RocksDB per pruning iteration.
     #[metrics(buckets = Buckets::LATENCIES)]
     pub apply_patch: Histogram<Duration>,
+    /// Total time spent per pruning iteration that has collected stale keys.
+    #[metrics(buckets = Buckets::LATENCIES)]
+    pub iteration: Histogram<Duration>,
 }
 
 #[vise::register]
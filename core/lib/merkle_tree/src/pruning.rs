@@ -1,10 +1,11 @@
 //! Tree pruning logic.
 
-use std::{fmt, sync::mpsc, time::Duration};
+use std::{collections::HashSet, fmt, sync::mpsc, time::Duration};
 
 use crate::{
     metrics::{PruningStats, PRUNING_TIMINGS},
-    storage::{PruneDatabase, PrunePatchSet},
+    storage::{Database, PruneDatabase, PrunePatchSet, RocksDBWrapper},
+    types::{Nibbles, Node, Root},
 };
 
 /// Handle for a [`MerkleTreePruner`] allowing to abort its operation.
@@ -24,6 +25,70 @@ impl MerkleTreePrunerHandle {
     }
 }
 
+/// Report on orphaned tree nodes, i.e. nodes persisted in the database, but not reachable from any
+/// of the tree roots. Orphaned nodes are never removed by the pruner and thus waste disk space.
+/// Produced by [`ZkSyncTreeReader::audit_orphans()`](crate::domain::ZkSyncTreeReader::audit_orphans()).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct OrphanReport {
+    /// Total number of tree nodes (including roots) in the database.
+    pub node_count: u64,
+    /// Number of orphaned nodes.
+    pub orphan_count: u64,
+    /// Approximate total byte size of orphaned nodes (keys and values).
+    pub orphan_byte_size: u64,
+}
+
+impl OrphanReport {
+    /// Scans the database for orphaned nodes. This is a read-only, but potentially slow and memory-intensive
+    /// operation: it traverses all nodes reachable from the roots of all versions present in the database,
+    /// and then all nodes in the database.
+    pub(crate) fn new(db: &RocksDBWrapper) -> Self {
+        let version_count = db.manifest().map_or(0, |manifest| manifest.version_count);
+        let mut reachable_keys = HashSet::new();
+        for version in 0..version_count {
+            // Roots of old versions may be pruned.
+            let Some(root) = db.root(version) else {
+                continue;
+            };
+            reachable_keys.insert(Nibbles::EMPTY.with_version(version));
+            let Root::Filled {
+                node: Node::Internal(root_node),
+                ..
+            } = root
+            else {
+                continue;
+            };
+
+            let mut nodes_to_visit = vec![(Nibbles::EMPTY, root_node)];
+            while let Some((nibbles, node)) = nodes_to_visit.pop() {
+                for (nibble, child_ref) in node.children() {
+                    let child_key = nibbles.push(nibble).unwrap();
+                    // ^ `unwrap()` is safe; there can be no internal nodes on the bottom-most tree level
+                    let child_key = child_key.with_version(child_ref.version);
+                    if !reachable_keys.insert(child_key) || child_ref.is_leaf {
+                        // The child is a leaf, or the subtree was already visited from another root.
+                        continue;
+                    }
+                    if let Some(Node::Internal(child)) = db.tree_node(&child_key, false) {
+                        nodes_to_visit.push((child_key.nibbles, child));
+                    }
+                }
+            }
+        }
+
+        let mut report = Self::default();
+        for (key, byte_size) in db.node_keys_with_sizes() {
+            report.node_count += 1;
+            if !reachable_keys.contains(&key) {
+                report.orphan_count += 1;
+                report.orphan_byte_size += byte_size as u64;
+            }
+        }
+        report
+    }
+}
+
 /// Component responsible for Merkle tree pruning, i.e. removing nodes not referenced by new versions
 /// of the tree. A pruner should be instantiated using a [`Clone`] of the tree database, possibly
 /// configured and then [`run()`](Self::run()) on its own thread. [`MerkleTreePrunerHandle`] provides
@@ -108,6 +173,7 @@ impl<DB: PruneDatabase> MerkleTreePruner<DB> {
         let stale_key_new_versions = min_stale_key_version..=target_retained_version;
         tracing::info!("Collecting stale keys with new versions in {stale_key_new_versions:?}");
 
+        let iteration_latency = PRUNING_TIMINGS.iteration.start();
         let load_stale_keys_latency = PRUNING_TIMINGS.load_stale_keys.start();
         let mut pruned_keys = vec![];
         let mut max_stale_key_version = min_stale_key_version;
@@ -139,6 +205,7 @@ impl<DB: PruneDatabase> MerkleTreePruner<DB> {
         let apply_patch_latency = PRUNING_TIMINGS.apply_patch.start();
         self.db.prune(patch);
         apply_patch_latency.observe();
+        iteration_latency.observe();
         Some(stats)
     }
 
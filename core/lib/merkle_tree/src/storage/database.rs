@@ -1,6 +1,6 @@
 //! `Database` trait and its implementations.
 
-use std::{any::Any, ops};
+use std::{any::Any, ops, sync::Arc};
 
 use crate::{
     errors::DeserializeError,
@@ -177,7 +177,15 @@ impl Database for PatchSet {
             // Remove obsolete sub-patches from the patch.
             self.patches_by_version
                 .retain(|&version, _| version < new_version_count);
+            self.stale_keys_by_version
+                .retain(|&version, _| version < new_version_count);
+            self.witness_inputs
+                .retain(|&version, _| version < new_version_count);
+        }
+        if let Some(first_version) = other.removed_witness_inputs_from {
+            self.remove_witness_inputs(first_version);
         }
+        self.witness_inputs.extend(other.witness_inputs);
         self.manifest = other.manifest;
         self.patches_by_version.extend(other.patches_by_version);
         for (version, stale_keys) in other.stale_keys_by_version {
@@ -196,16 +204,24 @@ impl Database for PatchSet {
 // an instruction to truncate tree versions. In order to do this, we use the
 // `is_responsible_for_version()` in `PatchSet`, which is based not only on the contained
 // tree roots, but on the manifest as well.
+//
+// While the patch is being flushed in the background (see `Self::freeze()`), it is kept as a readonly
+// frozen layer between the mutable patch and the wrapped DB, and the lookup logic is applied to both patches in turn.
 #[derive(Debug)]
 pub struct Patched<DB> {
     inner: DB,
     patch: Option<PatchSet>,
+    frozen_patch: Option<Arc<PatchSet>>,
 }
 
 impl<DB: Database> Patched<DB> {
     /// Wraps the provided database.
     pub fn new(inner: DB) -> Self {
-        Self { inner, patch: None }
+        Self {
+            inner,
+            patch: None,
+            frozen_patch: None,
+        }
     }
 
     pub(crate) fn patched_versions(&self) -> Vec<u64> {
@@ -214,12 +230,23 @@ impl<DB: Database> Patched<DB> {
         })
     }
 
-    /// Returns the value from the patch and a flag whether this value is final (i.e., a DB lookup
+    fn patches(&self) -> impl Iterator<Item = &PatchSet> + '_ {
+        self.patch.iter().chain(self.frozen_patch.as_deref())
+    }
+
+    /// Returns the value from the patches and a flag whether this value is final (i.e., a DB lookup
     /// is not required).
     fn lookup_patch(&self, key: &NodeKey, is_leaf: bool) -> (Option<Node>, bool) {
-        let Some(patch) = &self.patch else {
-            return (None, false);
-        };
+        for patch in self.patches() {
+            let (node, is_final) = Self::lookup_single_patch(patch, key, is_leaf);
+            if is_final || node.is_some() {
+                return (node, is_final);
+            }
+        }
+        (None, false)
+    }
+
+    fn lookup_single_patch(patch: &PatchSet, key: &NodeKey, is_leaf: bool) -> (Option<Node>, bool) {
         if patch.is_new_version(key.version) {
             return (patch.tree_node(key, is_leaf), true);
         }
@@ -233,6 +260,12 @@ impl<DB: Database> Patched<DB> {
         (None, false)
     }
 
+    /// Estimates the memory footprint of the patches held by this wrapper (including a frozen patch, if any)
+    /// in bytes.
+    pub(crate) fn estimated_memory_usage(&self) -> usize {
+        self.patches().map(PatchSet::estimated_memory_usage).sum()
+    }
+
     /// Provides readonly access to the wrapped DB.
     pub(crate) fn inner(&self) -> &DB {
         &self.inner
@@ -243,18 +276,93 @@ impl<DB: Database> Patched<DB> {
         &mut self.inner
     }
 
+    fn patch_mut(&mut self) -> &mut PatchSet {
+        if self.patch.is_none() {
+            let manifest = self.manifest().unwrap_or_default();
+            self.patch = Some(PatchSet::from_manifest(manifest));
+        }
+        self.patch.as_mut().unwrap()
+        // ^ `unwrap()` is safe by construction
+    }
+
+    /// Adds serialized witness inputs for the specified tree version to the changes held in RAM,
+    /// so that they are written to the wrapped database atomically with tree changes.
+    pub(crate) fn add_witness_inputs(&mut self, version: u64, raw_inputs: Vec<u8>) {
+        self.patch_mut().witness_inputs.insert(version, raw_inputs);
+    }
+
+    /// Removes witness inputs for all tree versions starting from `first_version`. Removal is held in RAM
+    /// together with other changes, i.e., it is only applied to the wrapped database on [flush](Self::flush()).
+    pub(crate) fn remove_witness_inputs(&mut self, first_version: u64) {
+        self.patch_mut().remove_witness_inputs(first_version);
+    }
+
     /// Flushes changes from RAM to the wrapped database.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the database has a [frozen](Self::freeze()) patch.
     pub fn flush(&mut self) {
+        assert!(
+            self.frozen_patch.is_none(),
+            "Cannot flush a `Patched` database with a frozen patch"
+        );
         if let Some(patch) = self.patch.take() {
             self.inner.apply_patch(patch);
         }
     }
 
     /// Forgets about changes held in RAM.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the database has a [frozen](Self::freeze()) patch.
     pub fn reset(&mut self) {
+        assert!(
+            self.frozen_patch.is_none(),
+            "Cannot reset a `Patched` database with a frozen patch"
+        );
         self.patch = None;
     }
 
+    /// Freezes the current changes so that they can be written to the wrapped database in the background.
+    /// Frozen changes remain visible to reads, and new changes are accumulated on top of them.
+    /// Returns `None` if there are no changes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the database already has a frozen patch.
+    pub(crate) fn freeze(&mut self) -> Option<Arc<PatchSet>> {
+        assert!(
+            self.frozen_patch.is_none(),
+            "`Patched` database already has a frozen patch"
+        );
+        let patch = Arc::new(self.patch.take()?);
+        self.frozen_patch = Some(patch.clone());
+        Some(patch)
+    }
+
+    /// Drops the frozen patch. If `is_flushed` is not set, the frozen changes are merged back with
+    /// the changes accumulated after freezing, as if [`Self::freeze()`] was never called.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `is_flushed` is not set, and the frozen patch is still referenced elsewhere.
+    pub(crate) fn unfreeze(&mut self, is_flushed: bool) {
+        let Some(frozen_patch) = self.frozen_patch.take() else {
+            return;
+        };
+        if is_flushed {
+            return;
+        }
+        let mut frozen_patch =
+            Arc::try_unwrap(frozen_patch).expect("frozen patch is still referenced");
+        if let Some(patch) = self.patch.take() {
+            frozen_patch.apply_patch(patch);
+        }
+        self.patch = Some(frozen_patch);
+    }
+
     /// Returns the wrapped database.
     ///
     /// # Panics
@@ -263,7 +371,7 @@ impl<DB: Database> Patched<DB> {
     /// or [`Self::reset()`] beforehand to avoid this panic.
     pub fn into_inner(self) -> DB {
         assert!(
-            self.patch.is_none(),
+            self.patch.is_none() && self.frozen_patch.is_none(),
             "The `Patched` database contains uncommitted changes"
         );
         self.inner
@@ -272,7 +380,7 @@ impl<DB: Database> Patched<DB> {
 
 impl<DB: Database> Database for Patched<DB> {
     fn try_manifest(&self) -> Result<Option<Manifest>, DeserializeError> {
-        if let Some(patch) = &self.patch {
+        if let Some(patch) = self.patches().next() {
             Ok(Some(patch.manifest.clone()))
         } else {
             self.inner.try_manifest()
@@ -280,7 +388,7 @@ impl<DB: Database> Database for Patched<DB> {
     }
 
     fn try_root(&self, version: u64) -> Result<Option<Root>, DeserializeError> {
-        if let Some(patch) = &self.patch {
+        for patch in self.patches() {
             let has_root = patch.is_new_version(version) || patch.updated_version == Some(version);
             if has_root {
                 return patch.try_root(version);
@@ -305,7 +413,7 @@ impl<DB: Database> Database for Patched<DB> {
     }
 
     fn tree_nodes(&self, keys: &NodeKeys) -> Vec<Option<Node>> {
-        if self.patch.is_none() {
+        if self.patches().next().is_none() {
             return self.inner.tree_nodes(keys);
         }
 
@@ -2,7 +2,7 @@
 
 use std::{
     collections::{hash_map::Entry, HashMap},
-    iter,
+    iter, mem,
     time::Instant,
 };
 
@@ -46,6 +46,10 @@ pub struct PatchSet {
     /// is smaller than all other keys in `patches_by_version`.
     pub(super) updated_version: Option<u64>,
     pub(super) stale_keys_by_version: HashMap<u64, Vec<NodeKey>>,
+    /// Serialized inputs for lazy witnesses keyed by the tree version. Written atomically with tree nodes.
+    pub(super) witness_inputs: HashMap<u64, Vec<u8>>,
+    /// If set, witness inputs for all versions starting from this one are removed before writing `witness_inputs`.
+    pub(super) removed_witness_inputs_from: Option<u64>,
 }
 
 impl PatchSet {
@@ -55,6 +59,8 @@ impl PatchSet {
             patches_by_version: HashMap::new(),
             updated_version: None,
             stale_keys_by_version: HashMap::new(),
+            witness_inputs: HashMap::new(),
+            removed_witness_inputs_from: None,
         }
     }
 
@@ -101,9 +107,22 @@ impl PatchSet {
             patches_by_version: HashMap::from([(version, partial_patch)]),
             updated_version,
             stale_keys_by_version: HashMap::from([(version, stale_keys)]),
+            witness_inputs: HashMap::new(),
+            removed_witness_inputs_from: None,
         }
     }
 
+    /// Removes witness inputs for all versions starting from `first_version`, both from this patch
+    /// and (once the patch is applied) from the database.
+    pub(super) fn remove_witness_inputs(&mut self, first_version: u64) {
+        self.witness_inputs
+            .retain(|&version, _| version < first_version);
+        self.removed_witness_inputs_from = Some(
+            self.removed_witness_inputs_from
+                .map_or(first_version, |version| version.min(first_version)),
+        );
+    }
+
     pub(super) fn is_new_version(&self, version: u64) -> bool {
         version >= self.manifest.version_count // this patch truncates `version`
             || (self.updated_version != Some(version) && self.patches_by_version.contains_key(&version))
@@ -124,6 +143,39 @@ impl PatchSet {
         });
         copied_hashes.sum()
     }
+
+    /// Estimates the memory footprint of this patch set in bytes. The estimate is based on the allocated
+    /// capacity of the contained collections and heap allocations of nodes, so it scales linearly
+    /// with the number of nodes and stale keys. Allocator overhead is not taken into account.
+    pub(crate) fn estimated_memory_usage(&self) -> usize {
+        // Each `hashbrown` bucket has a 1-byte control word besides the entry itself.
+        const HASH_MAP_BUCKET_OVERHEAD: usize = 1;
+
+        let node_bucket_size = mem::size_of::<(NodeKey, Node)>() + HASH_MAP_BUCKET_OVERHEAD;
+        let patches_size = self.patches_by_version.values().map(|patch| {
+            let root_heap_size = match &patch.root {
+                Some(Root::Filled { node, .. }) => node.heap_size(),
+                Some(Root::Empty) | None => 0,
+            };
+            let nodes_heap_size: usize = patch.nodes.values().map(Node::heap_size).sum();
+            mem::size_of::<(u64, PartialPatchSet)>()
+                + HASH_MAP_BUCKET_OVERHEAD
+                + patch.nodes.capacity() * node_bucket_size
+                + nodes_heap_size
+                + root_heap_size
+        });
+        let stale_keys_size = self.stale_keys_by_version.values().map(|keys| {
+            mem::size_of::<(u64, Vec<NodeKey>)>()
+                + HASH_MAP_BUCKET_OVERHEAD
+                + keys.capacity() * mem::size_of::<NodeKey>()
+        });
+        let witness_inputs_size = self.witness_inputs.values().map(|raw_inputs| {
+            mem::size_of::<(u64, Vec<u8>)>() + HASH_MAP_BUCKET_OVERHEAD + raw_inputs.capacity()
+        });
+        patches_size.sum::<usize>()
+            + stale_keys_size.sum::<usize>()
+            + witness_inputs_size.sum::<usize>()
+    }
 }
 
 #[cfg(test)] // extensions to test tree consistency
@@ -673,6 +725,36 @@ impl WorkingPatchSet {
         };
         (leaf, merkle_path)
     }
+
+    /// Computes the length of the non-empty part of the Merkle path for the specified `key`
+    /// (i.e., the length of the path returned by [`Self::create_proof()`]) without hashing.
+    /// Returns `None` if the `key` is not present in the tree.
+    pub(crate) fn merkle_path_len(&self, key: Key, parent_nibbles: &Nibbles) -> Option<usize> {
+        let TraverseOutcome::LeafMatch(mut nibbles, _) = self.traverse(key, parent_nibbles) else {
+            return None;
+        };
+
+        while let Some((parent_nibbles, last_nibble)) = nibbles.split_last() {
+            let Some(Node::Internal(parent)) = self.get(&parent_nibbles) else {
+                unreachable!("leaf ancestors are always internal nodes");
+            };
+            // Look for a non-empty sibling subtree inside `parent`, starting from the bottom-most level.
+            // At `level_in_node`, the sibling subtree consists of the children whose nibbles share
+            // `level_in_node - 1` most significant bits with `last_nibble` and differ in the next bit.
+            for level_in_node in (1..=4).rev() {
+                let shift = 4 - level_in_node;
+                let sibling_prefix = (last_nibble >> shift) ^ 1;
+                let has_sibling = parent
+                    .children()
+                    .any(|(nibble, _)| nibble >> shift == sibling_prefix);
+                if has_sibling {
+                    return Some(parent_nibbles.nibble_count() * 4 + level_in_node);
+                }
+            }
+            nibbles = parent_nibbles;
+        }
+        Some(0)
+    }
 }
 
 #[cfg(test)]
@@ -1,6 +1,6 @@
 //! RocksDB implementation of [`Database`].
 
-use std::{any::Any, cell::RefCell, path::Path, sync::Arc};
+use std::{any::Any, cell::RefCell, mem, path::Path, sync::Arc};
 
 use rayon::prelude::*;
 use thread_local::ThreadLocal;
@@ -8,7 +8,7 @@ use zksync_storage::{
     db::{NamedColumnFamily, ProfileGuard, ProfiledOperation},
     rocksdb,
     rocksdb::DBPinnableSlice,
-    RocksDB,
+    BlockCacheStats, RocksDB,
 };
 
 use crate::{
@@ -32,16 +32,20 @@ pub enum MerkleTreeColumnFamily {
     Tree,
     /// Column family containing stale node keys that are eventually removed by the pruning logic.
     StaleKeys,
+    /// Column family containing inputs necessary to lazily reconstruct witnesses, keyed by the tree version.
+    /// Only populated if lazy witnesses are enabled for [`ZkSyncTree`](crate::domain::ZkSyncTree).
+    WitnessInputs,
 }
 
 impl NamedColumnFamily for MerkleTreeColumnFamily {
     const DB_NAME: &'static str = "merkle_tree";
-    const ALL: &'static [Self] = &[Self::Tree, Self::StaleKeys];
+    const ALL: &'static [Self] = &[Self::Tree, Self::StaleKeys, Self::WitnessInputs];
 
     fn name(&self) -> &'static str {
         match self {
             Self::Tree => "default",
             Self::StaleKeys => "stale_keys",
+            Self::WitnessInputs => "witness_inputs",
         }
     }
 
@@ -71,6 +75,9 @@ pub struct RocksDBWrapper {
     // struct (as opposed to `thread_local!` vars).
     profiled_operation: Arc<ThreadLocal<LocalProfiledOperation>>,
     multi_get_chunk_size: usize,
+    /// If set, writing patches fails. Shared among clones of the wrapper.
+    #[cfg(test)]
+    write_failure: Arc<std::sync::atomic::AtomicBool>,
 }
 
 impl RocksDBWrapper {
@@ -99,10 +106,30 @@ impl RocksDBWrapper {
     /// Thus, setting this value to around `100..1_000` can still lead to substantial
     /// performance boost (order of 2x) in some environments.
     ///
+    /// Returns the previously set chunk size.
+    ///
     /// [RocksDB docs]: https://github.com/facebook/rocksdb/wiki/MultiGet-Performance
     // TODO (BFT-153): Benchmark multi-get performance to find out optimal value
-    pub fn set_multi_get_chunk_size(&mut self, chunk_size: usize) {
-        self.multi_get_chunk_size = chunk_size;
+    pub fn set_multi_get_chunk_size(&mut self, chunk_size: usize) -> usize {
+        mem::replace(&mut self.multi_get_chunk_size, chunk_size)
+    }
+
+    /// Makes all subsequent patch writes fail (or stops failing them) for this wrapper and all its clones.
+    #[cfg(test)]
+    pub(crate) fn set_write_failure(&self, fail: bool) {
+        self.write_failure
+            .store(fail, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Returns the approximate on-disk size of the specified column family in bytes.
+    pub(crate) fn sst_size(&self, cf: MerkleTreeColumnFamily) -> u64 {
+        self.db.sst_size(cf)
+    }
+
+    /// Returns cumulative block cache stats for the underlying RocksDB instance, or `None` if statistics
+    /// are not enabled for it.
+    pub(crate) fn block_cache_stats(&self) -> Option<BlockCacheStats> {
+        self.db.block_cache_stats()
     }
 
     fn raw_node(&self, key: &[u8]) -> Option<Vec<u8>> {
@@ -156,6 +183,29 @@ impl RocksDBWrapper {
         })
     }
 
+    /// Iterates over all tree node keys in the database together with the byte size of the key-value entries.
+    pub(crate) fn node_keys_with_sizes(&self) -> impl Iterator<Item = (NodeKey, usize)> + '_ {
+        let kvs = self
+            .db
+            .prefix_iterator_cf(MerkleTreeColumnFamily::Tree, &[]);
+        kvs.filter_map(|(key, value)| {
+            if &*key == Self::MANIFEST_KEY {
+                return None;
+            }
+            Some((NodeKey::from_db_key(&key), key.len() + value.len()))
+        })
+    }
+
+    /// Returns serialized witness inputs for the specified tree version, if any.
+    pub(crate) fn witness_inputs(&self, version: u64) -> Option<Vec<u8>> {
+        self.db
+            .get_cf(
+                MerkleTreeColumnFamily::WitnessInputs,
+                &version.to_be_bytes(),
+            )
+            .expect("Failed reading from RocksDB")
+    }
+
     /// Returns the wrapped RocksDB instance.
     pub fn into_inner(self) -> RocksDB<MerkleTreeColumnFamily> {
         self.db
@@ -168,6 +218,8 @@ impl From<RocksDB<MerkleTreeColumnFamily>> for RocksDBWrapper {
             db,
             profiled_operation: Arc::new(ThreadLocal::new()),
             multi_get_chunk_size: usize::MAX,
+            #[cfg(test)]
+            write_failure: Arc::default(),
         }
     }
 }
@@ -237,8 +289,19 @@ impl Database for RocksDBWrapper {
         })
     }
 
-    #[allow(clippy::missing_panics_doc)]
     fn apply_patch(&mut self, patch: PatchSet) {
+        self.write_patch(&patch);
+    }
+}
+
+impl RocksDBWrapper {
+    /// Writes the patch to RocksDB. Unlike [`Database::apply_patch()`], this doesn't require exclusive access
+    /// to the wrapper, so it can be used to write a patch in the background.
+    ///
+    /// # Panics
+    ///
+    /// Panics if writing to RocksDB fails.
+    pub(crate) fn write_patch(&self, patch: &PatchSet) {
         let tree_cf = MerkleTreeColumnFamily::Tree;
         let mut write_batch = self.db.new_write_batch();
         let mut node_bytes = Vec::with_capacity(128);
@@ -249,7 +312,7 @@ impl Database for RocksDBWrapper {
         patch.manifest.serialize(&mut node_bytes);
         write_batch.put_cf(tree_cf, Self::MANIFEST_KEY, &node_bytes);
 
-        for (version, sub_patch) in patch.patches_by_version {
+        for (&version, sub_patch) in &patch.patches_by_version {
             let is_update = patch.updated_version == Some(version);
             let root_key = NodeKey::empty(version);
             if !is_update {
@@ -260,13 +323,13 @@ impl Database for RocksDBWrapper {
                 write_batch.delete_range_cf(tree_cf, keys_to_delete);
             }
 
-            if let Some(root) = sub_patch.root {
+            if let Some(root) = &sub_patch.root {
                 node_bytes.clear();
                 root.serialize(&mut node_bytes);
                 metrics.update_node_bytes(&Nibbles::EMPTY, &node_bytes);
                 write_batch.put_cf(tree_cf, &root_key.to_db_key(), &node_bytes);
             }
-            for (node_key, node) in sub_patch.nodes {
+            for (node_key, node) in &sub_patch.nodes {
                 node_bytes.clear();
                 node.serialize(&mut node_bytes);
                 metrics.update_node_bytes(&node_key.nibbles, &node_bytes);
@@ -277,15 +340,31 @@ impl Database for RocksDBWrapper {
         let stale_keys_cf = MerkleTreeColumnFamily::StaleKeys;
         let all_stale_keys = patch
             .stale_keys_by_version
-            .into_iter()
-            .flat_map(|(version, keys)| {
-                keys.into_iter()
-                    .map(move |key| StaleNodeKey::new(key, version))
+            .iter()
+            .flat_map(|(&version, keys)| {
+                keys.iter().map(move |&key| StaleNodeKey::new(key, version))
             });
         for replaced_key in all_stale_keys {
             write_batch.put_cf(stale_keys_cf, &replaced_key.to_db_key(), &[]);
         }
 
+        // Removal must precede insertions since the removed range may include inserted versions.
+        let witness_inputs_cf = MerkleTreeColumnFamily::WitnessInputs;
+        if let Some(first_version) = patch.removed_witness_inputs_from {
+            let first_version = &first_version.to_be_bytes() as &[_];
+            write_batch.delete_range_cf(witness_inputs_cf, first_version..&u64::MAX.to_be_bytes());
+        }
+        for (version, raw_inputs) in &patch.witness_inputs {
+            write_batch.put_cf(witness_inputs_cf, &version.to_be_bytes(), raw_inputs);
+        }
+
+        #[cfg(test)]
+        assert!(
+            !self
+                .write_failure
+                .load(std::sync::atomic::Ordering::Relaxed),
+            "Injected failure writing a batch to RocksDB"
+        );
         self.db
             .write(write_batch)
             .expect("Failed writing a batch to RocksDB");
@@ -327,6 +406,11 @@ impl PruneDatabase for RocksDBWrapper {
         let first_version = &patch.deleted_stale_key_versions.start.to_be_bytes() as &[_];
         let last_version = &patch.deleted_stale_key_versions.end.to_be_bytes();
         write_batch.delete_range_cf(stale_keys_cf, first_version..last_version);
+        // Witness for a version cannot be reconstructed once the root of the preceding version is pruned.
+        // Roots are pruned as stale keys for the following version, so witness inputs are removed
+        // for all versions up to the last version with deleted stale keys.
+        let witness_inputs_cf = MerkleTreeColumnFamily::WitnessInputs;
+        write_batch.delete_range_cf(witness_inputs_cf, &[0; 8] as &[_]..last_version);
 
         self.db
             .write(write_batch)
@@ -2,7 +2,7 @@
 //! some of these types are declared as public and can be even exported using the `unstable` module.
 //! Still, logically these types are private, so adding them to new public APIs etc. is a logical error.
 
-use std::{fmt, num::NonZeroU64};
+use std::{fmt, mem, num::NonZeroU64};
 
 use crate::{
     hasher::{HashTree, InternalNodeCache},
@@ -434,6 +434,16 @@ impl InternalNode {
         self.children.len()
     }
 
+    /// Returns the approximate size of heap allocations owned by this node in bytes.
+    pub(crate) fn heap_size(&self) -> usize {
+        let cache_size = if self.cache.is_some() {
+            mem::size_of::<InternalNodeCache>()
+        } else {
+            0
+        };
+        self.children.len() * mem::size_of::<ChildRef>() + cache_size
+    }
+
     pub(crate) fn cache_mut(&mut self) -> Option<&mut InternalNodeCache> {
         self.cache.as_deref_mut()
     }
@@ -492,6 +502,16 @@ pub enum Node {
     Leaf(LeafNode),
 }
 
+impl Node {
+    /// Returns the approximate size of heap allocations owned by this node in bytes.
+    pub(crate) fn heap_size(&self) -> usize {
+        match self {
+            Self::Internal(node) => node.heap_size(),
+            Self::Leaf(_) => 0,
+        }
+    }
+}
+
 impl From<LeafNode> for Node {
     fn from(leaf: LeafNode) -> Self {
         Self::Leaf(leaf)
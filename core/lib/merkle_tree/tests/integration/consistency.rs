@@ -65,3 +65,34 @@ fn five_thousand_angry_monkeys_vs_merkle_tree() {
         raw_db.write(reverse_batch).unwrap();
     }
 }
+
+#[test]
+fn consistency_errors_are_deterministic() {
+    const RNG_SEED: u64 = 123;
+
+    let dir = TempDir::new().expect("failed creating temporary dir for RocksDB");
+    let mut db = RocksDBWrapper::new(dir.path()).unwrap();
+    let mut tree = MerkleTree::new(&mut db);
+    tree.extend(generate_key_value_pairs(0..1_000));
+
+    let mut raw_db = db.into_inner();
+    let cf = MerkleTreeColumnFamily::Tree;
+    let raw_kvs: Vec<_> = raw_db.prefix_iterator_cf(cf, &[0; 8]).collect();
+    let mut rng = StdRng::seed_from_u64(RNG_SEED);
+    let mut batch = raw_db.new_write_batch();
+    for (key, value) in raw_kvs.choose_multiple(&mut rng, 10) {
+        let mut mangled_value = value.to_vec();
+        let mangled_idx = rng.gen_range(0..mangled_value.len());
+        mangled_value[mangled_idx] ^= 1;
+        batch.put_cf(cf, key, &mangled_value);
+    }
+    raw_db.write(batch).unwrap();
+
+    let mut db = RocksDBWrapper::from(raw_db);
+    let tree = MerkleTree::new(&mut db);
+    let first_err = tree.verify_consistency(0, false).unwrap_err().to_string();
+    for _ in 0..10 {
+        let err = tree.verify_consistency(0, false).unwrap_err();
+        assert_eq!(err.to_string(), first_err);
+    }
+}
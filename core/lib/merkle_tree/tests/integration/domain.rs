@@ -1,13 +1,36 @@
 //! Domain-specific tests. Taken almost verbatim from the previous tree implementation.
 
-use std::slice;
+use std::{
+    collections::HashSet,
+    mem, slice,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
 
+use assert_matches::assert_matches;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use serde_with::{hex::Hex, serde_as};
 use tempfile::TempDir;
+use test_casing::test_casing;
+use tokio::sync::broadcast::error::TryRecvError;
 use zksync_crypto::hasher::blake2::Blake2Hasher;
-use zksync_merkle_tree::{domain::ZkSyncTree, HashTree, TreeEntry, TreeInstruction};
-use zksync_prover_interface::inputs::StorageLogMetadata;
+use zksync_merkle_tree::{
+    domain::{
+        decode_entries_with_proofs, verify_proofs_consistent, BatchProofNode, EnumerationError,
+        ImportError, LatencySignal, LatencyThresholdPolicy, ProofInconsistencyError,
+        RangeCommitmentError, TreeStructureStats, TreeWorkloadProfile, WitnessSink, WitnessStats,
+        ZkSyncTree,
+    },
+    HashTree, Key, LatestEntriesError, MerkleTreeColumnFamily, MerkleTreePruner,
+    ProcessL1BatchError, ProofWithStateError, RebuildWitnessError, RocksDBWrapper, SnapshotError,
+    TreeEntry, TreeInstruction, TreeLogEntry, TreeOpenError,
+};
+use zksync_prover_interface::inputs::{PrepareBasicCircuitsJob, StorageLogMetadata};
 use zksync_storage::RocksDB;
 use zksync_system_constants::ACCOUNT_CODE_STORAGE_ADDRESS;
 use zksync_types::{AccountTreeId, Address, L1BatchNumber, StorageKey, H256};
@@ -71,7 +94,126 @@ fn basic_workflow() {
 }
 
 #[test]
-fn basic_workflow_multiblock() {
+fn verifying_consistency_without_panicking() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    tree.process_l1_batch(&gen_storage_logs());
+    tree.save();
+
+    tree.try_verify_consistency(L1BatchNumber(0)).unwrap();
+    let err = tree.try_verify_consistency(L1BatchNumber(1)).unwrap_err();
+    assert_eq!(err.version, 1);
+    assert!(err.description.contains("does not exist"), "{err}");
+}
+
+#[test]
+fn opening_checked_tree() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_checked(db.into()).unwrap();
+    let logs = gen_storage_logs();
+    tree.process_l1_batch(&logs);
+    tree.save();
+    let root_hash = tree.root_hash();
+    drop(tree);
+
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let tree = ZkSyncTree::new_checked(db.into()).unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+    drop(tree);
+
+    // Remove a child of the root node.
+    let mut raw_db = RocksDBWrapper::from(RocksDB::new(temp_dir.as_ref()).unwrap()).into_inner();
+    let cf = MerkleTreeColumnFamily::Tree;
+    let child_key = raw_db
+        .prefix_iterator_cf(cf, &[0; 8])
+        .map(|(key, _)| key)
+        .find(|key| key.len() == 10 && key[8] == 1)
+        .unwrap();
+    let mut batch = raw_db.new_write_batch();
+    batch.delete_cf(cf, &child_key);
+    raw_db.write(batch).unwrap();
+
+    let err = ZkSyncTree::new_checked(raw_db.into()).unwrap_err();
+    let TreeOpenError::Corrupted(err) = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    assert_eq!(err.version, 0);
+    assert!(err.description.contains("missing"), "{err}");
+}
+
+#[test]
+fn verifying_recent_consistency() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    for chunk in gen_storage_logs().chunks(20) {
+        tree.process_l1_batch(chunk);
+    }
+    // Unsaved versions must be checked as well.
+    tree.verify_recent_consistency(L1BatchNumber(4), 3).unwrap();
+    tree.save();
+
+    tree.verify_recent_consistency(L1BatchNumber(4), 3).unwrap();
+    tree.verify_recent_consistency(L1BatchNumber(2), 100)
+        .unwrap();
+    tree.verify_recent_consistency(L1BatchNumber(4), 0).unwrap();
+    let err = tree
+        .verify_recent_consistency(L1BatchNumber(5), 2)
+        .unwrap_err();
+    assert_eq!(err.version, 5);
+    assert!(err.description.contains("does not exist"), "{err}");
+}
+
+#[test_casing(3, [1, 7, 100])]
+fn processing_genesis_batch_in_chunks(chunk_size: usize) {
+    let mut logs = gen_storage_logs();
+    // Add an overwrite spanning chunks.
+    logs.push(TreeInstruction::write(
+        logs[0].key(),
+        1,
+        H256::repeat_byte(0x23),
+    ));
+    let expected_output = ZkSyncTree::process_genesis_batch(&logs);
+    let output = ZkSyncTree::process_genesis_batch_chunked(&logs, chunk_size);
+    assert_eq!(output, expected_output);
+}
+
+#[test_casing(3, [1, 2, 4])]
+fn processing_genesis_batch_in_parallel(thread_count: usize) {
+    let logs = gen_storage_logs();
+    let expected_output = ZkSyncTree::process_genesis_batch(&logs);
+    let output = ZkSyncTree::process_genesis_batch_parallel(&logs, thread_count);
+    assert_eq!(output, expected_output);
+}
+
+#[test]
+fn processing_genesis_batch_twice() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let logs = gen_storage_logs();
+    let expected_output = ZkSyncTree::process_genesis_batch(&logs);
+
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let metadata = tree.process_genesis_batch_persisted(&logs).unwrap();
+    assert_eq!(metadata.root_hash, expected_output.root_hash);
+    // Genesis must be detected even if it's not saved yet.
+    let err = tree.process_genesis_batch_persisted(&logs).unwrap_err();
+    assert_eq!(err.version_count, 1);
+    tree.save();
+    drop(tree);
+
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let err = tree.process_genesis_batch_persisted(&logs).unwrap_err();
+    assert_eq!(err.version_count, 1);
+    assert_eq!(tree.root_hash(), expected_output.root_hash);
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(1));
+}
+
+#[test_casing(2, [ZkSyncTree::DEFAULT_THREAD_POOL_MIN_BATCH_SIZE, 100])]
+fn basic_workflow_multiblock(thread_pool_min_batch_size: usize) {
     let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
     let logs = gen_storage_logs();
     let blocks = logs.chunks(9);
@@ -80,6 +222,7 @@ fn basic_workflow_multiblock() {
         let db = RocksDB::new(temp_dir.as_ref()).unwrap();
         let mut tree = ZkSyncTree::new_lightweight(db.into());
         tree.use_dedicated_thread_pool(2);
+        tree.set_thread_pool_min_batch_size(thread_pool_min_batch_size);
         for block in blocks {
             tree.process_l1_batch(block);
         }
@@ -113,6 +256,7 @@ fn filtering_out_no_op_writes() {
     // All writes are no-op updates and thus must be filtered out.
     let new_metadata = tree.process_l1_batch(&logs);
     assert_eq!(new_metadata.root_hash, root_hash);
+    assert_eq!(new_metadata.noop_writes, logs.len());
     let merkle_paths = new_metadata.witness.unwrap().into_merkle_paths();
     assert_eq!(merkle_paths.len(), 0);
 
@@ -135,6 +279,254 @@ fn filtering_out_no_op_writes() {
     }
 }
 
+#[test]
+fn previewing_filtered_root() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    let (first_logs, second_logs) = logs.split_at(50);
+    tree.process_l1_batch(first_logs);
+    // Unsaved changes must be taken into account.
+    let root_hash = tree.root_hash();
+
+    let preview_root_hash = tree.preview_filtered_root(second_logs, |_| true);
+    assert_eq!(tree.root_hash(), root_hash);
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(1));
+    assert_eq!(
+        tree.preview_filtered_root(second_logs, |_| false),
+        root_hash
+    );
+
+    let address = *second_logs[0].key().address();
+    let is_filtered_in =
+        |instruction: &TreeInstruction<StorageKey>| *instruction.key().address() == address;
+    let filtered_root_hash = tree.preview_filtered_root(second_logs, is_filtered_in);
+    assert_ne!(filtered_root_hash, root_hash);
+    assert_ne!(filtered_root_hash, preview_root_hash);
+
+    let filtered_logs: Vec<_> = second_logs
+        .iter()
+        .filter(|&instruction| is_filtered_in(instruction))
+        .copied()
+        .collect();
+    tree.save();
+    let metadata = tree.process_l1_batch(&filtered_logs);
+    assert_eq!(metadata.root_hash, filtered_root_hash);
+    tree.revert_logs(L1BatchNumber(0));
+    let metadata = tree.process_l1_batch(second_logs);
+    assert_eq!(metadata.root_hash, preview_root_hash);
+}
+
+#[test]
+fn peeking_root_hash() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new(db.into());
+    let logs = gen_storage_logs();
+    let (first_logs, second_logs) = logs.split_at(50);
+    assert_eq!(
+        tree.peek_root_hash(&[]),
+        ZkSyncTree::process_genesis_batch(&[]).root_hash
+    );
+
+    let peeked_root_hash = tree.peek_root_hash(first_logs);
+    assert!(tree.is_empty());
+    let metadata = tree.process_l1_batch(first_logs);
+    assert_eq!(metadata.root_hash, peeked_root_hash);
+
+    // Unsaved changes must be taken into account.
+    let peeked_root_hash = tree.peek_root_hash(second_logs);
+    assert_eq!(tree.root_hash(), metadata.root_hash);
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(1));
+    tree.save();
+    let metadata = tree.process_l1_batch(second_logs);
+    assert_eq!(metadata.root_hash, peeked_root_hash);
+}
+
+#[test]
+fn deferring_saves_based_on_latency() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let latency_signal = LatencySignal::default();
+    latency_signal.set(Duration::from_secs(1));
+    // Without a policy, saves are never deferred.
+    assert!(!tree.should_defer_save(&latency_signal));
+
+    tree.set_save_deferral_policy(LatencyThresholdPolicy {
+        latency_threshold: Duration::from_millis(100),
+        max_unsaved_batches: 2,
+    });
+    // There's nothing to save yet.
+    assert!(!tree.should_defer_save(&latency_signal));
+
+    let logs = gen_storage_logs();
+    let mut blocks = logs.chunks(10);
+    tree.process_l1_batch(blocks.next().unwrap());
+    assert!(tree.should_defer_save(&latency_signal));
+    latency_signal.set(Duration::from_millis(10));
+    assert!(!tree.should_defer_save(&latency_signal));
+
+    latency_signal.set(Duration::from_secs(1));
+    tree.process_l1_batch(blocks.next().unwrap());
+    // Too many unsaved batches.
+    assert!(!tree.should_defer_save(&latency_signal));
+    tree.save();
+    assert!(!tree.should_defer_save(&latency_signal));
+}
+
+#[test]
+fn estimating_memory_usage() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    assert_eq!(tree.estimated_memory_usage(), 0);
+
+    let logs = gen_storage_logs();
+    tree.process_l1_batch(&logs[..20]);
+    let usage_after_first_batch = tree.estimated_memory_usage();
+    assert!(usage_after_first_batch > 0);
+    tree.process_l1_batch(&logs[20..]);
+    let usage_after_second_batch = tree.estimated_memory_usage();
+    assert!(
+        usage_after_second_batch > usage_after_first_batch,
+        "{usage_after_second_batch} <= {usage_after_first_batch}"
+    );
+
+    tree.save();
+    assert_eq!(tree.estimated_memory_usage(), 0);
+}
+
+#[test]
+fn limiting_witness_size() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new(db.into());
+    let logs = gen_storage_logs();
+    tree.process_l1_batch(&logs[..50]);
+    tree.set_max_witness_bytes(4_096);
+
+    let root_hash = tree.try_process_l1_batch(&logs[50..52]).unwrap().root_hash;
+    let err = tree.try_process_l1_batch(&logs[52..]).unwrap_err();
+    let ProcessL1BatchError::WitnessTooLarge(err) = err else {
+        panic!("Unexpected error: {err:?}");
+    };
+    assert!(err.size > 4_096, "{err}");
+    assert_eq!(err.max_size, 4_096);
+    // The batch must be reverted.
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+
+    // `process_l1_batch()` ignores the limit.
+    let metadata = tree.process_l1_batch(&logs[52..]);
+    assert!(metadata.witness.is_some());
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(3));
+    assert_ne!(metadata.root_hash, root_hash);
+    tree.save();
+    assert_eq!(tree.root_hash(), metadata.root_hash);
+}
+
+#[test]
+fn creating_tree_with_custom_hasher() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    // The no-op hasher returns zero hashes for all operations, including empty subtree hashes.
+    let mut tree = ZkSyncTree::new_with_hasher(db.into(), ());
+    let logs = gen_storage_logs();
+    let metadata = tree.process_l1_batch(&logs);
+    assert_eq!(metadata.root_hash, H256::zero());
+    let merkle_paths = metadata.witness.unwrap().into_merkle_paths();
+    assert_eq!(merkle_paths.len(), logs.len());
+    for merkle_path in merkle_paths {
+        assert!(merkle_path.merkle_paths.iter().all(|hash| *hash == [0; 32]));
+    }
+    tree.save();
+
+    let reader = tree.reader();
+    assert_eq!(reader.root_hash(), H256::zero());
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    let entries = reader
+        .entries_with_proofs(L1BatchNumber(0), &keys[..3])
+        .unwrap();
+    for entry in entries {
+        assert!(entry.merkle_path.iter().all(H256::is_zero));
+    }
+}
+
+#[test]
+fn limiting_pending_versions() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    tree.set_max_pending_versions(2);
+    let logs = gen_storage_logs();
+    let mut blocks = logs.chunks(10);
+
+    tree.process_l1_batch(blocks.next().unwrap());
+    tree.try_process_l1_batch(blocks.next().unwrap()).unwrap();
+    let root_hash = tree.root_hash();
+    let err = tree
+        .try_process_l1_batch(blocks.next().unwrap())
+        .unwrap_err();
+    let ProcessL1BatchError::PendingLimitExceeded(err) = err else {
+        panic!("Unexpected error: {err:?}");
+    };
+    assert_eq!(err.pending_version_count, 2);
+    assert_eq!(err.max_pending_versions, 2);
+    assert_eq!(tree.root_hash(), root_hash);
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.reader().next_l1_batch_number(), L1BatchNumber(0));
+
+    // `process_l1_batch()` should save the tree.
+    let metadata = tree.process_l1_batch(blocks.next().unwrap());
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(3));
+    assert_eq!(tree.reader().next_l1_batch_number(), L1BatchNumber(2));
+    tree.save();
+    assert_eq!(tree.reader().root_hash(), metadata.root_hash);
+}
+
+#[test]
+fn subscribing_to_batch_events() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new(db.into());
+    let mut events = tree.subscribe_batches();
+    let mut logs = gen_storage_logs();
+    let metadata = tree.process_l1_batch(&logs[..60]);
+
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.l1_batch_number, L1BatchNumber(0));
+    assert_eq!(event.root_hash, metadata.root_hash);
+    assert_eq!((event.initial_writes, event.repeated_writes), (60, 0));
+    assert_matches!(events.try_recv(), Err(TryRecvError::Empty));
+
+    // Update some existing entries, perform no-op updates and insert new entries.
+    for log in logs.iter_mut().take(10) {
+        let TreeInstruction::Write(entry) = log else {
+            unreachable!("Unexpected instruction: {log:?}");
+        };
+        entry.value = H256::repeat_byte(0xff);
+    }
+    let metadata = tree.process_l1_batch(&logs[..80]);
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.l1_batch_number, L1BatchNumber(1));
+    assert_eq!(event.root_hash, metadata.root_hash);
+    assert_eq!((event.initial_writes, event.repeated_writes), (20, 10));
+
+    // Check that slow subscribers lag.
+    let mut lagging_events = tree.subscribe_batches();
+    for _ in 0..=ZkSyncTree::BATCH_EVENTS_CAPACITY {
+        tree.process_l1_batch(&[]);
+    }
+    assert_matches!(lagging_events.try_recv(), Err(TryRecvError::Lagged(1)));
+    let event = lagging_events.try_recv().unwrap();
+    assert_eq!(event.l1_batch_number, L1BatchNumber(3));
+}
+
 #[test]
 fn revert_blocks() {
     let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
@@ -217,72 +609,2063 @@ fn revert_blocks() {
         tree.save();
     }
 
-    // Revert two more blocks second time; the result should be the same
-    let storage = RocksDB::new(temp_dir.as_ref()).unwrap();
-    {
-        let mut tree = ZkSyncTree::new_lightweight(storage.into());
-        tree.revert_logs(L1BatchNumber(1));
-        assert_eq!(tree.root_hash(), tree_metadata[1].root_hash);
-        tree.save();
+    // Revert two more blocks second time; the result should be the same
+    let storage = RocksDB::new(temp_dir.as_ref()).unwrap();
+    {
+        let mut tree = ZkSyncTree::new_lightweight(storage.into());
+        tree.revert_logs(L1BatchNumber(1));
+        assert_eq!(tree.root_hash(), tree_metadata[1].root_hash);
+        tree.save();
+    }
+
+    // Reapply one of the reverted logs
+    let storage = RocksDB::new(temp_dir.as_ref()).unwrap();
+    {
+        let storage_log = mirror_logs.get(3 * block_size).unwrap();
+        let mut tree = ZkSyncTree::new_lightweight(storage.into());
+        tree.process_l1_batch(slice::from_ref(storage_log));
+        tree.save();
+    }
+
+    // check saved block number
+    let storage = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let tree = ZkSyncTree::new_lightweight(storage.into());
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(3));
+}
+
+#[test]
+fn reverting_tree_with_diff() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let mut logs = gen_storage_logs();
+    let root_hash = tree.process_l1_batch(&logs[..50]).root_hash;
+    tree.process_l1_batch(&logs[50..]);
+    tree.save();
+
+    // Add repeated writes and a no-op update, which are not saved.
+    for log in &mut logs[..3] {
+        let TreeInstruction::Write(entry) = log else {
+            unreachable!("Unexpected instruction: {log:?}");
+        };
+        entry.value = H256::repeat_byte(0xff);
+    }
+    tree.process_l1_batch(&logs[..3]);
+    tree.process_l1_batch(&logs[10..11]);
+
+    let changed_keys = tree.revert_logs_with_diff(L1BatchNumber(0));
+    let expected_keys: Vec<_> = logs[..3]
+        .iter()
+        .chain(&logs[50..])
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    assert_eq!(changed_keys, expected_keys);
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(1));
+    assert_eq!(tree.root_hash(), root_hash);
+
+    let changed_keys = tree.revert_logs_with_diff(L1BatchNumber(0));
+    assert!(changed_keys.is_empty());
+}
+
+#[test]
+fn auditing_orphaned_nodes() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    for block in logs.chunks(20) {
+        tree.process_l1_batch(block);
+    }
+    tree.save();
+
+    let report = tree.reader().audit_orphans();
+    assert!(report.node_count > 100, "{report:?}");
+    assert_eq!(report.orphan_count, 0);
+    assert_eq!(report.orphan_byte_size, 0);
+
+    // Reverting the tree leaves nodes for the reverted versions in the database.
+    tree.revert_logs(L1BatchNumber(2));
+    tree.save();
+    let new_report = tree.reader().audit_orphans();
+    assert_eq!(new_report.node_count, report.node_count);
+    assert!(new_report.orphan_count > 0, "{new_report:?}");
+    assert!(new_report.orphan_byte_size > 0, "{new_report:?}");
+}
+
+#[test]
+fn reverting_tree_to_root_hash() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    let root_hashes: Vec<_> = logs
+        .chunks(20)
+        .map(|block| tree.process_l1_batch(block).root_hash)
+        .collect();
+    tree.save();
+
+    let err = tree.revert_to_root(H256::repeat_byte(0xff)).unwrap_err();
+    assert_eq!(err.root_hash, H256::repeat_byte(0xff));
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(5));
+    assert_eq!(tree.root_hash(), root_hashes[4]);
+
+    let l1_batch_number = tree.revert_to_root(root_hashes[2]).unwrap();
+    assert_eq!(l1_batch_number, L1BatchNumber(2));
+    tree.save();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(3));
+    assert_eq!(tree.root_hash(), root_hashes[2]);
+
+    // Reverted versions cannot be reverted to.
+    tree.revert_to_root(root_hashes[3]).unwrap_err();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(3));
+}
+
+#[test]
+fn saving_tree_in_background() {
+    let logs = gen_storage_logs();
+    let overwrites: Vec<_> = logs
+        .iter()
+        .enumerate()
+        .map(|(i, instr)| TreeInstruction::write(instr.key(), i as u64 + 1, H256::zero()))
+        .collect();
+    let all_batches: Vec<_> = logs.chunks(20).chain(overwrites.chunks(50)).collect();
+
+    let reference_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let reference_db = RocksDB::new(reference_dir.as_ref()).unwrap();
+    let mut reference_tree = ZkSyncTree::new_lightweight(reference_db.into());
+    let root_hashes: Vec<_> = all_batches
+        .iter()
+        .map(|batch| reference_tree.process_l1_batch(batch).root_hash)
+        .collect();
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let reader = tree.reader();
+    tree.finish_save().unwrap(); // no-op
+    for batch in &all_batches[..5] {
+        tree.process_l1_batch(batch);
+    }
+    tree.begin_save();
+
+    // Changes being saved must be visible to the tree.
+    for (i, batch) in all_batches[5..].iter().enumerate() {
+        let output = tree.process_l1_batch(batch);
+        assert_eq!(output.root_hash, root_hashes[5 + i]);
+    }
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(7));
+
+    tree.finish_save().unwrap();
+    assert_eq!(reader.next_l1_batch_number(), L1BatchNumber(5));
+    assert_eq!(reader.root_hash(), root_hashes[4]);
+    tree.save();
+    assert_eq!(reader.next_l1_batch_number(), L1BatchNumber(7));
+    assert_eq!(reader.root_hash(), root_hashes[6]);
+    assert_eq!(tree.root_hash(), root_hashes[6]);
+
+    // Check that the saved tree can be reverted to a version saved in background.
+    tree.revert_logs(L1BatchNumber(4));
+    assert_eq!(tree.root_hash(), root_hashes[4]);
+}
+
+#[tokio::test]
+async fn saving_tree_asynchronously() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let reader = tree.reader();
+    tree.save_async().await.unwrap(); // no-op
+
+    let logs = gen_storage_logs();
+    let root_hashes: Vec<_> = logs
+        .chunks(20)
+        .map(|batch| tree.process_l1_batch(batch).root_hash)
+        .collect();
+    tree.begin_save();
+    let root_hash = tree.process_l1_batch(&logs[..10]).root_hash;
+    tree.save_async().await.unwrap();
+    assert_eq!(reader.next_l1_batch_number(), L1BatchNumber(6));
+    assert_eq!(reader.root_hash(), root_hash);
+    assert_eq!(
+        reader.root_exists(root_hashes[2]).unwrap(),
+        Some(L1BatchNumber(2))
+    );
+}
+
+#[test]
+fn reader_with_concurrency_limit() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    tree.process_l1_batch(&logs);
+    tree.save();
+
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    let reader = tree.reader();
+    let expected_entries = reader.entries_with_proofs(L1BatchNumber(0), &keys).unwrap();
+    let expected_entries: Vec<_> = expected_entries
+        .into_iter()
+        .map(|entry| (entry.base, entry.merkle_path))
+        .collect();
+    let limited_reader = reader.with_concurrency_limit(2);
+
+    thread::scope(|scope| {
+        for chunk in keys.chunks(10) {
+            let reader = limited_reader.clone();
+            let expected_entries = &expected_entries;
+            scope.spawn(move || {
+                for _ in 0..5 {
+                    let entries = reader.entries_with_proofs(L1BatchNumber(0), chunk).unwrap();
+                    for entry in entries {
+                        assert!(expected_entries.contains(&(entry.base, entry.merkle_path)));
+                    }
+                }
+            });
+        }
+    });
+
+    // Requests from a `rayon` thread pool must not deadlock, even if they exceed the limit.
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(4)
+        .build()
+        .unwrap();
+    let limited_reader = limited_reader.with_concurrency_limit(1);
+    let entries: Vec<_> = pool.install(|| {
+        keys.par_iter()
+            .map(|&key| {
+                limited_reader
+                    .entries_with_proofs(L1BatchNumber(0), &[key])
+                    .unwrap()
+            })
+            .flatten()
+            .map(|entry| (entry.base, entry.merkle_path))
+            .collect()
+    });
+    assert_eq!(entries, expected_entries);
+}
+
+#[test]
+fn concurrency_limit_with_dedicated_thread_pool() {
+    let thread_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(2)
+        .build()
+        .unwrap();
+    let thread_pool = Arc::new(thread_pool);
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new(db.into());
+    tree.set_thread_pool(thread_pool.clone());
+    tree.set_thread_pool_min_batch_size(0);
+    let logs = gen_storage_logs();
+    tree.process_l1_batch(&logs[..50]);
+    tree.save();
+
+    let leaves: Vec<_> = logs[..50]
+        .iter()
+        .map(|instr| match instr {
+            TreeInstruction::Write(entry) => {
+                (ZkSyncTree::hash_storage_key(&entry.key), entry.value)
+            }
+            TreeInstruction::Read(_) => unreachable!(),
+        })
+        .collect();
+    let reader = tree.reader().with_concurrency_limit(1);
+
+    thread::scope(|scope| {
+        for i in 0..4 {
+            let reader = reader.clone();
+            let (leaves, thread_pool) = (&leaves, &thread_pool);
+            scope.spawn(move || {
+                for _ in 0..5 {
+                    // Requests from within the pool are not limited, while other requests hold a permit
+                    // while waiting for the pool; neither must deadlock.
+                    let results = if i % 2 == 0 {
+                        thread_pool
+                            .install(|| reader.verify_leaves_in_root(L1BatchNumber(0), leaves))
+                    } else {
+                        reader.verify_leaves_in_root(L1BatchNumber(0), leaves)
+                    };
+                    assert!(results.unwrap().into_iter().all(|is_valid| is_valid));
+                }
+            });
+        }
+
+        // Process more batches using the same pool in the meantime.
+        for chunk in logs[50..].chunks(10) {
+            tree.process_l1_batch(chunk);
+        }
+        tree.save();
+    });
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(6));
+}
+
+#[test]
+fn getting_proof_with_state() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    let root_hashes: Vec<_> = logs
+        .chunks(50)
+        .map(|batch| tree.process_l1_batch(batch).root_hash)
+        .collect();
+    tree.save();
+
+    let reader = tree.reader();
+    let key = ZkSyncTree::hash_storage_key(&logs[70].key());
+    let (entry, root_hash, leaf_count) = reader.proof_with_state(L1BatchNumber(1), key).unwrap();
+    assert_eq!(root_hash, root_hashes[1]);
+    assert_eq!(leaf_count, 100);
+    assert_eq!(entry.base.leaf_index, 71);
+    entry.verify(&Blake2Hasher, root_hash);
+
+    // The key is missing in the previous L1 batch.
+    let (entry, root_hash, leaf_count) = reader.proof_with_state(L1BatchNumber(0), key).unwrap();
+    assert_eq!(root_hash, root_hashes[0]);
+    assert_eq!(leaf_count, 50);
+    assert!(entry.base.is_empty());
+    entry.verify(&Blake2Hasher, root_hash);
+
+    let err = reader.proof_with_state(L1BatchNumber(2), key).unwrap_err();
+    assert_matches!(err, ProofWithStateError::NoVersion(err) if err.missing_version == 2);
+}
+
+#[test]
+fn getting_proof_with_state_for_corrupted_tree() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    let root_hash = tree.process_l1_batch(&logs).root_hash;
+    tree.save();
+    drop(tree);
+
+    // Mangle the value hash of a leaf.
+    let key = ZkSyncTree::hash_storage_key(&logs[0].key());
+    let mut key_bytes = [0_u8; 32];
+    key.to_big_endian(&mut key_bytes);
+    let mut raw_db = RocksDBWrapper::from(RocksDB::new(temp_dir.as_ref()).unwrap()).into_inner();
+    let cf = MerkleTreeColumnFamily::Tree;
+    let (leaf_key, mut leaf) = raw_db
+        .prefix_iterator_cf(cf, &[0; 8])
+        .find(|(_, value)| value.starts_with(&key_bytes))
+        .unwrap();
+    leaf[32] ^= 1;
+    let mut batch = raw_db.new_write_batch();
+    batch.put_cf(cf, &leaf_key, &leaf);
+    raw_db.write(batch).unwrap();
+
+    let reader = ZkSyncTree::new_lightweight(raw_db.into()).reader();
+    let err = reader.proof_with_state(L1BatchNumber(0), key).unwrap_err();
+    assert_matches!(
+        err,
+        ProofWithStateError::RootHashMismatch { expected, actual }
+            if expected == root_hash && actual != root_hash
+    );
+}
+
+#[test_casing(2, [TreeWorkloadProfile::ReadOptimized, TreeWorkloadProfile::WriteOptimized])]
+fn creating_tree_with_profile(profile: TreeWorkloadProfile) {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let mut tree = ZkSyncTree::with_profile(temp_dir.as_ref(), profile).unwrap();
+    let logs = gen_storage_logs();
+    let metadata = tree.process_l1_batch(&logs);
+    assert!(metadata.witness.is_some());
+    tree.save();
+
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    let entries = tree
+        .reader()
+        .entries_with_proofs(L1BatchNumber(0), &keys)
+        .unwrap();
+    for entry in &entries {
+        entry.verify(&Blake2Hasher, metadata.root_hash);
+    }
+}
+
+#[test]
+fn verifying_proof_bundle_consistency() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    let root_hashes: Vec<_> = logs
+        .chunks(50)
+        .map(|batch| tree.process_l1_batch(batch).root_hash)
+        .collect();
+    tree.save();
+
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    let reader = tree.reader();
+    let mut proofs = reader
+        .entries_with_proofs(L1BatchNumber(1), &keys[..10])
+        .unwrap();
+    // Add a proof for a missing key.
+    proofs.extend(
+        reader
+            .entries_with_proofs(L1BatchNumber(1), &[Key::from(1)])
+            .unwrap(),
+    );
+    assert_eq!(verify_proofs_consistent(&proofs).unwrap(), root_hashes[1]);
+
+    let old_proofs = reader
+        .entries_with_proofs(L1BatchNumber(0), &keys[..10])
+        .unwrap();
+    proofs.insert(5, old_proofs[3].clone());
+    let err = verify_proofs_consistent(&proofs).unwrap_err();
+    assert_matches!(
+        err,
+        ProofInconsistencyError::RootMismatch { index: 5, expected, actual }
+            if expected == root_hashes[1] && actual == root_hashes[0]
+    );
+
+    let err = verify_proofs_consistent(&[]).unwrap_err();
+    assert_matches!(err, ProofInconsistencyError::Empty);
+}
+
+#[test_casing(4, ["full", "lazy", "lightweight", "lightweight_with_reads"])]
+fn counting_instructions_in_metadata(mode: &str) {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = match mode {
+        "full" => ZkSyncTree::new(db.into()),
+        "lazy" => {
+            let mut tree = ZkSyncTree::new(db.into());
+            tree.set_lazy_witnesses(true);
+            tree
+        }
+        "lightweight" => ZkSyncTree::new_lightweight(db.into()),
+        "lightweight_with_reads" => ZkSyncTree::new_lightweight_with_reads(db.into()),
+        _ => unreachable!(),
+    };
+    let logs = gen_storage_logs();
+    let metadata = tree.process_l1_batch(&logs[..50]);
+    assert_eq!(metadata.read_count, 0);
+    assert_eq!(metadata.total_writes(), 50);
+    assert_eq!(metadata.noop_writes, 0);
+
+    let mut batch: Vec<_> = logs[..20]
+        .iter()
+        .map(|instr| TreeInstruction::Read(instr.key()))
+        .collect();
+    batch.extend_from_slice(&logs[40..70]);
+    let metadata = tree.process_l1_batch(&batch);
+    assert_eq!(metadata.read_count, 20);
+    // No-op updates are counted as writes.
+    assert_eq!(metadata.total_writes(), 30);
+    assert_eq!(metadata.noop_writes, 10);
+}
+
+#[test_casing(2, [false, true])]
+fn processing_multiple_batches(use_thread_pool: bool) {
+    let logs = gen_storage_logs();
+    let batches: Vec<_> = logs.chunks(30).collect();
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new(db.into());
+    if use_thread_pool {
+        tree.use_dedicated_thread_pool(2);
+    }
+    let metadata = tree.process_l1_batches(&batches);
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(4));
+
+    let reference_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let reference_db = RocksDB::new(reference_dir.as_ref()).unwrap();
+    let mut reference_tree = ZkSyncTree::new(reference_db.into());
+    assert_eq!(metadata.len(), batches.len());
+    for (batch_metadata, batch) in metadata.iter().zip(&batches) {
+        let expected = reference_tree.process_l1_batch(batch);
+        assert_eq!(batch_metadata.root_hash, expected.root_hash);
+        assert_eq!(
+            batch_metadata.rollup_last_leaf_index,
+            expected.rollup_last_leaf_index
+        );
+        assert!(batch_metadata.witness.is_some());
+    }
+    assert_eq!(metadata.last().unwrap().rollup_last_leaf_index, 101);
+}
+
+#[test]
+fn sharing_thread_pool_among_trees() {
+    let thread_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(2)
+        .build()
+        .unwrap();
+    let thread_pool = Arc::new(thread_pool);
+    let logs = gen_storage_logs();
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new(db.into());
+    tree.set_thread_pool(thread_pool.clone());
+    tree.set_thread_pool_min_batch_size(0);
+    let other_temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let other_db = RocksDB::new(other_temp_dir.as_ref()).unwrap();
+    let mut other_tree = ZkSyncTree::new_lightweight(other_db.into());
+    other_tree.set_thread_pool(thread_pool.clone());
+    assert_eq!(Arc::strong_count(&thread_pool), 3);
+
+    let batches: Vec<_> = logs.chunks(30).collect();
+    let metadata = tree.process_l1_batches(&batches);
+    for (batch_metadata, batch) in metadata.iter().zip(&batches) {
+        let other_metadata = other_tree.process_l1_batch(batch);
+        assert_eq!(batch_metadata.root_hash, other_metadata.root_hash);
+        assert!(batch_metadata.witness.is_some());
+    }
+    tree.verify_consistency(L1BatchNumber(3));
+
+    drop((tree, other_tree));
+    assert_eq!(Arc::strong_count(&thread_pool), 1);
+}
+
+#[test]
+fn getting_historical_root_hashes() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let empty_root_hash = tree.process_l1_batch(&[]).root_hash;
+    let logs = gen_storage_logs();
+    let root_hashes: Vec<_> = logs
+        .chunks(50)
+        .map(|batch| tree.process_l1_batch(batch).root_hash)
+        .collect();
+    tree.save();
+
+    let reader = tree.reader();
+    assert_eq!(
+        reader.root_hash_at(L1BatchNumber(0)).unwrap(),
+        empty_root_hash
+    );
+    assert_eq!(empty_root_hash, Blake2Hasher.empty_tree_hash());
+    assert_eq!(
+        reader.root_hash_at(L1BatchNumber(1)).unwrap(),
+        root_hashes[0]
+    );
+    assert_eq!(
+        reader.root_hash_at(L1BatchNumber(2)).unwrap(),
+        root_hashes[1]
+    );
+    let err = reader.root_hash_at(L1BatchNumber(3)).unwrap_err();
+    assert_eq!(err.missing_version, 3);
+}
+
+#[test]
+fn hashing_storage_keys() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new(db.into());
+    let logs = gen_storage_logs();
+    let metadata = tree.process_l1_batch(&logs);
+    tree.save();
+
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    let merkle_paths = metadata.witness.unwrap().into_merkle_paths();
+    let hashed_keys_in_witness: Vec<_> = merkle_paths.map(|path| path.leaf_hashed_key).collect();
+    assert_eq!(hashed_keys_in_witness, keys);
+
+    let entries = tree
+        .reader()
+        .entries_with_proofs(L1BatchNumber(0), &keys)
+        .unwrap();
+    for (entry, log) in entries.iter().zip(&logs) {
+        let TreeInstruction::Write(expected_entry) = log else {
+            unreachable!("Unexpected instruction: {log:?}");
+        };
+        assert_eq!(entry.base.value, expected_entry.value);
+        assert_eq!(entry.base.leaf_index, expected_entry.leaf_index);
+    }
+}
+
+#[test]
+fn reset_tree() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let storage = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let logs = gen_storage_logs();
+    let mut tree = ZkSyncTree::new_lightweight(storage.into());
+    let empty_root_hash = tree.root_hash();
+
+    logs.chunks(5).fold(empty_root_hash, |hash, chunk| {
+        tree.process_l1_batch(chunk);
+        tree.reset();
+        assert_eq!(tree.root_hash(), hash);
+
+        tree.process_l1_batch(chunk);
+        tree.save();
+        tree.root_hash()
+    });
+}
+
+#[test]
+fn read_logs() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let mut logs = gen_storage_logs();
+    logs.truncate(5);
+
+    let write_metadata = {
+        let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+        let mut tree = ZkSyncTree::new_lightweight(db.into());
+        let metadata = tree.process_l1_batch(&logs);
+        tree.save();
+        metadata
+    };
+
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let read_logs: Vec<_> = logs
+        .into_iter()
+        .map(|instr| TreeInstruction::Read(instr.key()))
+        .collect();
+    let read_metadata = tree.process_l1_batch(&read_logs);
+
+    assert_eq!(read_metadata.root_hash, write_metadata.root_hash);
+}
+
+#[test]
+fn counting_writes() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let mut logs = gen_storage_logs();
+    tree.process_l1_batch(&logs[..60]);
+
+    // Update some existing entries, perform a no-op update and insert new entries.
+    for log in logs.iter_mut().take(10) {
+        let TreeInstruction::Write(entry) = log else {
+            unreachable!("Unexpected instruction: {log:?}");
+        };
+        entry.value = H256::repeat_byte(0xff);
+    }
+    tree.process_l1_batch(&logs[..80]);
+    tree.save();
+
+    let reader = tree.reader();
+    assert_eq!(reader.write_counts(L1BatchNumber(0)).unwrap(), (60, 0));
+    assert_eq!(reader.write_counts(L1BatchNumber(1)).unwrap(), (20, 10));
+    assert!(reader.write_counts(L1BatchNumber(2)).is_err());
+}
+
+#[test]
+fn proofs_against_root() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    let root_hashes: Vec<_> = logs
+        .chunks(25)
+        .map(|chunk| tree.process_l1_batch(chunk).root_hash)
+        .collect();
+    tree.save();
+
+    let reader = tree.reader();
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    for (i, &root_hash) in root_hashes.iter().enumerate() {
+        let l1_batch_number = L1BatchNumber(i as u32);
+        let expected_entries = reader.entries_with_proofs(l1_batch_number, &keys).unwrap();
+        let entries = reader
+            .entries_with_proofs_against_root(root_hash, &keys)
+            .unwrap();
+        assert_eq!(entries.len(), keys.len());
+        for (entry, expected_entry) in entries.iter().zip(&expected_entries) {
+            assert_eq!(entry.base, expected_entry.base);
+            assert_eq!(entry.merkle_path, expected_entry.merkle_path);
+            entry.verify(&Blake2Hasher, root_hash);
+        }
+    }
+
+    let err = reader
+        .entries_with_proofs_against_root(H256::repeat_byte(0xff), &keys)
+        .unwrap_err();
+    assert_eq!(err.root_hash, H256::repeat_byte(0xff));
+}
+
+#[test]
+fn latest_proofs() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    let err = tree.reader().latest_entries_with_proofs(&keys).unwrap_err();
+    assert_matches!(err, LatestEntriesError::EmptyTree(_));
+
+    let mut root_hash = H256::zero();
+    for chunk in logs.chunks(30) {
+        root_hash = tree.process_l1_batch(chunk).root_hash;
+    }
+    tree.save();
+
+    let (l1_batch_number, entries) = tree.reader().latest_entries_with_proofs(&keys).unwrap();
+    assert_eq!(l1_batch_number, L1BatchNumber(3));
+    assert_eq!(entries.len(), keys.len());
+    for (entry, key) in entries.iter().zip(&keys) {
+        assert_eq!(entry.base.key, *key);
+        assert!(!entry.base.is_empty());
+        entry.verify(&Blake2Hasher, root_hash);
+    }
+    drop(tree);
+
+    // Remove the latest root so that the latest version is persistently missing.
+    let mut raw_db = RocksDBWrapper::from(RocksDB::new(temp_dir.as_ref()).unwrap()).into_inner();
+    let mut root_key = 3_u64.to_be_bytes().to_vec();
+    root_key.push(0);
+    let mut batch = raw_db.new_write_batch();
+    batch.delete_cf(MerkleTreeColumnFamily::Tree, &root_key);
+    raw_db.write(batch).unwrap();
+
+    let reader = ZkSyncTree::new_lightweight(raw_db.into()).reader();
+    let err = reader.latest_entries_with_proofs(&keys).unwrap_err();
+    assert_matches!(err, LatestEntriesError::NoVersion(err) if err.missing_version == 3);
+}
+
+#[test]
+fn checking_root_existence() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    assert_eq!(tree.reader().root_exists(H256::zero()).unwrap(), None);
+
+    let logs = gen_storage_logs();
+    let root_hashes: Vec<_> = logs
+        .chunks(25)
+        .map(|chunk| tree.process_l1_batch(chunk).root_hash)
+        .collect();
+    // A batch with no-op updates only has the same root hash as the previous one.
+    tree.process_l1_batch(&logs[..10]);
+    tree.save();
+
+    let reader = tree.reader();
+    for (i, &root_hash) in root_hashes.iter().enumerate().take(3) {
+        let l1_batch_number = reader.root_exists(root_hash).unwrap();
+        assert_eq!(l1_batch_number, Some(L1BatchNumber(i as u32)));
+    }
+    let l1_batch_number = reader.root_exists(root_hashes[3]).unwrap();
+    assert_eq!(l1_batch_number, Some(L1BatchNumber(4)));
+    assert_eq!(reader.root_exists(H256::repeat_byte(0xff)).unwrap(), None);
+    drop((tree, reader));
+
+    // Prune all versions except for the latest one.
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut db = RocksDBWrapper::from(db);
+    MerkleTreePruner::new(&mut db, 1).0.run_once().unwrap();
+    let reader = ZkSyncTree::new_lightweight(db).reader();
+    assert_eq!(reader.root_exists(root_hashes[0]).unwrap(), None);
+    assert_eq!(
+        reader.root_exists(root_hashes[3]).unwrap(),
+        Some(L1BatchNumber(4))
+    );
+}
+
+#[test]
+fn exporting_and_importing_snapshot() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    for chunk in logs.chunks(30) {
+        tree.process_l1_batch(chunk);
+    }
+    tree.save();
+
+    let mut snapshot = vec![];
+    tree.reader()
+        .export_snapshot(L1BatchNumber(3), &mut snapshot)
+        .unwrap();
+    assert!(tree
+        .reader()
+        .export_snapshot(L1BatchNumber(4), &mut vec![])
+        .is_err());
+
+    let imported_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(imported_dir.as_ref()).unwrap();
+    ZkSyncTree::import_snapshot(db.into(), snapshot.as_slice()).unwrap();
+
+    let db = RocksDB::new(imported_dir.as_ref()).unwrap();
+    let imported_tree = ZkSyncTree::new_lightweight(db.into());
+    assert_eq!(imported_tree.root_hash(), tree.root_hash());
+    assert_eq!(imported_tree.next_l1_batch_number(), L1BatchNumber(4));
+    imported_tree.verify_consistency(L1BatchNumber(3));
+    drop(imported_tree);
+
+    // Importing into a non-empty database should fail.
+    let db = RocksDB::new(imported_dir.as_ref()).unwrap();
+    let err = ZkSyncTree::import_snapshot(db.into(), snapshot.as_slice()).unwrap_err();
+    assert_matches!(err, ImportError::NonEmptyDatabase);
+
+    // Check that corrupted snapshots are rejected.
+    let mut corrupted_snapshot = snapshot.clone();
+    *corrupted_snapshot.last_mut().unwrap() ^= 1;
+    let corrupted_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(corrupted_dir.as_ref()).unwrap();
+    let err = ZkSyncTree::import_snapshot(db.into(), corrupted_snapshot.as_slice()).unwrap_err();
+    assert_matches!(err, ImportError::RootHashMismatch { .. });
+
+    let truncated_snapshot = &snapshot[..snapshot.len() - 1];
+    let truncated_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(truncated_dir.as_ref()).unwrap();
+    let err = ZkSyncTree::import_snapshot(db.into(), truncated_snapshot).unwrap_err();
+    assert_matches!(err, ImportError::Io(_));
+}
+
+#[test]
+fn iterating_over_leaves() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    assert_eq!(tree.process_l1_batch(&[]).rollup_last_leaf_index, 1);
+    let logs = gen_storage_logs();
+    tree.process_l1_batch(&logs);
+    tree.save();
+
+    let mut expected_entries: Vec<_> = logs
+        .iter()
+        .enumerate()
+        .map(|(i, instr)| {
+            let key = ZkSyncTree::hash_storage_key(&instr.key());
+            TreeEntry::new(key, i as u64 + 1, H256::from_low_u64_be(i as u64))
+        })
+        .collect();
+    expected_entries.sort_unstable_by_key(|entry| entry.key);
+
+    let reader = tree.reader();
+    assert_eq!(reader.leaves(L1BatchNumber(0)).unwrap().count(), 0);
+    assert!(reader.leaves(L1BatchNumber(2)).is_err());
+    let mut leaves = reader.leaves(L1BatchNumber(1)).unwrap();
+    let mut entries: Vec<_> = leaves.by_ref().take(10).collect();
+
+    // Writing a new tree version concurrently must not influence iteration.
+    let updated_logs: Vec<_> = logs
+        .iter()
+        .map(|instr| {
+            let TreeInstruction::Write(entry) = instr else {
+                unreachable!();
+            };
+            TreeInstruction::Write(TreeEntry::new(
+                entry.key,
+                entry.leaf_index,
+                H256::repeat_byte(0xff),
+            ))
+        })
+        .collect();
+    tree.process_l1_batch(&updated_logs);
+    tree.save();
+
+    entries.extend(leaves);
+    assert_eq!(entries, expected_entries);
+}
+
+#[test]
+fn last_changed_version_for_key() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let mut logs = gen_storage_logs();
+    tree.process_l1_batch(&logs[..50]);
+    tree.process_l1_batch(&logs[50..]);
+    for (i, log) in logs.iter_mut().take(2).enumerate() {
+        let TreeInstruction::Write(entry) = log else {
+            unreachable!("Unexpected instruction: {log:?}");
+        };
+        entry.value = H256::repeat_byte(0xff);
+        tree.process_l1_batch(slice::from_ref(log));
+        if i == 0 {
+            // Add a no-op update, which shouldn't be considered a change.
+            tree.process_l1_batch(slice::from_ref(log));
+        }
+    }
+    tree.save();
+
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    let reader = tree.reader();
+    let last_changed_version = |key| reader.last_changed_version(key).unwrap();
+    assert_eq!(last_changed_version(keys[0]), Some(L1BatchNumber(2)));
+    assert_eq!(last_changed_version(keys[1]), Some(L1BatchNumber(4)));
+    assert_eq!(last_changed_version(keys[10]), Some(L1BatchNumber(0)));
+    assert_eq!(last_changed_version(keys[55]), Some(L1BatchNumber(1)));
+    assert_eq!(last_changed_version(Key::from(123)), None);
+    drop((tree, reader));
+
+    // Prune tree versions older than #3.
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut db = RocksDBWrapper::from(db);
+    MerkleTreePruner::new(&mut db, 1).0.run_once().unwrap();
+    let reader = ZkSyncTree::new_lightweight(db).reader();
+    assert_eq!(
+        reader.last_changed_version(keys[1]).unwrap(),
+        Some(L1BatchNumber(4))
+    );
+    let err = reader.last_changed_version(keys[0]).unwrap_err();
+    assert_eq!(err.missing_version, 2);
+    assert!(reader.last_changed_version(keys[10]).is_err());
+}
+
+#[test]
+fn reading_snapshots_during_save() {
+    const BATCH_SIZE: usize = 20;
+
+    let logs = gen_storage_logs();
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    let expected_root_hashes: Vec<_> = {
+        let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+        let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+        let mut tree = ZkSyncTree::new_lightweight(db.into());
+        logs.chunks(BATCH_SIZE)
+            .map(|chunk| tree.process_l1_batch(chunk).root_hash)
+            .collect()
+    };
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let empty_snapshot = tree.snapshot();
+    assert_eq!(empty_snapshot.l1_batch_number(), None);
+    assert_eq!(empty_snapshot.root_hash(), Blake2Hasher.empty_tree_hash());
+    assert_matches!(
+        empty_snapshot.entries_with_proofs(&keys),
+        Err(SnapshotError::EmptyTree(_))
+    );
+
+    let reader = tree.reader();
+    let is_done = AtomicBool::new(false);
+    thread::scope(|scope| {
+        let reader_task = scope.spawn(|| {
+            let mut last_l1_batch_number = None;
+            while !is_done.load(Ordering::Acquire) {
+                let snapshot = reader.snapshot();
+                let Some(l1_batch_number) = snapshot.l1_batch_number() else {
+                    continue;
+                };
+                assert!(last_l1_batch_number <= Some(l1_batch_number));
+                last_l1_batch_number = Some(l1_batch_number);
+
+                let root_hash = snapshot.root_hash();
+                assert_eq!(root_hash, expected_root_hashes[l1_batch_number.0 as usize]);
+                let entries = snapshot.entries_with_proofs(&keys).unwrap();
+                let written_key_count = (l1_batch_number.0 as usize + 1) * BATCH_SIZE;
+                for (i, entry) in entries.iter().enumerate() {
+                    // Keys from unsaved batches must not be visible in the snapshot.
+                    assert_eq!(entry.base.is_empty(), i >= written_key_count, "{entry:?}");
+                    entry.verify(&Blake2Hasher, root_hash);
+                }
+            }
+            last_l1_batch_number
+        });
+
+        for chunk in logs.chunks(BATCH_SIZE) {
+            tree.process_l1_batch(chunk);
+            tree.save();
+        }
+        is_done.store(true, Ordering::Release);
+        let last_l1_batch_number = reader_task.join().unwrap();
+        assert!(last_l1_batch_number.is_some());
+    });
+
+    // Check that a snapshot remains pinned to its version after a save.
+    let snapshot = tree.snapshot();
+    let l1_batch_number = snapshot.l1_batch_number().unwrap();
+    assert_eq!(l1_batch_number, L1BatchNumber(4));
+    let new_logs: Vec<_> = logs
+        .iter()
+        .map(|log| {
+            let TreeInstruction::Write(entry) = log else {
+                unreachable!("Unexpected instruction: {log:?}");
+            };
+            TreeInstruction::Write(TreeEntry {
+                value: H256::repeat_byte(0xff),
+                ..*entry
+            })
+        })
+        .collect();
+    tree.process_l1_batch(&new_logs);
+    tree.save();
+
+    assert_ne!(tree.snapshot().root_hash(), snapshot.root_hash());
+    assert_eq!(snapshot.l1_batch_number(), Some(l1_batch_number));
+    assert_eq!(snapshot.root_hash(), expected_root_hashes[4]);
+    let entries = snapshot.entries_with_proofs(&keys).unwrap();
+    for (entry, log) in entries.iter().zip(&logs) {
+        let TreeInstruction::Write(expected) = log else {
+            unreachable!("Unexpected instruction: {log:?}");
+        };
+        assert_eq!(entry.base.value, expected.value);
+        entry.verify(&Blake2Hasher, snapshot.root_hash());
+    }
+}
+
+#[test]
+fn reading_snapshots_after_revert() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    tree.process_l1_batch(&logs[..50]);
+    tree.process_l1_batch(&logs[50..]);
+    tree.save();
+    let snapshot = tree.snapshot();
+    assert_eq!(snapshot.l1_batch_number(), Some(L1BatchNumber(1)));
+
+    // Reverting the pinned version doesn't influence the snapshot until the version is overwritten.
+    tree.revert_logs(L1BatchNumber(0));
+    tree.save();
+    let entries = snapshot.entries_with_proofs(&keys).unwrap();
+    for entry in &entries {
+        assert!(!entry.base.is_empty(), "{entry:?}");
+        entry.verify(&Blake2Hasher, snapshot.root_hash());
+    }
+
+    tree.process_l1_batch(&logs[50..60]);
+    tree.save();
+    let err = snapshot.entries_with_proofs(&keys).unwrap_err();
+    let SnapshotError::VersionReplaced {
+        version,
+        expected,
+        actual,
+    } = err
+    else {
+        panic!("Unexpected error: {err:?}");
+    };
+    assert_eq!(version, 1);
+    assert_eq!(expected, snapshot.root_hash());
+    assert_eq!(actual, tree.root_hash());
+
+    // A new snapshot observes the new version.
+    let new_snapshot = tree.snapshot();
+    assert_eq!(new_snapshot.root_hash(), tree.root_hash());
+    let entries = new_snapshot.entries_with_proofs(&keys).unwrap();
+    for (i, entry) in entries.iter().enumerate() {
+        assert_eq!(entry.base.is_empty(), i >= 60, "{entry:?}");
+        entry.verify(&Blake2Hasher, new_snapshot.root_hash());
+    }
+}
+
+#[test]
+fn reading_values() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let mut logs = gen_storage_logs();
+    tree.process_l1_batch(&logs[..50]);
+    let TreeInstruction::Write(updated_entry) = &mut logs[10] else {
+        unreachable!("Unexpected instruction");
+    };
+    updated_entry.value = H256::repeat_byte(0xff);
+    tree.process_l1_batch(&logs[10..]);
+    tree.save();
+
+    let reader = tree.reader();
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    let entries = reader.entries_with_proofs(L1BatchNumber(0), &keys).unwrap();
+    for (key, entry) in keys.iter().zip(&entries) {
+        let value = reader.value(L1BatchNumber(0), *key).unwrap();
+        let expected_value = (!entry.base.is_empty()).then_some(entry.base.value);
+        assert_eq!(value, expected_value);
+    }
+    assert_eq!(
+        reader.value(L1BatchNumber(0), keys[10]).unwrap(),
+        Some(H256::from_low_u64_be(10))
+    );
+    assert_eq!(reader.value(L1BatchNumber(0), keys[60]).unwrap(), None);
+    assert_eq!(
+        reader.value(L1BatchNumber(1), keys[10]).unwrap(),
+        Some(H256::repeat_byte(0xff))
+    );
+    assert_eq!(
+        reader.value(L1BatchNumber(1), keys[60]).unwrap(),
+        Some(H256::from_low_u64_be(60))
+    );
+    assert_eq!(
+        reader.value(L1BatchNumber(1), Key::from(123)).unwrap(),
+        None
+    );
+
+    let err = reader.value(L1BatchNumber(2), keys[0]).unwrap_err();
+    assert_eq!(err.missing_version, 2);
+}
+
+#[test]
+fn getting_proofs_at_or_before_version() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    let mut root_hashes = vec![];
+    for chunk in logs.chunks(20) {
+        root_hashes.push(tree.process_l1_batch(chunk).root_hash);
+    }
+    tree.save();
+
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    let reader = tree.reader();
+    let (l1_batch_number, entries) = reader
+        .entries_with_proofs_at_or_before(L1BatchNumber(2), &keys)
+        .unwrap();
+    assert_eq!(l1_batch_number, L1BatchNumber(2));
+    for (i, entry) in entries.iter().enumerate() {
+        assert_eq!(entry.base.is_empty(), i >= 60, "{i}");
+        entry.verify(&Blake2Hasher, root_hashes[2]);
+    }
+
+    let (l1_batch_number, entries) = reader
+        .entries_with_proofs_at_or_before(L1BatchNumber(100), &keys[..3])
+        .unwrap();
+    assert_eq!(l1_batch_number, L1BatchNumber(4));
+    for entry in &entries {
+        entry.verify(&Blake2Hasher, root_hashes[4]);
+    }
+    drop((tree, reader));
+
+    // Prune tree versions older than #3.
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut db = RocksDBWrapper::from(db);
+    MerkleTreePruner::new(&mut db, 1).0.run_once().unwrap();
+    let reader = ZkSyncTree::new_lightweight(db).reader();
+    let (l1_batch_number, _) = reader
+        .entries_with_proofs_at_or_before(L1BatchNumber(3), &keys[..3])
+        .unwrap();
+    assert_eq!(l1_batch_number, L1BatchNumber(3));
+    let err = reader
+        .entries_with_proofs_at_or_before(L1BatchNumber(2), &keys[..3])
+        .unwrap_err();
+    assert_eq!(err.missing_version, 2);
+    assert!(err.is_pruned());
+}
+
+#[test]
+fn getting_proofs_at_multiple_versions() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    let mut root_hashes = vec![];
+    for chunk in logs.chunks(20) {
+        root_hashes.push(tree.process_l1_batch(chunk).root_hash);
+    }
+    tree.save();
+
+    let keys: Vec<_> = logs
+        .iter()
+        .step_by(10)
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    let reader = tree.reader();
+    let l1_batch_numbers = [3, 0, 3, 4].map(L1BatchNumber);
+    let entries = reader
+        .entries_with_proofs_multi(&l1_batch_numbers, &keys)
+        .unwrap();
+    assert_eq!(entries.len(), l1_batch_numbers.len());
+    for (l1_batch_number, entries) in l1_batch_numbers.into_iter().zip(entries) {
+        let expected_entries = reader.entries_with_proofs(l1_batch_number, &keys).unwrap();
+        assert_eq!(entries.len(), keys.len());
+        for (entry, expected_entry) in entries.iter().zip(&expected_entries) {
+            assert_eq!(entry.base, expected_entry.base);
+            assert_eq!(entry.merkle_path, expected_entry.merkle_path);
+            entry.verify(&Blake2Hasher, root_hashes[l1_batch_number.0 as usize]);
+        }
+    }
+
+    let err = reader
+        .entries_with_proofs_multi(&[L1BatchNumber(1), L1BatchNumber(5)], &keys)
+        .unwrap_err();
+    assert_eq!(err.missing_version, 5);
+}
+
+#[test]
+fn reading_entries() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    tree.process_l1_batch(&logs[..50]);
+    tree.save();
+
+    let reader = tree.reader();
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    assert_eq!(
+        reader.entry(L1BatchNumber(0), keys[10]).unwrap(),
+        Some(TreeEntry::new(keys[10], 11, H256::from_low_u64_be(10)))
+    );
+    assert_eq!(reader.entry(L1BatchNumber(0), keys[60]).unwrap(), None);
+
+    let entries = reader.entries(L1BatchNumber(0), &keys).unwrap();
+    assert_eq!(entries.len(), keys.len());
+    for (i, (key, entry)) in keys.iter().zip(&entries).enumerate() {
+        let expected_entry =
+            (i < 50).then(|| TreeEntry::new(*key, i as u64 + 1, H256::from_low_u64_be(i as u64)));
+        assert_eq!(*entry, expected_entry);
+    }
+
+    let err = reader.entry(L1BatchNumber(1), keys[0]).unwrap_err();
+    assert_eq!(err.missing_version, 1);
+}
+
+#[test]
+fn reading_leaf_indices() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    assert_eq!(tree.reader().leaf_index(keys[0]).unwrap(), None);
+
+    tree.process_l1_batch(&logs[..50]);
+    tree.process_l1_batch(&logs[30..70]);
+    tree.save();
+
+    let reader = tree.reader();
+    for (i, &key) in keys.iter().enumerate() {
+        let expected_index = (i < 70).then_some(i as u64 + 1);
+        assert_eq!(reader.leaf_index(key).unwrap(), expected_index, "{i}");
+    }
+}
+
+#[test]
+fn resolving_enumeration_indices_for_storage_keys() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    tree.process_l1_batch(&logs[..50]);
+    tree.save();
+
+    let reader = tree.reader();
+    let keys: Vec<_> = logs.iter().rev().map(TreeInstruction::key).collect();
+    let indices = reader
+        .enumeration_indices_for_storage_keys(L1BatchNumber(0), &keys)
+        .unwrap();
+    assert_eq!(indices.len(), keys.len());
+    for (i, index) in indices.into_iter().rev().enumerate() {
+        let expected_index = (i < 50).then_some(i as u64 + 1);
+        assert_eq!(index, expected_index, "{i}");
+    }
+
+    let indices = reader
+        .enumeration_indices_for_storage_keys(L1BatchNumber(0), &[])
+        .unwrap();
+    assert!(indices.is_empty());
+    let err = reader
+        .enumeration_indices_for_storage_keys(L1BatchNumber(1), &keys)
+        .unwrap_err();
+    assert_eq!(err.missing_version, 1);
+}
+
+#[test]
+fn encoding_entries_with_proofs() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    let metadata = tree.process_l1_batch(&logs[..50]);
+    tree.save();
+
+    let reader = tree.reader();
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    let bytes = reader
+        .entries_with_proofs_encoded(L1BatchNumber(0), &keys)
+        .unwrap();
+    let entries = decode_entries_with_proofs(&bytes).unwrap();
+    let expected_entries = reader.entries_with_proofs(L1BatchNumber(0), &keys).unwrap();
+    assert_eq!(entries.len(), expected_entries.len());
+    for (entry, expected) in entries.iter().zip(&expected_entries) {
+        assert_eq!(entry.base, expected.base);
+        assert_eq!(entry.merkle_path, expected.merkle_path);
+        entry.verify(&Blake2Hasher, metadata.root_hash);
+    }
+}
+
+#[test]
+fn witness_without_paths() {
+    let logs = gen_storage_logs();
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new(db.into());
+    let expected_witness = tree.process_l1_batch(&logs).witness.unwrap();
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new(db.into());
+    tree.set_witness_without_paths(true);
+    let witness = tree.process_l1_batch(&logs).witness.unwrap();
+
+    let expected_size = serde_json::to_vec(&expected_witness).unwrap().len();
+    let size = serde_json::to_vec(&witness).unwrap().len();
+    assert!(size * 10 < expected_size, "{size} vs {expected_size}");
+
+    assert_eq!(
+        witness.next_enumeration_index(),
+        expected_witness.next_enumeration_index()
+    );
+    let merkle_paths: Vec<_> = witness.into_merkle_paths().collect();
+    let expected_merkle_paths: Vec<_> = expected_witness.into_merkle_paths().collect();
+    assert_eq!(merkle_paths.len(), expected_merkle_paths.len());
+    for (log, mut expected_log) in merkle_paths.into_iter().zip(expected_merkle_paths) {
+        assert!(log.merkle_paths.is_empty());
+        expected_log.merkle_paths.clear();
+        assert_eq!(log, expected_log);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct RecordingWitnessSink(Arc<Mutex<Vec<(L1BatchNumber, PrepareBasicCircuitsJob)>>>);
+
+impl WitnessSink for RecordingWitnessSink {
+    fn consume(&mut self, l1_batch_number: L1BatchNumber, chunk: PrepareBasicCircuitsJob) {
+        self.0.lock().unwrap().push((l1_batch_number, chunk));
+    }
+}
+
+#[test]
+fn collecting_witness_stats() {
+    let mut logs = gen_storage_logs();
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new(db.into());
+    let empty_metadata = tree.process_l1_batch(&[]);
+    assert_eq!(empty_metadata.witness_stats, Some(WitnessStats::default()));
+
+    // Add some no-op updates, which must not be counted.
+    logs.extend_from_within(..10);
+    let metadata = tree.process_l1_batch(&logs);
+    let stats = metadata.witness_stats.unwrap();
+    let merkle_paths: Vec<_> = metadata.witness.unwrap().into_merkle_paths().collect();
+    assert_eq!(stats.entries, 100);
+    assert_eq!(merkle_paths.len(), 100);
+    let first_path = &merkle_paths[0].merkle_paths;
+    let path_lens: Vec<_> = merkle_paths
+        .iter()
+        .enumerate()
+        .map(|(i, log)| {
+            if i == 0 {
+                return first_path.len();
+            }
+            let shared_len = log
+                .merkle_paths
+                .iter()
+                .zip(first_path)
+                .take_while(|(hash, first_hash)| hash == first_hash)
+                .count();
+            log.merkle_paths.len() - shared_len
+        })
+        .collect();
+    assert_eq!(stats.total_hashes, path_lens.iter().sum::<usize>());
+    assert_eq!(stats.max_path_len, 256);
+    assert_eq!(stats.min_path_len, *path_lens.iter().min().unwrap());
+    assert!(stats.min_path_len < 256);
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new(db.into());
+    tree.set_witness_without_paths(true);
+    let stats = tree.process_l1_batch(&logs).witness_stats.unwrap();
+    assert_eq!(
+        stats,
+        WitnessStats {
+            entries: 100,
+            ..WitnessStats::default()
+        }
+    );
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    assert_eq!(tree.process_l1_batch(&logs).witness_stats, None);
+}
+
+#[test]
+fn flushing_witness_incrementally() {
+    let logs = gen_storage_logs();
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut eager_tree = ZkSyncTree::new(db.into());
+    let expected_witness = eager_tree.process_l1_batch(&logs).witness.unwrap();
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new(db.into());
+    let sink = RecordingWitnessSink::default();
+    tree.set_witness_flush(30, sink.clone());
+    let metadata = tree.process_l1_batch(&logs);
+    assert!(metadata.witness.is_none());
+    assert_eq!(metadata.root_hash, eager_tree.root_hash());
+
+    let chunks = mem::take(&mut *sink.0.lock().unwrap());
+    let chunk_lens: Vec<_> = chunks
+        .iter()
+        .map(|(_, chunk)| chunk.clone().into_merkle_paths().len())
+        .collect();
+    assert_eq!(chunk_lens, [30, 30, 30, 10]);
+    let mut merkle_paths = vec![];
+    for (l1_batch_number, chunk) in chunks {
+        assert_eq!(l1_batch_number, L1BatchNumber(0));
+        assert_eq!(
+            chunk.next_enumeration_index(),
+            expected_witness.next_enumeration_index()
+        );
+        merkle_paths.extend(chunk.into_merkle_paths());
+    }
+    let expected_merkle_paths: Vec<_> = expected_witness.into_merkle_paths().collect();
+    assert_eq!(merkle_paths, expected_merkle_paths);
+}
+
+#[test]
+fn streaming_witness() {
+    let logs = gen_storage_logs();
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut eager_tree = ZkSyncTree::new(db.into());
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new(db.into());
+
+    // The second batch contains no-op updates for the first 50 keys, which must be omitted from the witness.
+    for batch in [&logs[..50], &logs] {
+        let expected_metadata = eager_tree.process_l1_batch(batch);
+        let mut merkle_paths = vec![];
+        let metadata = tree.process_l1_batch_full_streaming(batch, |log| merkle_paths.push(log));
+        assert!(metadata.witness.is_none());
+        assert_eq!(metadata.root_hash, expected_metadata.root_hash);
+        assert_eq!(
+            metadata.rollup_last_leaf_index,
+            expected_metadata.rollup_last_leaf_index
+        );
+        assert_eq!(metadata.total_writes(), expected_metadata.total_writes());
+
+        let expected_merkle_paths: Vec<_> = expected_metadata
+            .witness
+            .unwrap()
+            .into_merkle_paths()
+            .collect();
+        assert_eq!(merkle_paths.len(), 50);
+        assert_eq!(merkle_paths, expected_merkle_paths);
+    }
+}
+
+#[test]
+fn getting_path_node_hashes() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    let metadata = tree.process_l1_batch(&logs[..50]);
+    tree.save();
+
+    let reader = tree.reader();
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    for &key in &keys[..50] {
+        let hashes = reader.path_node_hashes(L1BatchNumber(0), key).unwrap();
+        assert!(hashes.len() >= 2, "{hashes:?}");
+        assert_eq!(hashes[0], metadata.root_hash);
+        let unique_hashes: HashSet<_> = hashes.iter().collect();
+        assert_eq!(unique_hashes.len(), hashes.len());
+    }
+    let hashes = reader.path_node_hashes(L1BatchNumber(0), keys[60]).unwrap();
+    assert!(hashes.is_empty());
+
+    let err = reader
+        .path_node_hashes(L1BatchNumber(1), keys[0])
+        .unwrap_err();
+    assert_eq!(err.missing_version, 1);
+}
+
+#[test]
+fn verifying_leaves_in_root() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    tree.process_l1_batch(&logs[..50]);
+    tree.save();
+
+    let reader = tree.reader();
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    let mut leaves: Vec<_> = keys
+        .iter()
+        .enumerate()
+        .map(|(i, &key)| {
+            let value = if i < 50 {
+                H256::from_low_u64_be(i as u64)
+            } else {
+                H256::zero()
+            };
+            (key, value)
+        })
+        .collect();
+    let results = reader
+        .verify_leaves_in_root(L1BatchNumber(0), &leaves)
+        .unwrap();
+    assert_eq!(results, vec![true; leaves.len()]);
+
+    leaves[10].1 = H256::repeat_byte(0xff);
+    leaves[60].1 = H256::repeat_byte(0xff);
+    let results = reader
+        .verify_leaves_in_root(L1BatchNumber(0), &leaves)
+        .unwrap();
+    for (i, is_verified) in results.into_iter().enumerate() {
+        assert_eq!(is_verified, i != 10 && i != 60, "{i}");
+    }
+
+    let err = reader
+        .verify_leaves_in_root(L1BatchNumber(1), &leaves)
+        .unwrap_err();
+    assert_eq!(err.missing_version, 1);
+}
+
+#[test]
+fn range_commitments() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    let metadata = tree.process_l1_batch(&logs[..50]);
+    tree.save();
+
+    let reader = tree.reader();
+    let mut commitment = reader.range_commitment(L1BatchNumber(0), 10, 20).unwrap();
+    assert_eq!(commitment.entries.len(), 11);
+    for (entry, leaf_index) in commitment.entries.iter().zip(10..) {
+        assert_eq!(entry.base.leaf_index, leaf_index);
+        assert_eq!(entry.base.value, H256::from_low_u64_be(leaf_index - 1));
+    }
+    commitment.verify(metadata.root_hash).unwrap();
+    commitment.verify(H256::zero()).unwrap_err();
+
+    let full_commitment = reader.range_commitment(L1BatchNumber(0), 1, 50).unwrap();
+    full_commitment.verify(metadata.root_hash).unwrap();
+    assert_ne!(full_commitment.hash, commitment.hash);
+
+    commitment.hash = H256::repeat_byte(1);
+    commitment.verify(metadata.root_hash).unwrap_err();
+
+    for (from_index, to_index) in [(0, 5), (20, 10), (40, 60)] {
+        let err = reader
+            .range_commitment(L1BatchNumber(0), from_index, to_index)
+            .unwrap_err();
+        assert_matches!(err, RangeCommitmentError::InvalidRange { .. });
+    }
+    let err = reader
+        .range_commitment(L1BatchNumber(1), 1, 10)
+        .unwrap_err();
+    assert_matches!(err, RangeCommitmentError::NoVersion(_));
+}
+
+#[test]
+fn processing_batches_aggregated() {
+    let mut logs = gen_storage_logs();
+    let first_batch = logs[..50].to_vec();
+    let TreeInstruction::Write(updated_entry) = &mut logs[10] else {
+        unreachable!("Unexpected instruction");
+    };
+    updated_entry.value = H256::repeat_byte(0xff);
+    let second_batch = &logs[10..];
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let aggregated = tree.process_l1_batches_aggregated(&[&first_batch, second_batch]);
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut reference_tree = ZkSyncTree::new_lightweight(db.into());
+    reference_tree.process_l1_batch(&first_batch);
+    let metadata = reference_tree.process_l1_batch(second_batch);
+    assert_eq!(aggregated.root_hash, metadata.root_hash);
+    assert_eq!(aggregated.leaf_count + 1, metadata.rollup_last_leaf_index);
+
+    assert_eq!(aggregated.writes.len(), logs.len());
+    for (write, instruction) in aggregated.writes.iter().zip(&logs) {
+        let TreeInstruction::Write(expected_write) = instruction else {
+            unreachable!("Unexpected instruction");
+        };
+        assert_eq!(write, expected_write);
+    }
+}
+
+#[test]
+fn applying_raw_writes() {
+    let logs = gen_storage_logs();
+    let mut updated_logs = logs[25..75].to_vec();
+    for log in &mut updated_logs[..25] {
+        let TreeInstruction::Write(entry) = log else {
+            unreachable!("Unexpected instruction: {log:?}");
+        };
+        entry.value = H256::repeat_byte(0xff);
+    }
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    tree.process_l1_batch(&logs[..50]);
+    let expected_root_hash = tree.process_l1_batch(&updated_logs).root_hash;
+
+    let to_writes = |logs: &[TreeInstruction<StorageKey>]| -> Vec<_> {
+        logs.iter()
+            .map(|log| {
+                let TreeInstruction::Write(entry) = log else {
+                    unreachable!("Unexpected instruction: {log:?}");
+                };
+                let key = ZkSyncTree::hash_storage_key(&entry.key);
+                (key, entry.value, entry.leaf_index)
+            })
+            .collect()
+    };
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut replayed_tree = ZkSyncTree::new_lightweight(db.into());
+    replayed_tree.apply_writes(&to_writes(&logs[..50]));
+    let root_hash = replayed_tree.apply_writes(&to_writes(&updated_logs));
+    assert_eq!(root_hash, expected_root_hash);
+    assert_eq!(replayed_tree.next_l1_batch_number(), L1BatchNumber(2));
+    replayed_tree.save();
+
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|log| ZkSyncTree::hash_storage_key(&log.key()))
+        .collect();
+    let entries = replayed_tree
+        .reader()
+        .entries_with_proofs(L1BatchNumber(1), &keys[..75])
+        .unwrap();
+    for (i, entry) in entries.iter().enumerate() {
+        assert_eq!(entry.base.leaf_index, i as u64 + 1);
+    }
+}
+
+#[test]
+fn computing_state_diff() {
+    let logs = gen_storage_logs();
+    let with_value = |logs: &[TreeInstruction<StorageKey>], value: H256| -> Vec<_> {
+        let mut logs = logs.to_vec();
+        for log in &mut logs {
+            let TreeInstruction::Write(entry) = log else {
+                unreachable!("Unexpected instruction: {log:?}");
+            };
+            entry.value = value;
+        }
+        logs
+    };
+    // Keys #25..#35 are updated, and keys #35..#50 are subject to no-op updates.
+    let mut updated_logs = with_value(&logs[25..35], H256::repeat_byte(0xff));
+    updated_logs.extend_from_slice(&logs[35..75]);
+    let mut final_logs = with_value(&logs[25..30], H256::repeat_byte(0xee));
+    final_logs.extend_from_slice(&logs[75..]);
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    tree.process_l1_batch(&logs[..50]);
+    tree.process_l1_batch(&updated_logs);
+    let expected_root_hash = tree.process_l1_batch(&final_logs).root_hash;
+    tree.save();
+
+    let reader = tree.reader();
+    let diff = reader
+        .state_diff(L1BatchNumber(0), L1BatchNumber(2))
+        .unwrap();
+    assert_eq!(diff.len(), 60);
+    let leaf_indices: Vec<_> = diff.iter().map(|&(_, _, leaf_index)| leaf_index).collect();
+    assert_eq!(leaf_indices, (26..36).chain(51..101).collect::<Vec<_>>());
+    for &(_, value, leaf_index) in &diff[..5] {
+        assert_eq!(value, H256::repeat_byte(0xee), "{leaf_index}");
+    }
+    for &(_, value, leaf_index) in &diff[5..10] {
+        assert_eq!(value, H256::repeat_byte(0xff), "{leaf_index}");
+    }
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut follower_tree = ZkSyncTree::new_lightweight(db.into());
+    follower_tree.process_l1_batch(&logs[..50]);
+    assert_eq!(follower_tree.apply_writes(&diff), expected_root_hash);
+
+    let diff = reader
+        .state_diff(L1BatchNumber(2), L1BatchNumber(2))
+        .unwrap();
+    assert!(diff.is_empty());
+    let err = reader
+        .state_diff(L1BatchNumber(0), L1BatchNumber(3))
+        .unwrap_err();
+    assert_eq!(err.missing_version, 3);
+}
+
+#[test]
+fn getting_batch_changelog() {
+    let logs = gen_storage_logs();
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    tree.process_l1_batch(&logs[..50]);
+    // Keys #0..#5 are updated, and keys #5..#10 are subject to no-op updates.
+    let mut updated_logs = logs[..10].to_vec();
+    for log in &mut updated_logs[..5] {
+        let TreeInstruction::Write(entry) = log else {
+            unreachable!("Unexpected instruction: {log:?}");
+        };
+        entry.value = H256::repeat_byte(0xff);
+    }
+    updated_logs.extend_from_slice(&logs[50..]);
+    tree.process_l1_batch(&updated_logs);
+    tree.save();
+
+    let keys: Vec<_> = logs
+        .iter()
+        .map(|instr| ZkSyncTree::hash_storage_key(&instr.key()))
+        .collect();
+    let reader = tree.reader();
+    let changelog = reader.batch_changelog(L1BatchNumber(0)).unwrap();
+    assert_eq!(changelog.len(), 50);
+    for (i, change) in changelog.iter().enumerate() {
+        assert_eq!(change.key, keys[i]);
+        assert_eq!(change.value, H256::from_low_u64_be(i as u64));
+        assert_eq!(change.leaf_index, i as u64 + 1);
+        assert!(change.is_initial_write);
     }
 
-    // Reapply one of the reverted logs
-    let storage = RocksDB::new(temp_dir.as_ref()).unwrap();
-    {
-        let storage_log = mirror_logs.get(3 * block_size).unwrap();
-        let mut tree = ZkSyncTree::new_lightweight(storage.into());
-        tree.process_l1_batch(slice::from_ref(storage_log));
-        tree.save();
+    let changelog = reader.batch_changelog(L1BatchNumber(1)).unwrap();
+    assert_eq!(changelog.len(), 55);
+    for (i, change) in changelog[..5].iter().enumerate() {
+        assert_eq!(change.key, keys[i]);
+        assert_eq!(change.value, H256::repeat_byte(0xff));
+        assert!(!change.is_initial_write);
+    }
+    for (change, i) in changelog[5..].iter().zip(50..) {
+        assert_eq!(change.key, keys[i]);
+        assert_eq!(change.leaf_index, i as u64 + 1);
+        assert!(change.is_initial_write);
     }
 
-    // check saved block number
-    let storage = RocksDB::new(temp_dir.as_ref()).unwrap();
-    let tree = ZkSyncTree::new_lightweight(storage.into());
-    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(3));
+    let err = reader.batch_changelog(L1BatchNumber(2)).unwrap_err();
+    assert_eq!(err.missing_version, 2);
 }
 
 #[test]
-fn reset_tree() {
+fn batch_write_proofs() {
     let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
-    let storage = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
     let logs = gen_storage_logs();
-    let mut tree = ZkSyncTree::new_lightweight(storage.into());
-    let empty_root_hash = tree.root_hash();
+    let root_hash = tree.process_l1_batch(&logs).root_hash;
+    tree.save();
 
-    logs.chunks(5).fold(empty_root_hash, |hash, chunk| {
+    let reader = tree.reader();
+    let mut keys: Vec<_> = logs[..30]
+        .iter()
+        .map(|log| ZkSyncTree::hash_storage_key(&log.key()))
+        .collect();
+    keys.push(Key::from(123)); // missing key
+    let proof = reader.batch_write_proof(L1BatchNumber(0), &keys).unwrap();
+    assert_eq!(proof.entries.len(), keys.len());
+    assert!(proof.entries[30].is_empty());
+    proof.verify(root_hash).unwrap();
+
+    let entries = reader.entries_with_proofs(L1BatchNumber(0), &keys).unwrap();
+    for (entry, entry_with_proof) in proof.entries.iter().zip(&entries) {
+        assert_eq!(*entry, entry_with_proof.base);
+    }
+    let individual_proofs_len: usize = entries.iter().map(|entry| entry.merkle_path.len()).sum();
+    assert!(
+        proof.nodes.len() < individual_proofs_len,
+        "{} vs {individual_proofs_len}",
+        proof.nodes.len()
+    );
+
+    let mut tampered_proof = proof.clone();
+    tampered_proof.entries[0].value = H256::repeat_byte(0xff);
+    let err = tampered_proof.verify(root_hash).unwrap_err();
+    assert!(err.to_string().contains("root hash mismatch"), "{err}");
+
+    let mut redundant_proof = proof.clone();
+    redundant_proof.nodes.push(BatchProofNode {
+        height: 0,
+        index: keys[0],
+        hash: H256::zero(),
+    });
+    let err = redundant_proof.verify(root_hash).unwrap_err();
+    assert!(err.to_string().contains("redundant"), "{err}");
+
+    let err = reader
+        .batch_write_proof(L1BatchNumber(1), &keys)
+        .unwrap_err();
+    assert_eq!(err.missing_version, 1);
+}
+
+#[test]
+fn computing_structure_stats() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    tree.process_l1_batch(&[]);
+    tree.process_l1_batch(&gen_storage_logs());
+    tree.save();
+
+    let reader = tree.reader();
+    let stats = reader.structure_stats(L1BatchNumber(0)).unwrap();
+    assert_eq!(stats, TreeStructureStats::default());
+
+    let stats = reader.structure_stats(L1BatchNumber(1)).unwrap();
+    assert_eq!(stats.leaf_count, 100);
+    // 100 leaves are split among 16 subtrees at the first level, most of which are further split.
+    assert!(stats.internal_node_count > 10, "{stats:?}");
+    assert!(stats.max_leaf_depth >= 2, "{stats:?}");
+    assert!(
+        stats.avg_leaf_depth > 1.0 && stats.avg_leaf_depth <= stats.max_leaf_depth as f64,
+        "{stats:?}"
+    );
+
+    let sampled_stats = reader.sampled_structure_stats(L1BatchNumber(1), 1).unwrap();
+    assert_eq!(sampled_stats, stats);
+    let sampled_stats = reader.sampled_structure_stats(L1BatchNumber(1), 4).unwrap();
+    assert_eq!(sampled_stats.leaf_count, 100);
+    assert!(sampled_stats.max_leaf_depth <= stats.max_leaf_depth);
+    assert!(sampled_stats.avg_leaf_depth > 1.0, "{sampled_stats:?}");
+
+    let err = reader.structure_stats(L1BatchNumber(2)).unwrap_err();
+    assert_eq!(err.missing_version, 2);
+}
+
+#[test]
+fn getting_disk_usage() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let usage = tree.reader().disk_usage();
+    assert_eq!(usage.live_version_count, 0);
+    assert_eq!(
+        usage.total_bytes,
+        usage.column_family_bytes.values().sum::<u64>()
+    );
+
+    let logs = gen_storage_logs();
+    for chunk in logs.chunks(20) {
         tree.process_l1_batch(chunk);
-        tree.reset();
-        assert_eq!(tree.root_hash(), hash);
+    }
+    tree.save();
+    let usage = tree.reader().disk_usage();
+    assert_eq!(usage.live_version_count, 5);
+    let cf_names: Vec<_> = usage.column_family_bytes.keys().copied().collect();
+    assert_eq!(cf_names, ["default", "stale_keys", "witness_inputs"]);
+    assert_eq!(
+        usage.total_bytes,
+        usage.column_family_bytes.values().sum::<u64>()
+    );
+    drop(tree);
+
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut db = RocksDBWrapper::from(db);
+    MerkleTreePruner::new(&mut db, 2).0.run_once().unwrap();
+    let usage = ZkSyncTree::new_lightweight(db).reader().disk_usage();
+    assert_eq!(usage.live_version_count, 3);
+}
+
+#[test]
+fn getting_retained_l1_batch_range() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    assert_eq!(tree.latest_l1_batch_number(), None);
+    assert_eq!(tree.oldest_retained_l1_batch_number(), None);
+
+    tree.process_l1_batch(&[]);
+    assert_eq!(tree.latest_l1_batch_number(), Some(L1BatchNumber(0)));
+    assert_eq!(
+        tree.oldest_retained_l1_batch_number(),
+        Some(L1BatchNumber(0))
+    );
 
+    let logs = gen_storage_logs();
+    for chunk in logs.chunks(20) {
         tree.process_l1_batch(chunk);
-        tree.save();
-        tree.root_hash()
-    });
+    }
+    tree.save();
+    assert_eq!(tree.latest_l1_batch_number(), Some(L1BatchNumber(5)));
+    assert_eq!(
+        tree.oldest_retained_l1_batch_number(),
+        Some(L1BatchNumber(0))
+    );
+    drop(tree);
+
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut db = RocksDBWrapper::from(db);
+    MerkleTreePruner::new(&mut db, 2).0.run_once().unwrap();
+    let tree = ZkSyncTree::new_lightweight(db);
+    assert_eq!(tree.latest_l1_batch_number(), Some(L1BatchNumber(5)));
+    assert_eq!(
+        tree.oldest_retained_l1_batch_number(),
+        Some(L1BatchNumber(3))
+    );
 }
 
 #[test]
-fn read_logs() {
+fn finding_deepest_leaf() {
     let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
-    let mut logs = gen_storage_logs();
-    logs.truncate(5);
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    tree.process_l1_batch(&[]);
+    let logs = gen_storage_logs();
+    tree.process_l1_batch(&logs);
+    tree.save();
 
-    let write_metadata = {
-        let db = RocksDB::new(temp_dir.as_ref()).unwrap();
-        let mut tree = ZkSyncTree::new_lightweight(db.into());
-        let metadata = tree.process_l1_batch(&logs);
-        tree.save();
-        metadata
-    };
+    let reader = tree.reader();
+    assert_eq!(reader.deepest_leaf(L1BatchNumber(0)).unwrap(), None);
+    let (deepest_key, max_depth) = reader.deepest_leaf(L1BatchNumber(1)).unwrap().unwrap();
+    assert_eq!(
+        reader.key_depth(L1BatchNumber(1), deepest_key).unwrap(),
+        Some(max_depth)
+    );
+    // 100 leaves cannot fit into a binary tree of depth 6.
+    assert!(max_depth >= 7, "{max_depth}");
+    for instr in &logs {
+        let key = ZkSyncTree::hash_storage_key(&instr.key());
+        let depth = reader.key_depth(L1BatchNumber(1), key).unwrap().unwrap();
+        assert!(depth <= max_depth, "{depth} > {max_depth}");
+    }
+
+    let err = reader.deepest_leaf(L1BatchNumber(2)).unwrap_err();
+    assert_eq!(err.missing_version, 2);
+}
 
+#[test]
+fn verifying_enumeration_indices() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
     let db = RocksDB::new(temp_dir.as_ref()).unwrap();
     let mut tree = ZkSyncTree::new_lightweight(db.into());
-    let read_logs: Vec<_> = logs
-        .into_iter()
-        .map(|instr| TreeInstruction::Read(instr.key()))
+    tree.process_l1_batch(&gen_storage_logs()[..50]);
+    // Inject a gap: the next inserted key must have index 51.
+    tree.apply_writes(&[(Key::from(1), H256::repeat_byte(1), 52)]);
+    // Inject a duplicate index.
+    tree.apply_writes(&[(Key::from(2), H256::repeat_byte(2), 50)]);
+    tree.save();
+
+    let reader = tree.reader();
+    reader.verify_enumeration_indices(L1BatchNumber(0)).unwrap();
+    let err = reader
+        .verify_enumeration_indices(L1BatchNumber(1))
+        .unwrap_err();
+    assert_matches!(err, EnumerationError::Missing { index: 51 });
+    let err = reader
+        .verify_enumeration_indices(L1BatchNumber(2))
+        .unwrap_err();
+    assert_matches!(err, EnumerationError::Duplicate { index: 50 });
+    let err = reader
+        .verify_enumeration_indices(L1BatchNumber(3))
+        .unwrap_err();
+    assert_matches!(err, EnumerationError::NoVersion(_));
+}
+
+#[test]
+fn prewarming_proofs() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    tree.process_l1_batch(&logs);
+    tree.save();
+
+    let hashed_keys: Vec<_> = logs
+        .iter()
+        .map(|instr| instr.key().hashed_key_u256())
         .collect();
-    let read_metadata = tree.process_l1_batch(&read_logs);
+    let reader = tree.reader();
+    reader
+        .prewarm_proofs(L1BatchNumber(0), &hashed_keys)
+        .unwrap();
+    reader.prewarm_proofs(L1BatchNumber(0), &[]).unwrap();
+    reader
+        .prewarm_proofs(L1BatchNumber(1), &hashed_keys)
+        .unwrap_err();
+}
 
-    assert_eq!(read_metadata.root_hash, write_metadata.root_hash);
+#[test]
+fn insertion_proofs() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    let root_hash = tree.process_l1_batch(&logs).root_hash;
+    tree.save();
+
+    let mut hashed_keys: Vec<_> = logs
+        .iter()
+        .map(|instr| instr.key().hashed_key_u256())
+        .collect();
+    hashed_keys.sort_unstable();
+    let reader = tree.reader();
+
+    let new_key = hashed_keys[42] + 1;
+    let proof = reader.insertion_proof(L1BatchNumber(0), new_key).unwrap();
+    assert!(proof.entry.base.is_empty());
+    proof.entry.verify(&Blake2Hasher, root_hash);
+    let low_neighbor = proof.low_neighbor.unwrap();
+    assert_eq!(low_neighbor.base.key, hashed_keys[42]);
+    low_neighbor.verify(&Blake2Hasher, root_hash);
+    let high_neighbor = proof.high_neighbor.unwrap();
+    assert_eq!(high_neighbor.base.key, hashed_keys[43]);
+    high_neighbor.verify(&Blake2Hasher, root_hash);
+
+    let proof = reader
+        .insertion_proof(L1BatchNumber(0), hashed_keys[0] - 1)
+        .unwrap();
+    assert!(proof.low_neighbor.is_none());
+    assert_eq!(proof.high_neighbor.unwrap().base.key, hashed_keys[0]);
+
+    let proof = reader
+        .insertion_proof(L1BatchNumber(0), hashed_keys[99] + 1)
+        .unwrap();
+    assert_eq!(proof.low_neighbor.unwrap().base.key, hashed_keys[99]);
+    assert!(proof.high_neighbor.is_none());
+
+    reader
+        .insertion_proof(L1BatchNumber(1), new_key)
+        .unwrap_err();
 }
 
 fn create_write_log(
@@ -445,6 +2828,53 @@ fn witness_workflow() {
     insta::assert_yaml_snapshot!("log-metadata-list-short", witnesses);
 }
 
+#[test]
+fn processing_batch_with_entry_proofs() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new(db.into());
+    let mut logs = gen_storage_logs();
+    tree.process_l1_batch(&logs[..50]);
+
+    // Update some existing keys, add a duplicate write and a read.
+    for log in &mut logs[40..60] {
+        let TreeInstruction::Write(entry) = log else {
+            unreachable!("Unexpected instruction: {log:?}");
+        };
+        entry.value = H256::repeat_byte(0xff);
+    }
+    let mut batch = logs[40..60].to_vec();
+    batch.push(logs[45]);
+    batch.push(TreeInstruction::Read(logs[0].key()));
+    let (metadata, proofs) = tree.process_l1_batch_full_with_entry_proofs(&batch);
+    assert!(metadata.witness.is_some());
+
+    let expected_keys: Vec<_> = logs[40..60]
+        .iter()
+        .map(|log| ZkSyncTree::hash_storage_key(&log.key()))
+        .collect();
+    let keys: Vec<_> = proofs.iter().map(|proof| proof.base.key).collect();
+    assert_eq!(keys, expected_keys);
+    for (proof, log) in proofs.iter().zip(&logs[40..60]) {
+        let TreeInstruction::Write(entry) = log else {
+            unreachable!("Unexpected instruction: {log:?}");
+        };
+        assert_eq!(proof.base.value, entry.value);
+        assert_eq!(proof.base.leaf_index, entry.leaf_index);
+        proof.verify(&Blake2Hasher, metadata.root_hash);
+    }
+
+    tree.save();
+    let reader_proofs = tree
+        .reader()
+        .entries_with_proofs(L1BatchNumber(1), &expected_keys)
+        .unwrap();
+    for (reader_proof, proof) in reader_proofs.iter().zip(&proofs) {
+        assert_eq!(reader_proof.base, proof.base);
+        assert_eq!(reader_proof.merkle_path, proof.merkle_path);
+    }
+}
+
 #[test]
 fn witnesses_with_multiple_blocks() {
     let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
@@ -486,3 +2916,244 @@ fn witnesses_with_multiple_blocks() {
         "{non_empty_levels_by_block:?}"
     );
 }
+
+#[test]
+fn lazy_witnesses() {
+    let logs = gen_storage_logs();
+    let mut blocks: Vec<_> = logs.chunks(10).map(<[_]>::to_vec).collect();
+    // Add some reads and no-op writes.
+    blocks[3].extend(
+        blocks[1]
+            .iter()
+            .map(|instr| TreeInstruction::Read(instr.key())),
+    );
+    blocks[4].extend_from_slice(&blocks[2][..5]);
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut eager_tree = ZkSyncTree::new(db.into());
+    let expected_witnesses: Vec<_> = blocks
+        .iter()
+        .map(|block| eager_tree.process_l1_batch(block).witness.unwrap())
+        .collect();
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new(db.into());
+    tree.set_lazy_witnesses(true);
+    for block in &blocks {
+        let metadata = tree.process_l1_batch(block);
+        assert!(metadata.witness.is_none());
+    }
+    assert_eq!(tree.root_hash(), eager_tree.root_hash());
+    // Witness inputs are only persisted on save.
+    assert!(tree
+        .reader()
+        .reconstruct_witness(L1BatchNumber(0))
+        .is_none());
+    tree.save();
+
+    let reader = tree.reader();
+    for (i, expected_witness) in expected_witnesses.into_iter().enumerate() {
+        let l1_batch_number = L1BatchNumber(i as u32);
+        let witness = reader.reconstruct_witness(l1_batch_number).unwrap();
+        assert_eq!(
+            witness.next_enumeration_index(),
+            expected_witness.next_enumeration_index()
+        );
+        let merkle_paths: Vec<_> = witness.into_merkle_paths().collect();
+        let expected_merkle_paths: Vec<_> = expected_witness.into_merkle_paths().collect();
+        assert_eq!(merkle_paths, expected_merkle_paths, "{l1_batch_number}");
+    }
+    assert!(reader
+        .reconstruct_witness(L1BatchNumber(blocks.len() as u32))
+        .is_none());
+}
+
+#[test]
+fn removing_lazy_witness_inputs() {
+    fn witness_input_count(db: RocksDBWrapper) -> (usize, RocksDBWrapper) {
+        let raw_db = db.into_inner();
+        let count = raw_db
+            .prefix_iterator_cf(MerkleTreeColumnFamily::WitnessInputs, &[])
+            .count();
+        (count, raw_db.into())
+    }
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new(db.into());
+    tree.set_lazy_witnesses(true);
+    let logs = gen_storage_logs();
+    for chunk in logs.chunks(10) {
+        tree.process_l1_batch(chunk);
+    }
+    tree.save();
+    // Inputs for reverted batches must be removed.
+    tree.revert_logs(L1BatchNumber(6));
+    tree.save();
+    drop(tree);
+
+    let db = RocksDBWrapper::from(RocksDB::new(temp_dir.as_ref()).unwrap());
+    let (count, mut db) = witness_input_count(db);
+    assert_eq!(count, 7);
+
+    // Inputs for batches which witnesses cannot be reconstructed for because of pruning must be removed.
+    let stats = MerkleTreePruner::new(&mut db, 2).0.run_once().unwrap();
+    assert_eq!(stats.target_retained_version, 4);
+    let (count, db) = witness_input_count(db);
+    assert_eq!(count, 2);
+
+    let reader = ZkSyncTree::new(db).reader();
+    assert!(reader.reconstruct_witness(L1BatchNumber(4)).is_none());
+    assert!(reader.reconstruct_witness(L1BatchNumber(5)).is_some());
+    assert!(reader.reconstruct_witness(L1BatchNumber(6)).is_some());
+}
+
+#[test]
+fn unsaved_revert_retains_lazy_witness_inputs() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new(db.into());
+    tree.set_lazy_witnesses(true);
+    let logs = gen_storage_logs();
+    for chunk in logs.chunks(10) {
+        tree.process_l1_batch(chunk);
+    }
+    tree.save();
+
+    // Revert isn't saved, so witness inputs for the "reverted" batches must be retained.
+    tree.revert_logs(L1BatchNumber(6));
+    tree.reset();
+    drop(tree);
+
+    let db = RocksDBWrapper::from(RocksDB::new(temp_dir.as_ref()).unwrap());
+    let reader = ZkSyncTree::new(db).reader();
+    assert!(reader.reconstruct_witness(L1BatchNumber(6)).is_some());
+    assert!(reader.reconstruct_witness(L1BatchNumber(7)).is_some());
+}
+
+#[test]
+fn rebuilding_witnesses_for_lightweight_tree() {
+    let logs = gen_storage_logs();
+    let mut blocks: Vec<_> = logs.chunks(10).map(<[_]>::to_vec).collect();
+    // Add some reads and no-op writes.
+    blocks[3].extend(
+        blocks[1]
+            .iter()
+            .map(|instr| TreeInstruction::Read(instr.key())),
+    );
+    blocks[4].extend_from_slice(&blocks[2][..5]);
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut full_tree = ZkSyncTree::new(db.into());
+    let expected_witnesses: Vec<_> = blocks
+        .iter()
+        .map(|block| full_tree.process_l1_batch(block).witness.unwrap())
+        .collect();
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    for block in &blocks {
+        tree.process_l1_batch(block);
+    }
+    tree.save();
+
+    let reader = tree.reader();
+    for (i, (block, expected_witness)) in blocks.iter().zip(expected_witnesses).enumerate() {
+        let l1_batch_number = L1BatchNumber(i as u32);
+        let witness = reader.rebuild_witness(l1_batch_number, block).unwrap();
+        assert_eq!(
+            witness.next_enumeration_index(),
+            expected_witness.next_enumeration_index()
+        );
+        let merkle_paths: Vec<_> = witness.into_merkle_paths().collect();
+        let expected_merkle_paths: Vec<_> = expected_witness.into_merkle_paths().collect();
+        assert_eq!(merkle_paths, expected_merkle_paths, "{l1_batch_number}");
+    }
+    assert_eq!(tree.root_hash(), full_tree.root_hash());
+
+    let err = reader
+        .rebuild_witness(L1BatchNumber(1), &blocks[2])
+        .unwrap_err();
+    assert_matches!(err, RebuildWitnessError::RootHashMismatch { .. });
+    let err = reader
+        .rebuild_witness(L1BatchNumber(blocks.len() as u32), &blocks[0])
+        .unwrap_err();
+    assert_matches!(err, RebuildWitnessError::NoVersion(_));
+}
+
+#[test]
+fn lightweight_tree_with_reads() {
+    let logs = gen_storage_logs();
+    let read = |instr: &TreeInstruction<StorageKey>| TreeInstruction::Read(instr.key());
+    let mut second_batch: Vec<_> = logs[..10].iter().map(read).collect();
+    second_batch.extend(logs[60..65].iter().map(read)); // missing keys
+    second_batch.extend_from_slice(&logs[50..60]);
+    second_batch.extend(logs[50..55].iter().map(read)); // keys inserted in the same batch
+    second_batch.extend(logs[5..10].iter().map(|instr| {
+        let TreeInstruction::Write(entry) = instr else {
+            unreachable!();
+        };
+        TreeInstruction::Write(TreeEntry::new(
+            entry.key,
+            entry.leaf_index,
+            H256::repeat_byte(0xff),
+        ))
+    }));
+    second_batch.extend(logs[5..10].iter().map(read)); // keys updated in the same batch
+    let batches = [&logs[..50], &second_batch];
+
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut full_tree = ZkSyncTree::new(db.into());
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight_with_reads(db.into());
+
+    for batch in batches {
+        let expected_metadata = full_tree.process_l1_batch(batch);
+        let metadata = tree.process_l1_batch(batch);
+        assert!(metadata.witness.is_none());
+        assert_eq!(metadata.root_hash, expected_metadata.root_hash);
+        assert_eq!(metadata.read_count, expected_metadata.read_count);
+
+        // The batches contain no no-op updates, so witness logs correspond to instructions one-to-one.
+        let expected_logs = expected_metadata.witness.unwrap().into_merkle_paths();
+        let expected_logs: Vec<_> = expected_logs
+            .map(|log| match (log.is_write, log.first_write) {
+                (true, true) => TreeLogEntry::Inserted,
+                (true, false) => TreeLogEntry::Updated {
+                    leaf_index: log.leaf_enumeration_index,
+                    previous_value: H256(log.value_read),
+                },
+                (false, _) if log.leaf_enumeration_index == 0 => TreeLogEntry::ReadMissingKey,
+                (false, _) => TreeLogEntry::Read {
+                    leaf_index: log.leaf_enumeration_index,
+                    value: H256(log.value_read),
+                },
+            })
+            .collect();
+        assert_eq!(metadata.logs.unwrap(), expected_logs);
+    }
+}
+
+#[test]
+fn replacing_multi_get_chunk_size() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db = RocksDB::new(temp_dir.as_ref()).unwrap();
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    let logs = gen_storage_logs();
+    let metadata = tree.process_l1_batch(&logs);
+    tree.save();
+
+    let prev_chunk_size = tree.set_multi_get_chunk_size(10);
+    assert_eq!(prev_chunk_size, usize::MAX);
+    // Reads should work the same with the lowered chunk size.
+    let new_metadata = tree.process_l1_batch(&logs);
+    assert_eq!(new_metadata.root_hash, metadata.root_hash);
+
+    assert_eq!(tree.set_multi_get_chunk_size(prev_chunk_size), 10);
+}
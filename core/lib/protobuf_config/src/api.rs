@@ -127,6 +127,7 @@ impl ProtoRepr for proto::Web3JsonRpc {
                 .map(|(i, k)| parse_h160(k).context(i))
                 .collect::<Result<Vec<_>, _>>()
                 .context("account_pks")?,
+            method_concurrency_limits: self.method_concurrency_limits.clone(),
         })
     }
     fn build(this: &Self::Type) -> Self {
@@ -182,6 +183,7 @@ impl ProtoRepr for proto::Web3JsonRpc {
                 .iter()
                 .map(|k| format!("{:?}", k))
                 .collect(),
+            method_concurrency_limits: this.method_concurrency_limits.clone(),
         }
     }
 }
@@ -95,11 +95,13 @@ impl PrepareBasicCircuitsJob {
         self.merkle_paths.reserve(additional_capacity);
     }
 
-    /// Pushes an additional Merkle path.
-    pub fn push_merkle_path(&mut self, mut path: StorageLogMetadata) {
+    /// Pushes an additional Merkle path. Returns the number of hashes retained in the path
+    /// after converting it to the compact form.
+    pub fn push_merkle_path(&mut self, mut path: StorageLogMetadata) -> usize {
         let Some(first_path) = self.merkle_paths.first() else {
+            let retained_hash_count = path.merkle_paths.len();
             self.merkle_paths.push(path);
-            return;
+            return retained_hash_count;
         };
         assert_eq!(first_path.merkle_paths.len(), path.merkle_paths.len());
 
@@ -108,7 +110,9 @@ impl PrepareBasicCircuitsJob {
             hash_pairs.position(|(hash, first_path_hash)| hash != first_path_hash);
         let first_unique_idx = first_unique_idx.unwrap_or(path.merkle_paths.len());
         path.merkle_paths = path.merkle_paths.split_off(first_unique_idx);
+        let retained_hash_count = path.merkle_paths.len();
         self.merkle_paths.push(path);
+        retained_hash_count
     }
 
     /// Converts this job into an iterator over the contained Merkle paths.
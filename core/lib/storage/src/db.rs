@@ -298,6 +298,9 @@ pub struct RocksDBOptions {
     pub stalled_writes_retries: StalledWritesRetries,
     /// Number of open files that can be used by the DB. Default is None, for no limit.
     pub max_open_files: Option<NonZeroU32>,
+    /// Enables collecting RocksDB statistics, such as block cache hits and misses
+    /// (see [`RocksDB::block_cache_stats()`]). Collecting statistics has a small performance overhead.
+    pub enable_statistics: bool,
 }
 
 impl Default for RocksDBOptions {
@@ -308,10 +311,39 @@ impl Default for RocksDBOptions {
             large_memtable_capacity: None,
             stalled_writes_retries: StalledWritesRetries::new(Duration::from_secs(10)),
             max_open_files: None,
+            enable_statistics: false,
         }
     }
 }
 
+/// Cumulative block cache statistics for a [`RocksDB`] instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockCacheStats {
+    /// Number of block reads served from the block cache.
+    pub hits: u64,
+    /// Number of block reads that missed the block cache and were served from disk.
+    pub misses: u64,
+}
+
+impl BlockCacheStats {
+    const HIT_TICKER: &'static str = "rocksdb.block.cache.hit";
+    const MISS_TICKER: &'static str = "rocksdb.block.cache.miss";
+
+    /// Parses stats from the RocksDB statistics dump, which has a `{ticker} COUNT : {value}` line per ticker.
+    fn parse(stats: &str) -> Option<Self> {
+        let ticker_value = |name: &str| {
+            stats.lines().find_map(|line| {
+                let value = line.strip_prefix(name)?.strip_prefix(" COUNT : ")?;
+                value.trim().parse::<u64>().ok()
+            })
+        };
+        Some(Self {
+            hits: ticker_value(Self::HIT_TICKER)?,
+            misses: ticker_value(Self::MISS_TICKER)?,
+        })
+    }
+}
+
 /// Thin wrapper around a RocksDB instance.
 ///
 /// The wrapper is cheaply cloneable (internally, it wraps a DB instance in an [`Arc`]).
@@ -337,6 +369,9 @@ impl<CF: NamedColumnFamily> RocksDB<CF> {
             -1
         };
         db_options.set_max_open_files(max_open_files);
+        if options.enable_statistics {
+            db_options.enable_statistics();
+        }
         let existing_cfs = DB::list_cf(&db_options, path).unwrap_or_else(|err| {
             tracing::warn!(
                 "Failed getting column families for RocksDB `{}` at `{}`, assuming CFs are empty; {err}",
@@ -588,6 +623,31 @@ impl<CF: NamedColumnFamily> RocksDB<CF> {
         // ^ unwrap() is safe for the same reasons as in `prefix_iterator_cf()`.
     }
 
+    /// Returns the total size of SST files for the specified column family in bytes, or 0 if the size
+    /// cannot be read. This is an approximation of the on-disk size (it doesn't include the write-ahead log
+    /// and other auxiliary files).
+    pub fn sst_size(&self, cf: CF) -> u64 {
+        let cf = self.inner.db.cf_handle(cf.name()).unwrap();
+        // ^ `unwrap()` is safe (CF existence is checked during DB initialization)
+        self.inner
+            .int_property(cf, properties::TOTAL_SST_FILES_SIZE)
+            .unwrap_or(0)
+    }
+
+    /// Returns cumulative block cache statistics for this DB instance since it was opened. Returns `None`
+    /// if [statistics](RocksDBOptions::enable_statistics) are not enabled or cannot be read.
+    pub fn block_cache_stats(&self) -> Option<BlockCacheStats> {
+        let stats = self
+            .inner
+            .db
+            .property_value(properties::OPTIONS_STATISTICS)
+            .unwrap_or_else(|err| {
+                tracing::warn!(%err, "Failed getting RocksDB statistics");
+                None
+            })?;
+        BlockCacheStats::parse(&stats)
+    }
+
     /// Creates a new profiled operation.
     pub fn new_profiled_operation(&self, name: &'static str) -> ProfiledOperation {
         ProfiledOperation {
@@ -866,6 +926,40 @@ mod tests {
         assert!(retry_count <= 2);
     }
 
+    #[test]
+    fn parsing_block_cache_stats() {
+        let stats = "rocksdb.block.cache.miss COUNT : 12\n\
+                     rocksdb.block.cache.hit COUNT : 345\n\
+                     rocksdb.block.cache.add COUNT : 12\n\
+                     rocksdb.db.get.micros P50 : 1.000000 P95 : 2.000000 COUNT : 10 SUM : 15\n";
+        let stats = BlockCacheStats::parse(stats).unwrap();
+        assert_eq!(
+            stats,
+            BlockCacheStats {
+                hits: 345,
+                misses: 12
+            }
+        );
+
+        assert_eq!(BlockCacheStats::parse(""), None);
+    }
+
+    #[test]
+    fn getting_block_cache_stats() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = RocksDB::<NewColumnFamilies>::new(temp_dir.path()).unwrap();
+        assert_eq!(db.block_cache_stats(), None);
+        drop(db);
+
+        let options = RocksDBOptions {
+            enable_statistics: true,
+            ..RocksDBOptions::default()
+        };
+        let db = RocksDB::<NewColumnFamilies>::with_options(temp_dir.path(), options).unwrap();
+        let stats = db.block_cache_stats().unwrap();
+        assert_eq!(stats, BlockCacheStats { hits: 0, misses: 0 });
+    }
+
     fn assert_close(lhs: Duration, rhs: Duration) {
         let lhs_millis = (lhs.as_secs_f64() * 1_000.0).round() as u64;
         let rhs_millis = (rhs.as_secs_f64() * 1_000.0).round() as u64;
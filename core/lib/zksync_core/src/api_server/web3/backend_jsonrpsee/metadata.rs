@@ -11,29 +11,39 @@ use zksync_web3_decl::{
 
 #[cfg(test)]
 use super::testonly::RecordedMethodCalls;
-use crate::api_server::web3::metrics::API_METRICS;
+use crate::api_server::web3::metrics::{ApiTransportLabel, Web3ErrorLogLevels, API_METRICS};
 
 /// Metadata assigned to a JSON-RPC method call.
 #[derive(Debug, Clone)]
 pub(crate) struct MethodMetadata {
     pub name: &'static str,
+    /// Transport of the server handling the call.
+    pub transport: ApiTransportLabel,
     pub started_at: Instant,
     /// Block ID requested by the call.
     pub block_id: Option<api::BlockId>,
     /// Difference between the latest block number and the requested block ID.
     pub block_diff: Option<u32>,
+    /// Timestamp (in seconds since UNIX epoch) of the block served by the call.
+    pub block_timestamp: Option<u64>,
     /// Did this call return an app-level error?
     pub has_app_error: bool,
+    /// Correlation ID of the call. If set, it is included into all log lines emitted when observing the call,
+    /// so that logs for a single call can be grouped.
+    pub request_id: Option<Arc<str>>,
 }
 
 impl MethodMetadata {
-    fn new(name: &'static str) -> Self {
+    fn new(name: &'static str, transport: ApiTransportLabel) -> Self {
         Self {
             name,
+            transport,
             started_at: Instant::now(),
             block_id: None,
             block_diff: None,
+            block_timestamp: None,
             has_app_error: false,
+            request_id: None,
         }
     }
 }
@@ -62,11 +72,20 @@ impl Drop for CurrentMethodGuard<'_> {
 #[derive(Debug, Default)]
 pub(crate) struct MethodTracer {
     inner: ThreadLocal<CurrentMethodInner>,
+    error_log_levels: Web3ErrorLogLevels,
     #[cfg(test)]
     recorder: RecordedMethodCalls,
 }
 
 impl MethodTracer {
+    /// Creates a tracer that logs application errors according to the specified levels.
+    pub fn new(error_log_levels: Web3ErrorLogLevels) -> Self {
+        Self {
+            error_log_levels,
+            ..Self::default()
+        }
+    }
+
     /// Sets the block ID for the current JSON-RPC method call. It will be used as a metric label for method latency etc.
     ///
     /// This should be called inside JSON-RPC method handlers; otherwise, this method is a no-op.
@@ -88,10 +107,25 @@ impl MethodTracer {
         }
     }
 
-    pub(super) fn new_call(self: &Arc<Self>, name: &'static str) -> MethodCall {
+    /// Sets the timestamp of the block served by the current JSON-RPC method call. It is used to compute
+    /// the age of the newest served block.
+    ///
+    /// This should be called inside JSON-RPC method handlers; otherwise, this method is a no-op.
+    pub fn set_block_timestamp(&self, timestamp: u64) {
+        let cell = self.inner.get_or_default();
+        if let Some(metadata) = &mut *cell.borrow_mut() {
+            metadata.block_timestamp = Some(timestamp);
+        }
+    }
+
+    pub(super) fn new_call(
+        self: &Arc<Self>,
+        name: &'static str,
+        transport: ApiTransportLabel,
+    ) -> MethodCall {
         MethodCall {
             tracer: self.clone(),
-            meta: MethodMetadata::new(name),
+            meta: MethodMetadata::new(name, transport),
             is_completed: false,
         }
     }
@@ -99,7 +133,7 @@ impl MethodTracer {
     pub(super) fn observe_error(&self, err: &Web3Error) {
         let cell = self.inner.get_or_default();
         if let Some(metadata) = &mut *cell.borrow_mut() {
-            API_METRICS.observe_web3_error(metadata.name, err);
+            API_METRICS.observe_web3_error(metadata.name, err, &self.error_log_levels);
             metadata.has_app_error = true;
         }
     }
@@ -133,6 +167,11 @@ impl Drop for MethodCall {
 }
 
 impl MethodCall {
+    /// Sets the correlation ID for this call. It will be included into logs emitted when observing the call.
+    pub(super) fn set_request_id(&mut self, request_id: &str) {
+        self.meta.request_id = Some(request_id.into());
+    }
+
     pub(super) fn set_as_current(&mut self) -> CurrentMethodGuard<'_> {
         let meta = &mut self.meta;
         let cell = self.tracer.inner.get_or_default();
@@ -149,10 +188,15 @@ impl MethodCall {
         let meta = &self.meta;
         match response.success_or_error {
             MethodResponseResult::Success => {
-                API_METRICS.observe_response_size(meta.name, response.result.len());
+                API_METRICS.observe_response_size(meta, response.result.len());
             }
             MethodResponseResult::Failed(error_code) => {
-                API_METRICS.observe_protocol_error(meta.name, error_code, meta.has_app_error);
+                API_METRICS.observe_protocol_error(
+                    meta.name,
+                    error_code,
+                    meta.has_app_error,
+                    meta.request_id.as_deref(),
+                );
             }
         }
         API_METRICS.observe_latency(meta);
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     future::Future,
     num::NonZeroU32,
     pin::Pin,
@@ -16,7 +16,7 @@ use governor::{
 };
 use once_cell::sync::OnceCell;
 use pin_project_lite::pin_project;
-use tokio::sync::watch;
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
 use vise::{
     Buckets, Counter, EncodeLabelSet, EncodeLabelValue, Family, GaugeGuard, Histogram, Metrics,
 };
@@ -27,7 +27,7 @@ use zksync_web3_decl::jsonrpsee::{
 };
 
 use super::metadata::{MethodCall, MethodTracer};
-use crate::api_server::web3::metrics::API_METRICS;
+use crate::api_server::web3::metrics::{ApiTransportLabel, MethodNameGuard, API_METRICS};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
 #[metrics(label = "transport", rename_all = "snake_case")]
@@ -104,6 +104,98 @@ where
     }
 }
 
+/// Per-method limits on the number of concurrently processed calls.
+#[derive(Debug, Default)]
+pub(crate) struct MethodConcurrencyLimits {
+    semaphores: HashMap<&'static str, Arc<Semaphore>>,
+}
+
+impl MethodConcurrencyLimits {
+    /// Creates limits for registered methods. Limits for unknown methods are ignored.
+    pub fn new(
+        limits: &HashMap<String, usize>,
+        registered_method_names: &HashSet<&'static str>,
+    ) -> Self {
+        let semaphores = limits.iter().filter_map(|(method_name, &limit)| {
+            let Some(&method_name) = registered_method_names.get(method_name.as_str()) else {
+                tracing::warn!(
+                    "Concurrency limit is set for method `{method_name}`, which is not registered; ignoring"
+                );
+                return None;
+            };
+            Some((method_name, Arc::new(Semaphore::new(limit))))
+        });
+        Self {
+            semaphores: semaphores.collect(),
+        }
+    }
+}
+
+/// RPC-level middleware enforcing [`MethodConcurrencyLimits`]. Calls exceeding the limit for the called method
+/// are rejected with the "server is busy" error.
+#[derive(Debug)]
+pub(crate) struct ConcurrencyLimitMiddleware<S> {
+    inner: S,
+    limits: Arc<MethodConcurrencyLimits>,
+}
+
+impl<S> ConcurrencyLimitMiddleware<S> {
+    pub fn new(inner: S, limits: Arc<MethodConcurrencyLimits>) -> Self {
+        Self { inner, limits }
+    }
+}
+
+impl<'a, S> RpcServiceT<'a> for ConcurrencyLimitMiddleware<S>
+where
+    S: Send + Sync + RpcServiceT<'a>,
+{
+    type Future = ResponseFuture<WithPermit<S::Future>>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        let semaphore = self.limits.semaphores.get_key_value(request.method_name());
+        let permit = if let Some((&method_name, semaphore)) = semaphore {
+            let Ok(permit) = semaphore.clone().try_acquire_owned() else {
+                API_METRICS.method_concurrency_rejections[&method_name].inc();
+                let rp = MethodResponse::error(
+                    request.id,
+                    ErrorObject::borrowed(
+                        ErrorCode::ServerIsBusy.code(),
+                        "Too many concurrent calls to the method",
+                        None,
+                    ),
+                );
+                return ResponseFuture::ready(rp);
+            };
+            Some(permit)
+        } else {
+            None
+        };
+
+        ResponseFuture::future(WithPermit {
+            _permit: permit,
+            inner: self.inner.call(request),
+        })
+    }
+}
+
+pin_project! {
+    /// Future holding a concurrency permit until it completes or is dropped.
+    #[derive(Debug)]
+    pub(crate) struct WithPermit<F> {
+        _permit: Option<OwnedSemaphorePermit>,
+        #[pin]
+        inner: F,
+    }
+}
+
+impl<F: Future> Future for WithPermit<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.project().inner.poll(cx)
+    }
+}
+
 /// RPC-level middleware that adds [`MethodCall`] metadata to method logic. Method handlers can then access this metadata
 /// using [`MethodTracer`], which is a part of `RpcState`. When the handler completes or is dropped, the results are reported
 /// as metrics.
@@ -112,20 +204,23 @@ where
 #[derive(Debug)]
 pub(crate) struct MetadataMiddleware<S> {
     inner: S,
-    registered_method_names: Arc<HashSet<&'static str>>,
+    method_names: Arc<MethodNameGuard>,
     method_tracer: Arc<MethodTracer>,
+    transport: ApiTransportLabel,
 }
 
 impl<S> MetadataMiddleware<S> {
     pub fn new(
         inner: S,
-        registered_method_names: Arc<HashSet<&'static str>>,
+        method_names: Arc<MethodNameGuard>,
         method_tracer: Arc<MethodTracer>,
+        transport: ApiTransportLabel,
     ) -> Self {
         Self {
             inner,
-            registered_method_names,
+            method_names,
             method_tracer,
+            transport,
         }
     }
 }
@@ -137,16 +232,18 @@ where
     type Future = WithMethodCall<S::Future>;
 
     fn call(&self, request: Request<'a>) -> Self::Future {
-        // "Normalize" the method name by searching it in the set of all registered methods. This extends the lifetime
-        // of the name to `'static` and maps unknown methods to "", so that method name metric labels don't have unlimited cardinality.
-        let method_name = self
-            .registered_method_names
-            .get(request.method_name())
-            .copied()
-            .unwrap_or("");
+        // "Normalize" the method name by searching it in the metrics allowlist. This extends the lifetime
+        // of the name to `'static` and maps unknown methods to "other", so that method name metric labels
+        // don't have unlimited cardinality.
+        let method_name = self.method_names.label(request.method_name());
+        let mut call = self.method_tracer.new_call(method_name, self.transport);
+        // Request IDs are only logged on the debug level, so we don't bother extracting them otherwise.
+        if tracing::enabled!(tracing::Level::DEBUG) {
+            call.set_request_id(&request.id.to_string());
+        }
 
         WithMethodCall {
-            call: self.method_tracer.new_call(method_name),
+            call,
             inner: self.inner.call(request),
         }
     }
@@ -254,12 +351,14 @@ where
 mod tests {
     use std::time::Duration;
 
-    use jsonrpsee::helpers::MethodResponseResult;
+    use futures::future::BoxFuture;
+    use jsonrpsee::{helpers::MethodResponseResult, types::Id};
     use rand::{thread_rng, Rng};
     use test_casing::{test_casing, Product};
     use zksync_types::api;
 
     use super::*;
+    use crate::api_server::web3::metrics::OTHER_METHOD_LABEL;
 
     #[test_casing(4, Product(([false, true], [false, true])))]
     #[tokio::test(flavor = "multi_thread")]
@@ -293,10 +392,9 @@ mod tests {
                 }
             };
 
-            WithMethodCall {
-                call: method_tracer.new_call("test"),
-                inner,
-            }
+            let mut call = method_tracer.new_call("test", ApiTransportLabel::Http);
+            call.set_request_id(&format!("req-{i}"));
+            WithMethodCall { call, inner }
         });
 
         if spawn_tasks {
@@ -312,12 +410,101 @@ mod tests {
         assert_eq!(calls.len(), 100);
         for call in &calls {
             assert_eq!(call.metadata.name, "test");
-            assert!(call.metadata.block_id.is_some());
+            let Some(api::BlockId::Number(api::BlockNumber::Number(number))) =
+                call.metadata.block_id
+            else {
+                panic!("Unexpected block ID: {:?}", call.metadata.block_id);
+            };
+            let expected_request_id = format!("req-{number}");
+            assert_eq!(
+                call.metadata.request_id.as_deref(),
+                Some(expected_request_id.as_str())
+            );
             assert_eq!(call.metadata.block_diff, Some(9));
             assert!(call.response.is_success());
         }
     }
 
+    #[test]
+    fn method_name_guard_basics() {
+        let registered_method_names = HashSet::from(["eth_call", "eth_chainId", "zks_L1ChainId"]);
+        let guard = MethodNameGuard::new(&registered_method_names, None);
+        assert_eq!(guard.label("eth_call"), "eth_call");
+        assert_eq!(guard.label("zks_L1ChainId"), "zks_L1ChainId");
+        assert_eq!(guard.label("eth_unknown"), OTHER_METHOD_LABEL);
+        assert_eq!(guard.label(""), OTHER_METHOD_LABEL);
+
+        let allowlist = HashSet::from(["eth_call".to_owned(), "eth_unknown".to_owned()]);
+        let guard = MethodNameGuard::new(&registered_method_names, Some(&allowlist));
+        assert_eq!(guard.label("eth_call"), "eth_call");
+        assert_eq!(guard.label("eth_chainId"), OTHER_METHOD_LABEL);
+        // Unregistered methods are not allowed even if they are in the allowlist.
+        assert_eq!(guard.label("eth_unknown"), OTHER_METHOD_LABEL);
+    }
+
+    /// Service completing each call once a permit is added to the wrapped semaphore.
+    #[derive(Debug)]
+    struct BlockingService(Arc<Semaphore>);
+
+    impl<'a> RpcServiceT<'a> for BlockingService {
+        type Future = BoxFuture<'a, MethodResponse>;
+
+        fn call(&self, _request: Request<'a>) -> Self::Future {
+            let unblock = self.0.clone();
+            Box::pin(async move {
+                unblock.acquire().await.unwrap().forget();
+                MethodResponse {
+                    result: "{}".to_string(),
+                    success_or_error: MethodResponseResult::Success,
+                    is_subscription: false,
+                }
+            })
+        }
+    }
+
+    fn mock_request(method_name: &'static str) -> Request<'static> {
+        Request::new(method_name.into(), None, Id::Number(1))
+    }
+
+    #[tokio::test]
+    async fn concurrency_limit_middleware_basics() {
+        let registered_method_names = HashSet::from(["eth_call", "eth_chainId"]);
+        let limits = HashMap::from([("eth_call".to_owned(), 1)]);
+        let limits = MethodConcurrencyLimits::new(&limits, &registered_method_names);
+        let unblock = Arc::new(Semaphore::new(0));
+        let middleware =
+            ConcurrencyLimitMiddleware::new(BlockingService(unblock.clone()), Arc::new(limits));
+        let rejections = &API_METRICS.method_concurrency_rejections[&"eth_call"];
+        let initial_rejections = rejections.get();
+
+        let first_call = middleware.call(mock_request("eth_call"));
+        let response = middleware.call(mock_request("eth_call")).await;
+        assert_eq!(
+            response.success_or_error.as_error_code(),
+            Some(ErrorCode::ServerIsBusy.code())
+        );
+        assert_eq!(rejections.get(), initial_rejections + 1);
+
+        // Methods without a limit are not affected.
+        unblock.add_permits(1);
+        let response = middleware.call(mock_request("eth_chainId")).await;
+        assert!(response.success_or_error.is_success());
+
+        // The permit is released once the call completes...
+        unblock.add_permits(1);
+        let response = first_call.await;
+        assert!(response.success_or_error.is_success());
+        let second_call = middleware.call(mock_request("eth_call"));
+        assert_eq!(rejections.get(), initial_rejections + 1);
+
+        // ...or is dropped.
+        drop(second_call);
+        unblock.add_permits(1);
+        let response = middleware.call(mock_request("eth_call")).await;
+        assert!(response.success_or_error.is_success());
+        assert_eq!(rejections.get(), initial_rejections + 1);
+    }
+
     #[tokio::test]
     async fn traffic_tracker_basics() {
         let traffic_tracker = TrafficTracker::default();
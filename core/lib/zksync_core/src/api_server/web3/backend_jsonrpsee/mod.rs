@@ -9,7 +9,10 @@ use zksync_web3_decl::{
 
 pub(crate) use self::{
     metadata::{MethodMetadata, MethodTracer},
-    middleware::{LimitMiddleware, MetadataMiddleware, ShutdownMiddleware, TrafficTracker},
+    middleware::{
+        ConcurrencyLimitMiddleware, LimitMiddleware, MetadataMiddleware, MethodConcurrencyLimits,
+        ShutdownMiddleware, TrafficTracker,
+    },
 };
 use crate::api_server::tx_sender::SubmitTxError;
 
@@ -1,12 +1,19 @@
 //! Metrics for the JSON-RPC server.
 
-use std::{fmt, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 
+use serde::Serialize;
 use vise::{
     Buckets, Counter, DurationAsSecs, EncodeLabelSet, EncodeLabelValue, Family, Gauge, Histogram,
     Info, LabeledFamily, Metrics, Unit,
 };
 use zksync_types::api;
+use zksync_utils::time::seconds_since_epoch;
 use zksync_web3_decl::error::Web3Error;
 
 use super::{
@@ -16,11 +23,21 @@ use super::{
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
 #[metrics(label = "scheme", rename_all = "UPPERCASE")]
-pub(in crate::api_server) enum ApiTransportLabel {
+pub(crate) enum ApiTransportLabel {
     Http,
     Ws,
 }
 
+impl ApiTransportLabel {
+    /// Returns the name of this transport; it coincides with the `scheme` label value in metrics.
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Http => "HTTP",
+            Self::Ws => "WS",
+        }
+    }
+}
+
 impl From<&ApiTransport> for ApiTransportLabel {
     fn from(transport: &ApiTransport) -> Self {
         match transport {
@@ -30,6 +47,52 @@ impl From<&ApiTransport> for ApiTransportLabel {
     }
 }
 
+/// Method name label used for methods not in the [allowlist](MethodNameGuard).
+pub(crate) const OTHER_METHOD_LABEL: &str = "other";
+
+/// Guard against unbounded cardinality of method name labels in metrics. Method names in the allowlist
+/// are used as labels as is, while all other names (e.g., ones for unknown methods in malformed requests)
+/// are mapped to [`OTHER_METHOD_LABEL`].
+#[derive(Debug)]
+pub(crate) struct MethodNameGuard {
+    allowlist: HashSet<&'static str>,
+    observed_other_method: AtomicBool,
+}
+
+impl MethodNameGuard {
+    /// Creates a guard allowing registered method names. If `allowlist` is specified, only registered methods
+    /// in it are allowed; this can be used to further restrict the number of reported time series.
+    pub fn new(
+        registered_method_names: &HashSet<&'static str>,
+        allowlist: Option<&HashSet<String>>,
+    ) -> Self {
+        let allowlist = registered_method_names
+            .iter()
+            .copied()
+            .filter(|name| allowlist.map_or(true, |allowlist| allowlist.contains(*name)))
+            .collect();
+        Self {
+            allowlist,
+            observed_other_method: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the metrics label for the specified method name. Only the first occurrence of a method
+    /// not in the allowlist is logged, so that malformed requests cannot spam logs.
+    pub fn label(&self, method_name: &str) -> &'static str {
+        if let Some(&name) = self.allowlist.get(method_name) {
+            return name;
+        }
+        if !self.observed_other_method.swap(true, Ordering::Relaxed) {
+            tracing::info!(
+                "Observed call to method `{method_name}` not in metrics allowlist; calls to such methods \
+                 will be reported with the `{OTHER_METHOD_LABEL}` method label"
+            );
+        }
+        OTHER_METHOD_LABEL
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue)]
 #[metrics(rename_all = "snake_case")]
 enum BlockIdLabel {
@@ -91,6 +154,7 @@ impl From<&MethodMetadata> for MethodLabels {
         }
     }
 }
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue)]
 #[metrics(rename_all = "snake_case")]
 enum Web3ErrorKind {
@@ -108,6 +172,37 @@ enum Web3ErrorKind {
 }
 
 impl Web3ErrorKind {
+    const ALL: [Self; 11] = [
+        Self::NoBlock,
+        Self::Pruned,
+        Self::SubmitTransaction,
+        Self::TransactionSerialization,
+        Self::Proxy,
+        Self::TooManyTopics,
+        Self::FilterNotFound,
+        Self::LogsLimitExceeded,
+        Self::InvalidFilterBlockHash,
+        Self::TreeApiUnavailable,
+        Self::Internal,
+    ];
+
+    /// Returns the name of this kind; it coincides with the `kind` label value in metrics.
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::NoBlock => "no_block",
+            Self::Pruned => "pruned",
+            Self::SubmitTransaction => "submit_transaction",
+            Self::TransactionSerialization => "transaction_serialization",
+            Self::Proxy => "proxy",
+            Self::TooManyTopics => "too_many_topics",
+            Self::FilterNotFound => "filter_not_found",
+            Self::LogsLimitExceeded => "logs_limit_exceeded",
+            Self::InvalidFilterBlockHash => "invalid_filter_block_hash",
+            Self::TreeApiUnavailable => "tree_api_unavailable",
+            Self::Internal => "internal",
+        }
+    }
+
     fn new(err: &Web3Error) -> Self {
         match err {
             Web3Error::NoBlock => Self::NoBlock,
@@ -125,6 +220,65 @@ impl Web3ErrorKind {
     }
 }
 
+/// Log levels for application errors returned by Web3 methods, keyed by the error kind. Errors of kinds
+/// not present in the map are not logged (but are still reported in metrics).
+#[derive(Debug, Clone)]
+pub(crate) struct Web3ErrorLogLevels(HashMap<Web3ErrorKind, tracing::Level>);
+
+impl Default for Web3ErrorLogLevels {
+    fn default() -> Self {
+        Self(HashMap::from([
+            (Web3ErrorKind::Internal, tracing::Level::ERROR),
+            (Web3ErrorKind::Proxy, tracing::Level::WARN),
+        ]))
+    }
+}
+
+impl Web3ErrorLogLevels {
+    /// Creates log levels by applying `overrides` keyed by error kind names (e.g., `internal` or `proxy`)
+    /// to the default levels. Unknown error kinds are ignored with a warning.
+    pub fn new(overrides: &HashMap<String, tracing::Level>) -> Self {
+        let mut this = Self::default();
+        for (kind_name, &level) in overrides {
+            let kind = Web3ErrorKind::ALL
+                .into_iter()
+                .find(|kind| kind.as_str() == kind_name);
+            if let Some(kind) = kind {
+                this.0.insert(kind, level);
+            } else {
+                tracing::warn!(
+                    "Unknown Web3 error kind `{kind_name}` in log level overrides; ignoring"
+                );
+            }
+        }
+        this
+    }
+
+    fn level(&self, err: &Web3Error) -> Option<tracing::Level> {
+        self.0.get(&Web3ErrorKind::new(err)).copied()
+    }
+
+    fn log(&self, method: &str, err: &Web3Error) {
+        let Some(level) = self.level(err) else {
+            return;
+        };
+        let message = match err {
+            Web3Error::InternalError(err) => format!("Internal error in method `{method}`: {err}"),
+            Web3Error::ProxyError(err) => {
+                format!("Error proxying call to main node in method `{method}`: {err}")
+            }
+            _ => format!("Error in method `{method}`: {err}"),
+        };
+        match level {
+            tracing::Level::ERROR => tracing::error!("{message}"),
+            tracing::Level::WARN => tracing::warn!("{message}"),
+            tracing::Level::INFO => tracing::info!("{message}"),
+            tracing::Level::DEBUG => tracing::debug!("{message}"),
+            _ => tracing::trace!("{message}"), // `tracing::Level::TRACE`
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue)]
 #[metrics(rename_all = "snake_case")]
 enum ProtocolErrorOrigin {
@@ -139,6 +293,12 @@ struct ProtocolErrorLabels {
     origin: ProtocolErrorOrigin,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelSet)]
+struct ResponseSizeLabels {
+    method: &'static str,
+    scheme: ApiTransportLabel,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelSet)]
 struct Web3ErrorLabels {
     method: &'static str,
@@ -167,6 +327,21 @@ const BLOCK_DIFF_BUCKETS: Buckets = Buckets::values(&[
 
 const RESPONSE_SIZE_BUCKETS: Buckets = Buckets::exponential(1.0..=1_048_576.0, 4.0);
 
+/// Point-in-time sample of key API server metrics that can be serialized, e.g. into a diagnostics bundle.
+/// Values are read from metrics one by one, so the snapshot is not atomic.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiMetricsSnapshot {
+    /// Total number of application errors grouped by error kind.
+    pub web3_errors: BTreeMap<&'static str, u64>,
+    /// Total number of protocol errors grouped by error code.
+    pub web3_rpc_errors: BTreeMap<i32, u64>,
+    /// Number of in-flight requests grouped by transport (`HTTP` or `WS`), as last sampled
+    /// by the server middleware.
+    pub web3_in_flight_requests: BTreeMap<&'static str, usize>,
+    /// Number of currently open WebSocket sessions.
+    pub ws_open_sessions: i64,
+}
+
 /// General-purpose API server metrics.
 #[derive(Debug, Metrics)]
 #[metrics(prefix = "api")]
@@ -183,14 +358,18 @@ pub(in crate::api_server) struct ApiMetrics {
     /// Difference between the latest sealed miniblock and the resolved miniblock for a web3 call.
     #[metrics(buckets = BLOCK_DIFF_BUCKETS, labels = ["method"])]
     web3_call_block_diff: LabeledFamily<&'static str, Histogram<u64>>,
-    /// Serialized response size in bytes. Only recorded for successful responses.
-    #[metrics(buckets = RESPONSE_SIZE_BUCKETS, labels = ["method"], unit = Unit::Bytes)]
-    web3_call_response_size: LabeledFamily<&'static str, Histogram<usize>>,
+    /// Age of the newest block served by a call that resolved `latest`, `committed` or `finalized` block ID,
+    /// relative to the current time. Only updated if the call has recorded the block timestamp.
+    #[metrics(unit = Unit::Seconds)]
+    newest_served_block_age: Gauge<Duration>,
+    /// Serialized response size in bytes grouped by method name and transport. Only recorded for successful responses.
+    #[metrics(buckets = RESPONSE_SIZE_BUCKETS, unit = Unit::Bytes)]
+    web3_call_response_size: Family<ResponseSizeLabels, Histogram<usize>>,
 
     /// Number of application errors grouped by error kind and method name. Only collected for errors that were successfully routed
     /// to a method (i.e., this method is defined).
     web3_errors: Family<Web3ErrorLabels, Counter>,
-    /// Number of protocol errors grouped by error code and method name. Method name is not set for "method not found" errors.
+    /// Number of protocol errors grouped by error code and method name. Method name is set to "other" for "method not found" errors.
     web3_rpc_errors: Family<ProtocolErrorLabels, Counter>,
     /// Number of transaction submission errors for a specific submission error reason.
     #[metrics(labels = ["reason"])]
@@ -198,6 +377,12 @@ pub(in crate::api_server) struct ApiMetrics {
 
     #[metrics(buckets = Buckets::exponential(1.0..=128.0, 2.0))]
     pub web3_in_flight_requests: Family<ApiTransportLabel, Histogram<usize>>,
+    /// Last sampled number of in-flight requests. Complements the `web3_in_flight_requests` histogram
+    /// so that the current value can be read back.
+    pub web3_in_flight_requests_current: Family<ApiTransportLabel, Gauge<usize>>,
+    /// Number of calls rejected because of exceeding the concurrency limit for the called method.
+    #[metrics(labels = ["method"])]
+    pub method_concurrency_rejections: LabeledFamily<&'static str, Counter>,
     /// Number of currently open WebSocket sessions.
     pub ws_open_sessions: Gauge,
     /// Number of currently inserted into DB transactions.
@@ -230,13 +415,57 @@ impl ApiMetrics {
         }
     }
 
+    /// Takes a point-in-time snapshot of the key metrics. This is a read-only aggregation over the metric families.
+    pub(super) fn snapshot(&self) -> ApiMetricsSnapshot {
+        let mut web3_errors = BTreeMap::new();
+        for (labels, counter) in self.web3_errors.to_entries() {
+            *web3_errors.entry(labels.kind.as_str()).or_default() += counter.get();
+        }
+        let mut web3_rpc_errors = BTreeMap::new();
+        for (labels, counter) in self.web3_rpc_errors.to_entries() {
+            *web3_rpc_errors.entry(labels.error_code).or_default() += counter.get();
+        }
+        let mut web3_in_flight_requests = BTreeMap::new();
+        for (transport, gauge) in self.web3_in_flight_requests_current.to_entries() {
+            *web3_in_flight_requests
+                .entry(transport.as_str())
+                .or_default() += gauge.get();
+        }
+        ApiMetricsSnapshot {
+            web3_errors,
+            web3_rpc_errors,
+            web3_in_flight_requests,
+            ws_open_sessions: self.ws_open_sessions.get(),
+        }
+    }
+
     /// Observes latency of a finished RPC call.
     pub fn observe_latency(&self, meta: &MethodMetadata) {
         let latency = meta.started_at.elapsed();
         self.web3_call[&MethodLabels::from(meta)].observe(latency);
+        if let Some(request_id) = &meta.request_id {
+            tracing::debug!(
+                request_id = %request_id,
+                "Call to method `{}` finished in {latency:?}",
+                meta.name
+            );
+        }
         if let Some(block_diff) = meta.block_diff {
             self.web3_call_block_diff[&meta.name].observe(block_diff.into());
         }
+        if let (Some(block_id), Some(block_timestamp)) = (meta.block_id, meta.block_timestamp) {
+            if matches!(
+                block_id,
+                api::BlockId::Number(
+                    api::BlockNumber::Latest
+                        | api::BlockNumber::Committed
+                        | api::BlockNumber::Finalized
+                )
+            ) {
+                let age = seconds_since_epoch().saturating_sub(block_timestamp);
+                self.newest_served_block_age.set(Duration::from_secs(age));
+            }
+        }
     }
 
     /// Observes latency of a dropped RPC call.
@@ -246,11 +475,28 @@ impl ApiMetrics {
     }
 
     /// Observes serialized size of a response.
-    pub fn observe_response_size(&self, method: &'static str, size: usize) {
-        self.web3_call_response_size[&method].observe(size);
+    pub fn observe_response_size(&self, meta: &MethodMetadata, size: usize) {
+        let labels = ResponseSizeLabels {
+            method: meta.name,
+            scheme: meta.transport,
+        };
+        self.web3_call_response_size[&labels].observe(size);
+        if let Some(request_id) = &meta.request_id {
+            tracing::debug!(
+                request_id = %request_id,
+                "Call to method `{}` returned response with size {size}B",
+                meta.name
+            );
+        }
     }
 
-    pub fn observe_protocol_error(&self, method: &'static str, error_code: i32, app_error: bool) {
+    pub fn observe_protocol_error(
+        &self,
+        method: &'static str,
+        error_code: i32,
+        app_error: bool,
+        request_id: Option<&str>,
+    ) {
         let labels = ProtocolErrorLabels {
             method,
             error_code,
@@ -260,29 +506,33 @@ impl ApiMetrics {
                 ProtocolErrorOrigin::Framework
             },
         };
-        if self.web3_rpc_errors[&labels].inc() == 0 {
-            let ProtocolErrorLabels {
-                method,
-                error_code,
-                origin,
-            } = &labels;
+        let is_new_error_code = self.web3_rpc_errors[&labels].inc() == 0;
+        let ProtocolErrorLabels {
+            method,
+            error_code,
+            origin,
+        } = &labels;
+        if is_new_error_code {
             tracing::info!(
+                request_id,
                 "Observed new error code for method `{method}`: {error_code}, origin: {origin:?}"
             );
+        } else if let Some(request_id) = request_id {
+            tracing::debug!(
+                request_id,
+                "Call to method `{method}` failed with error code {error_code}, origin: {origin:?}"
+            );
         }
     }
 
-    pub fn observe_web3_error(&self, method: &'static str, err: &Web3Error) {
-        // Log internal error details.
-        match err {
-            Web3Error::InternalError(err) => {
-                tracing::error!("Internal error in method `{method}`: {err}");
-            }
-            Web3Error::ProxyError(err) => {
-                tracing::warn!("Error proxying call to main node in method `{method}`: {err}");
-            }
-            _ => { /* do nothing */ }
-        }
+    pub fn observe_web3_error(
+        &self,
+        method: &'static str,
+        err: &Web3Error,
+        log_levels: &Web3ErrorLogLevels,
+    ) {
+        // Log error details according to the configured levels.
+        log_levels.log(method, err);
 
         let labels = Web3ErrorLabels {
             method,
@@ -321,6 +571,9 @@ pub(super) struct PubSubMetrics {
     pub notify_subscribers_latency: Family<SubscriptionType, Histogram<Duration>>,
     /// Total number of events sent to all subscribers of a certain type.
     pub notify: Family<SubscriptionType, Counter>,
+    /// Number of subscribers a batch of new events was broadcast to (i.e., the fan-out factor).
+    #[metrics(buckets = Buckets::exponential(1.0..=1_024.0, 2.0))]
+    pub notify_fanout: Family<SubscriptionType, Histogram<usize>>,
     /// Number of currently active subscribers split by the subscription type.
     pub active_subscribers: Family<SubscriptionType, Gauge>,
     /// Lifetime of a subscriber of a certain type.
@@ -390,3 +643,60 @@ pub(super) struct MempoolCacheMetrics {
 
 #[vise::register]
 pub(super) static MEMPOOL_CACHE_METRICS: vise::Global<MempoolCacheMetrics> = vise::Global::new();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overriding_web3_error_log_levels() {
+        let internal_err = Web3Error::InternalError(anyhow::anyhow!("oops"));
+        let levels = Web3ErrorLogLevels::default();
+        assert_eq!(levels.level(&internal_err), Some(tracing::Level::ERROR));
+        assert_eq!(levels.level(&Web3Error::NoBlock), None);
+
+        let overrides = HashMap::from([
+            ("internal".to_owned(), tracing::Level::WARN),
+            ("no_block".to_owned(), tracing::Level::DEBUG),
+            ("unknown".to_owned(), tracing::Level::ERROR),
+        ]);
+        let levels = Web3ErrorLogLevels::new(&overrides);
+        assert_eq!(levels.level(&internal_err), Some(tracing::Level::WARN));
+        assert_eq!(
+            levels.level(&Web3Error::NoBlock),
+            Some(tracing::Level::DEBUG)
+        );
+        assert_eq!(levels.level(&Web3Error::TooManyTopics), None);
+    }
+
+    #[test]
+    fn taking_metrics_snapshot() {
+        let metrics = ApiMetrics::default();
+        let levels = Web3ErrorLogLevels::default();
+        metrics.observe_web3_error("eth_getBlockByNumber", &Web3Error::NoBlock, &levels);
+        metrics.observe_web3_error("eth_getBalance", &Web3Error::NoBlock, &levels);
+        metrics.observe_web3_error("eth_getLogs", &Web3Error::TooManyTopics, &levels);
+        metrics.observe_protocol_error("eth_getLogs", -32602, true, None);
+        metrics.ws_open_sessions.inc_by(3);
+        metrics.web3_in_flight_requests_current[&ApiTransportLabel::Http].set(5);
+        metrics.web3_in_flight_requests_current[&ApiTransportLabel::Ws].set(2);
+        // The transaction submission gauge is not a request metric and must not be included.
+        metrics.inflight_tx_submissions.inc_by(1);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(
+            snapshot.web3_errors,
+            BTreeMap::from([("no_block", 2), ("too_many_topics", 1)])
+        );
+        assert_eq!(snapshot.web3_rpc_errors, BTreeMap::from([(-32602, 1)]));
+        assert_eq!(
+            snapshot.web3_in_flight_requests,
+            BTreeMap::from([("HTTP", 5), ("WS", 2)])
+        );
+        assert_eq!(snapshot.ws_open_sessions, 3);
+
+        let snapshot = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(snapshot["web3_errors"]["no_block"], 2);
+        assert_eq!(snapshot["web3_in_flight_requests"]["HTTP"], 5);
+    }
+}
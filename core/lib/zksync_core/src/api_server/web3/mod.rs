@@ -1,4 +1,10 @@
-use std::{collections::HashSet, net::SocketAddr, num::NonZeroU32, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    num::NonZeroU32,
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::Context as _;
 use chrono::NaiveDateTime;
@@ -24,12 +30,14 @@ use zksync_web3_decl::{
     types::Filter,
 };
 
+pub use self::metrics::ApiMetricsSnapshot;
 use self::{
     backend_jsonrpsee::{
-        LimitMiddleware, MetadataMiddleware, MethodTracer, ShutdownMiddleware, TrafficTracker,
+        ConcurrencyLimitMiddleware, LimitMiddleware, MetadataMiddleware, MethodConcurrencyLimits,
+        MethodTracer, ShutdownMiddleware, TrafficTracker,
     },
     mempool_cache::MempoolCache,
-    metrics::API_METRICS,
+    metrics::{MethodNameGuard, Web3ErrorLogLevels, API_METRICS},
     namespaces::{
         DebugNamespace, EnNamespace, EthNamespace, NetNamespace, SnapshotsNamespace, Web3Namespace,
         ZksNamespace,
@@ -67,6 +75,12 @@ const NO_REQUESTS_WAIT_TIMEOUT: Duration = Duration::from_secs(30);
 /// and start gracefully shutting down the server.
 const SHUTDOWN_INTERVAL_WITHOUT_REQUESTS: Duration = Duration::from_millis(500);
 
+/// Takes a point-in-time [snapshot](ApiMetricsSnapshot) of key API server metrics, e.g. to include it
+/// into a diagnostics bundle.
+pub fn api_metrics_snapshot() -> ApiMetricsSnapshot {
+    API_METRICS.snapshot()
+}
+
 /// Represents all kinds of `Filter`.
 #[derive(Debug, Clone)]
 pub(crate) enum TypedFilter {
@@ -127,6 +141,9 @@ struct OptionalApiParams {
     batch_request_size_limit: Option<usize>,
     response_body_size_limit: Option<usize>,
     websocket_requests_per_minute_limit: Option<NonZeroU32>,
+    method_concurrency_limits: HashMap<String, usize>,
+    metrics_method_allowlist: Option<HashSet<String>>,
+    web3_error_log_levels: HashMap<String, tracing::Level>,
     tree_api: Option<Arc<dyn TreeApiClient>>,
     mempool_cache: Option<MempoolCache>,
     pub_sub_events_sender: Option<mpsc::UnboundedSender<PubSubEvent>>,
@@ -160,7 +177,7 @@ pub struct ApiBuilder {
     // Optional params that may or may not be set using builder methods. We treat `namespaces`
     // specially because we want to output a warning if they are not set.
     namespaces: Option<Vec<Namespace>>,
-    method_tracer: Arc<MethodTracer>,
+    method_tracer: Option<Arc<MethodTracer>>,
     optional: OptionalApiParams,
 }
 
@@ -176,7 +193,7 @@ impl ApiBuilder {
             transport: None,
             tx_sender: None,
             namespaces: None,
-            method_tracer: Arc::new(MethodTracer::default()),
+            method_tracer: None,
             optional: OptionalApiParams::default(),
         }
     }
@@ -239,6 +256,30 @@ impl ApiBuilder {
         self
     }
 
+    /// Sets limits on the number of concurrently processed calls for specific methods (e.g., `eth_getLogs`).
+    /// Calls exceeding the limit are rejected with the "server is busy" error. Limits for methods
+    /// not served by the server are ignored.
+    pub fn with_method_concurrency_limits(mut self, limits: HashMap<String, usize>) -> Self {
+        self.optional.method_concurrency_limits = limits;
+        self
+    }
+
+    /// Restricts method names used as metric labels to the specified allowlist. Calls to other methods
+    /// are reported with the `other` method label. By default, all methods served by the server are allowed.
+    pub fn with_metrics_method_allowlist(mut self, allowlist: HashSet<String>) -> Self {
+        self.optional.metrics_method_allowlist = Some(allowlist);
+        self
+    }
+
+    /// Overrides log levels for application errors returned by Web3 methods. Levels are keyed by the error kind
+    /// (e.g., `internal` or `proxy`), which coincides with the `kind` label in error metrics. Overrides are applied
+    /// to the default levels, under which internal errors are logged as errors, proxying errors as warnings,
+    /// and other errors are not logged.
+    pub fn with_web3_error_log_levels(mut self, levels: HashMap<String, tracing::Level>) -> Self {
+        self.optional.web3_error_log_levels = levels;
+        self
+    }
+
     pub fn with_sync_state(mut self, sync_state: SyncState) -> Self {
         self.optional.sync_state = Some(sync_state);
         self
@@ -273,7 +314,7 @@ impl ApiBuilder {
 
     #[cfg(test)]
     fn with_method_tracer(mut self, method_tracer: Arc<MethodTracer>) -> Self {
-        self.method_tracer = method_tracer;
+        self.method_tracer = Some(method_tracer);
         self
     }
 }
@@ -301,7 +342,11 @@ impl ApiBuilder {
                 );
                 Namespace::DEFAULT.to_vec()
             }),
-            method_tracer: self.method_tracer,
+            method_tracer: self.method_tracer.unwrap_or_else(|| {
+                let error_log_levels =
+                    Web3ErrorLogLevels::new(&self.optional.web3_error_log_levels);
+                Arc::new(MethodTracer::new(error_log_levels))
+            }),
             optional: self.optional,
         })
     }
@@ -547,17 +592,27 @@ impl ApiServer {
         let websocket_requests_per_minute_limit = self.optional.websocket_requests_per_minute_limit;
         let subscriptions_limit = self.optional.subscriptions_limit;
         let vm_barrier = self.optional.vm_barrier.clone();
+        let method_concurrency_limits = self.optional.method_concurrency_limits.clone();
+        let metrics_method_allowlist = self.optional.metrics_method_allowlist.clone();
         let health_updater = self.health_updater.clone();
         let method_tracer = self.method_tracer.clone();
 
         let rpc = self
             .build_rpc_module(pub_sub, last_sealed_miniblock)
             .await?;
-        let registered_method_names = Arc::new(rpc.method_names().collect::<HashSet<_>>());
+        let registered_method_names: HashSet<_> = rpc.method_names().collect();
         tracing::debug!(
             "Built RPC module for {transport_str} server with {} methods: {registered_method_names:?}",
             registered_method_names.len()
         );
+        let method_names = Arc::new(MethodNameGuard::new(
+            &registered_method_names,
+            metrics_method_allowlist.as_ref(),
+        ));
+        let method_concurrency_limits = Arc::new(MethodConcurrencyLimits::new(
+            &method_concurrency_limits,
+            &registered_method_names,
+        ));
 
         // Setup CORS.
         let cors = is_http.then(|| {
@@ -573,6 +628,7 @@ impl ApiServer {
         tokio::spawn(
             counter.run_emitter(Duration::from_millis(100), move |count| {
                 API_METRICS.web3_in_flight_requests[&transport_label].observe(count);
+                API_METRICS.web3_in_flight_requests_current[&transport_label].set(count);
                 future::ready(())
             }),
         );
@@ -594,7 +650,15 @@ impl ApiServer {
                 ShutdownMiddleware::new(svc, traffic_tracker_for_middleware.clone())
             })
             .layer_fn(move |svc| {
-                MetadataMiddleware::new(svc, registered_method_names.clone(), method_tracer.clone())
+                MetadataMiddleware::new(
+                    svc,
+                    method_names.clone(),
+                    method_tracer.clone(),
+                    transport_label,
+                )
+            })
+            .layer_fn(move |svc| {
+                ConcurrencyLimitMiddleware::new(svc, method_concurrency_limits.clone())
             })
             .option_layer((!is_http).then(|| {
                 tower::layer::layer_fn(move |svc| {
@@ -239,6 +239,8 @@ impl EthNamespace {
             return Ok(None);
         };
         self.set_block_diff(block_number);
+        self.current_method()
+            .set_block_timestamp(block.timestamp.as_u64());
 
         let transactions = if full_transactions {
             let mut transactions = storage
@@ -2,7 +2,7 @@ use std::{
     future::Future,
     sync::{
         atomic::{AtomicU32, Ordering},
-        Arc,
+        Arc, RwLock,
     },
     time::{Duration, Instant},
 };
@@ -163,7 +163,11 @@ impl InternalApiConfig {
 /// (e.g., for metrics reporting). The value is updated by [`Self::diff()`] and [`Self::diff_with_block_args()`]
 /// and on an interval specified when creating an instance.
 #[derive(Debug, Clone)]
-pub(crate) struct SealedMiniblockNumber(Arc<AtomicU32>);
+pub(crate) struct SealedMiniblockNumber {
+    number: Arc<AtomicU32>,
+    /// Number and timestamp of the last sealed miniblock as of the latest scheduled update.
+    last_timestamp: Arc<RwLock<Option<(L2BlockNumber, u64)>>>,
+}
 
 impl SealedMiniblockNumber {
     /// Creates a handle to the last sealed miniblock number together with a task that will update
@@ -173,7 +177,10 @@ impl SealedMiniblockNumber {
         update_interval: Duration,
         stop_receiver: watch::Receiver<bool>,
     ) -> (Self, impl Future<Output = anyhow::Result<()>>) {
-        let this = Self(Arc::default());
+        let this = Self {
+            number: Arc::default(),
+            last_timestamp: Arc::default(),
+        };
         let number_updater = this.clone();
 
         let update_task = async move {
@@ -184,14 +191,25 @@ impl SealedMiniblockNumber {
                 }
 
                 let mut connection = connection_pool.connection_tagged("api").await.unwrap();
-                let Some(last_sealed_miniblock) =
-                    connection.blocks_dal().get_sealed_l2_block_number().await?
-                else {
+                let last_sealed_header = connection
+                    .blocks_dal()
+                    .get_last_sealed_l2_block_header()
+                    .await?;
+                let last_sealed_miniblock = match &last_sealed_header {
+                    Some(header) => Some(header.number),
+                    // The node may be recovered from a snapshot and have no miniblocks yet
+                    None => connection.blocks_dal().get_sealed_l2_block_number().await?,
+                };
+                drop(connection);
+                let Some(last_sealed_miniblock) = last_sealed_miniblock else {
                     tokio::time::sleep(update_interval).await;
                     continue;
                 };
-                drop(connection);
 
+                if let Some(header) = last_sealed_header {
+                    *number_updater.last_timestamp.write().unwrap() =
+                        Some((header.number, header.timestamp));
+                }
                 number_updater.update(last_sealed_miniblock);
                 tokio::time::sleep(update_interval).await;
             }
@@ -206,11 +224,19 @@ impl SealedMiniblockNumber {
     /// Returns the last sealed miniblock number after the update.
     fn update(&self, maybe_newer_miniblock_number: L2BlockNumber) -> L2BlockNumber {
         let prev_value = self
-            .0
+            .number
             .fetch_max(maybe_newer_miniblock_number.0, Ordering::Relaxed);
         L2BlockNumber(prev_value).max(maybe_newer_miniblock_number)
     }
 
+    /// Returns the timestamp of the specified miniblock if it is the last sealed miniblock as of the latest
+    /// scheduled update. Doesn't query the storage.
+    pub fn timestamp(&self, miniblock_number: L2BlockNumber) -> Option<u64> {
+        let last_timestamp = *self.last_timestamp.read().unwrap();
+        let (number, timestamp) = last_timestamp?;
+        (number == miniblock_number).then_some(timestamp)
+    }
+
     pub fn diff(&self, miniblock_number: L2BlockNumber) -> u32 {
         let sealed_miniblock_number = self.update(miniblock_number);
         sealed_miniblock_number.0.saturating_sub(miniblock_number.0)
@@ -289,12 +315,14 @@ impl RpcState {
         block: api::BlockId,
     ) -> Result<L2BlockNumber, Web3Error> {
         self.start_info.ensure_not_pruned(block, connection).await?;
-        connection
+        let block_number = connection
             .blocks_web3_dal()
             .resolve_block_id(block)
             .await
             .map_err(DalError::generalize)?
-            .ok_or(Web3Error::NoBlock)
+            .ok_or(Web3Error::NoBlock)?;
+        self.record_block_timestamp(block, block_number);
+        Ok(block_number)
     }
 
     /// Resolves the specified block ID to a block number, which is **not** guaranteed to be present in the node storage.
@@ -315,11 +343,17 @@ impl RpcState {
                 Ok(u32::try_from(number).ok().map(L2BlockNumber))
             }
             api::BlockId::Number(api::BlockNumber::Earliest) => Ok(Some(L2BlockNumber(0))),
-            _ => Ok(connection
-                .blocks_web3_dal()
-                .resolve_block_id(block)
-                .await
-                .map_err(DalError::generalize)?),
+            _ => {
+                let block_number = connection
+                    .blocks_web3_dal()
+                    .resolve_block_id(block)
+                    .await
+                    .map_err(DalError::generalize)?;
+                if let Some(block_number) = block_number {
+                    self.record_block_timestamp(block, block_number);
+                }
+                Ok(block_number)
+            }
         }
     }
 
@@ -328,13 +362,34 @@ impl RpcState {
         connection: &mut Connection<'_, Core>,
         block: api::BlockId,
     ) -> Result<BlockArgs, Web3Error> {
-        BlockArgs::new(connection, block, &self.start_info)
+        let block_args = BlockArgs::new(connection, block, &self.start_info)
             .await
             .map_err(|err| match err {
                 BlockArgsError::Pruned(number) => Web3Error::PrunedBlock(number),
                 BlockArgsError::Missing => Web3Error::NoBlock,
                 BlockArgsError::Database(err) => Web3Error::InternalError(err),
-            })
+            })?;
+        self.record_block_timestamp(block, block_args.resolved_block_number());
+        Ok(block_args)
+    }
+
+    /// Records the timestamp of the resolved block for the current method call if `block` is the `latest`,
+    /// `committed` or `finalized` block ID. The timestamp is used to compute the age of the newest served block.
+    /// It is taken from the cached last sealed miniblock, so it is only recorded if the resolved block is that miniblock.
+    fn record_block_timestamp(&self, block: api::BlockId, block_number: L2BlockNumber) {
+        if !matches!(
+            block,
+            api::BlockId::Number(
+                api::BlockNumber::Latest
+                    | api::BlockNumber::Committed
+                    | api::BlockNumber::Finalized
+            )
+        ) {
+            return;
+        }
+        if let Some(timestamp) = self.last_sealed_miniblock.timestamp(block_number) {
+            self.current_method.set_block_timestamp(timestamp);
+        }
     }
 
     pub async fn resolve_filter_block_number(
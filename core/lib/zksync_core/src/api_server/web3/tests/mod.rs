@@ -989,6 +989,7 @@ impl HttpTest for RpcCallsTracingTest {
             Some(api::BlockId::Number(api::BlockNumber::Latest))
         );
         assert_eq!(calls[0].metadata.block_diff, Some(0));
+        assert!(calls[0].metadata.block_timestamp.is_some());
 
         let block_number = api::BlockNumber::Number(1.into());
         client.get_block_by_number(block_number, false).await?;
@@ -1002,6 +1003,7 @@ impl HttpTest for RpcCallsTracingTest {
             Some(api::BlockId::Number(block_number))
         );
         assert_eq!(calls[0].metadata.block_diff, None);
+        assert_eq!(calls[0].metadata.block_timestamp, None);
 
         // Check protocol-level errors.
         client
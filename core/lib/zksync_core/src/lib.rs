@@ -1299,6 +1299,10 @@ async fn run_http_api(
         .await
         .context("failed to build last_miniblock_pool")?;
 
+    let method_concurrency_limits = api_config
+        .web3_json_rpc
+        .method_concurrency_limits()
+        .context("invalid method concurrency limits")?;
     let mut api_builder =
         web3::ApiBuilder::jsonrpsee_backend(internal_api.clone(), replica_connection_pool)
             .http(api_config.web3_json_rpc.http_port)
@@ -1306,6 +1310,7 @@ async fn run_http_api(
             .with_filter_limit(api_config.web3_json_rpc.filters_limit())
             .with_batch_request_size_limit(api_config.web3_json_rpc.max_batch_request_size())
             .with_response_body_size_limit(api_config.web3_json_rpc.max_response_body_size())
+            .with_method_concurrency_limits(method_concurrency_limits)
             .with_tx_sender(tx_sender)
             .with_vm_barrier(vm_barrier)
             .with_mempool_cache(mempool_cache)
@@ -1360,6 +1365,10 @@ async fn run_ws_api(
     let mut namespaces = Namespace::DEFAULT.to_vec();
     namespaces.push(Namespace::Snapshots);
 
+    let method_concurrency_limits = api_config
+        .web3_json_rpc
+        .method_concurrency_limits()
+        .context("invalid method concurrency limits")?;
     let mut api_builder =
         web3::ApiBuilder::jsonrpsee_backend(internal_api.clone(), replica_connection_pool)
             .ws(api_config.web3_json_rpc.ws_port)
@@ -1368,6 +1377,7 @@ async fn run_ws_api(
             .with_subscriptions_limit(api_config.web3_json_rpc.subscriptions_limit())
             .with_batch_request_size_limit(api_config.web3_json_rpc.max_batch_request_size())
             .with_response_body_size_limit(api_config.web3_json_rpc.max_response_body_size())
+            .with_method_concurrency_limits(method_concurrency_limits)
             .with_websocket_requests_per_minute_limit(
                 api_config
                     .web3_json_rpc
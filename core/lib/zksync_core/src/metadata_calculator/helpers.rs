@@ -84,6 +84,7 @@ fn create_db_sync(config: &MetadataCalculatorConfig) -> anyhow::Result<RocksDBWr
         multi_get_chunk_size,
         memtable_capacity,
         stalled_writes_timeout,
+        enable_block_cache_stats,
         ..
     } = config;
 
@@ -91,7 +92,7 @@ fn create_db_sync(config: &MetadataCalculatorConfig) -> anyhow::Result<RocksDBWr
         "Initializing Merkle tree database at `{path}` (max open files: {max_open_files:?}) with {multi_get_chunk_size} multi-get chunk size, \
          {block_cache_capacity}B block cache (indices & filters included: {include_indices_and_filters_in_block_cache:?}), \
          {memtable_capacity}B memtable capacity, \
-         {stalled_writes_timeout:?} stalled writes timeout",
+         {stalled_writes_timeout:?} stalled writes timeout, block cache stats enabled: {enable_block_cache_stats:?}",
         path = path.display()
     );
 
@@ -103,6 +104,7 @@ fn create_db_sync(config: &MetadataCalculatorConfig) -> anyhow::Result<RocksDBWr
             large_memtable_capacity: Some(memtable_capacity),
             stalled_writes_retries: StalledWritesRetries::new(stalled_writes_timeout),
             max_open_files,
+            enable_statistics: enable_block_cache_stats,
         },
     )?;
     if cfg!(test) {
@@ -220,6 +222,10 @@ impl AsyncTree {
     pub fn revert_logs(&mut self, last_l1_batch_to_keep: L1BatchNumber) {
         self.as_mut().revert_logs(last_l1_batch_to_keep);
     }
+
+    pub fn report_block_cache_stats(&self) {
+        self.as_ref().report_block_cache_stats();
+    }
 }
 
 /// Async version of [`ZkSyncTreeReader`].
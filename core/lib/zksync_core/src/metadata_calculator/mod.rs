@@ -60,6 +60,9 @@ pub struct MetadataCalculatorConfig {
     pub memtable_capacity: usize,
     /// Timeout to wait for the Merkle tree database to run compaction on stalled writes.
     pub stalled_writes_timeout: Duration,
+    /// Whether to collect RocksDB statistics and report block cache hits and misses for the tree. Collecting statistics
+    /// has a runtime overhead for all RocksDB operations, so it is disabled by default.
+    pub enable_block_cache_stats: bool,
 }
 
 impl MetadataCalculatorConfig {
@@ -78,6 +81,7 @@ impl MetadataCalculatorConfig {
             include_indices_and_filters_in_block_cache: false,
             memtable_capacity: merkle_tree_config.memtable_capacity(),
             stalled_writes_timeout: merkle_tree_config.stalled_writes_timeout(),
+            enable_block_cache_stats: false,
         }
     }
 }
@@ -300,6 +300,7 @@ impl TreeUpdater {
                 tracing::trace!(
                     "Metadata calculator (next L1 batch: #{next_l1_batch_to_seal}) made progress from #{snapshot}"
                 );
+                self.tree.report_block_cache_stats();
                 future::ready(()).right_future()
             };
 